@@ -1,10 +1,13 @@
 #![allow(clippy::manual_unwrap_or_default)]
+mod shell_parser;
+
 use darling::ast::Data;
 use darling::util::Ignored;
-use darling::{FromDeriveInput, FromField};
+use darling::{FromDeriveInput, FromField, FromMeta};
 use itertools::Itertools;
 use proc_macro2::{Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned};
+use shell_parser::{implement_shell_parser, ShellParserInput};
 use syn::spanned::Spanned;
 use syn::{parse_macro_input, DeriveInput, Generics, Ident, Index, Type};
 
@@ -17,6 +20,7 @@ pub fn display_cli(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             let headers = implement_headers(&input);
             //let headers_with_mode = implement_headers(&input, implement_header_with_mode);
             let format_value = implement_format_value(&input);
+            let description = implement_description(&input);
 
             let name = input.ident;
             let type_params = input.generics.type_params();
@@ -26,22 +30,40 @@ pub fn display_cli(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 impl <#(#type_params,)*> shellui::format::ObjectFormatter for #name #ty_generics #where_clause {
                     type Header = &'static str;
                     type Mode = &'static str;
-                    type Output = shellui::format::Message;
+                    type Output<'a> = shellui::format::Message where Self: 'a;
 
                     fn headers(mode: Option<Self::Mode>) -> Vec<Self::Header> {
                         #headers
                     }
 
-                    fn format_value(&self, mode: Option<Self::Mode>, header: &Self::Header) -> Self::Output {
+                    fn format_value(&self, mode: Option<Self::Mode>, header: &Self::Header) -> Self::Output<'_> {
                         #format_value
                     }
+
+                    fn description(header: &Self::Header) -> Option<&'static str> {
+                        #description
+                    }
                 }
             }
         }
-        Err(error) => {
-            let message = error.to_string();
-            quote_spanned! { raw.ident.span() => compile_error!(#message); }
-        }
+        Err(error) => error.write_errors(),
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+/// Implement `shellui::ShellParser`
+///
+/// The context type and the function running commands are set with
+/// `#[shell_parser(context = "Context", run = "run_command")]`. The subcommand
+/// field is marked with `#[shell_parser(command)]`, and the optional flattened
+/// `GlobalOptions` field with `#[shell_parser(global_options)]`.
+#[proc_macro_derive(ShellParser, attributes(shell_parser))]
+pub fn shell_parser(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let raw = parse_macro_input!(input as DeriveInput);
+    let expanded = match ShellParserInput::from_derive_input(&raw) {
+        Ok(input) => implement_shell_parser(input),
+        Err(error) => error.write_errors(),
     };
 
     proc_macro::TokenStream::from(expanded)
@@ -56,7 +78,7 @@ struct FormatterInput {
 }
 
 #[derive(Debug, FromField)]
-#[darling(attributes(object_formatter))]
+#[darling(attributes(object_formatter), and_then = FormatterField::validate)]
 struct FormatterField {
     ident: Option<Ident>,
     ty: Type,
@@ -71,6 +93,56 @@ struct FormatterField {
     level: Option<String>,
     #[darling(default)]
     with: Option<String>,
+    #[darling(default)]
+    description: Option<String>,
+    #[darling(default)]
+    color: Option<ColumnColor>,
+}
+
+impl FormatterField {
+    /// Reject attributes that would be ignored
+    fn validate(self) -> darling::Result<Self> {
+        let conflict = match (&self.color, &self.with, &self.level) {
+            (Some(_), Some(_), _) => "with",
+            (Some(_), None, Some(_)) => "level",
+            _ => return Ok(self),
+        };
+        let message = format!("`color` cannot be used with `{conflict}`");
+        let error = darling::Error::custom(message);
+        Err(match &self.ident {
+            Some(ident) => error.with_span(ident),
+            None => error.with_span(&self.ty),
+        })
+    }
+}
+
+/// Fixed color of a column, like `cyan` or `bright blue`
+#[derive(Debug)]
+struct ColumnColor(Ident);
+
+impl FromMeta for ColumnColor {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        let variant = match value.to_lowercase().as_str() {
+            "black" => "Black",
+            "red" => "Red",
+            "green" => "Green",
+            "yellow" => "Yellow",
+            "blue" => "Blue",
+            "magenta" | "purple" => "Magenta",
+            "cyan" => "Cyan",
+            "white" => "White",
+            "bright black" => "BrightBlack",
+            "bright red" => "BrightRed",
+            "bright green" => "BrightGreen",
+            "bright yellow" => "BrightYellow",
+            "bright blue" => "BrightBlue",
+            "bright magenta" => "BrightMagenta",
+            "bright cyan" => "BrightCyan",
+            "bright white" => "BrightWhite",
+            _ => return Err(darling::Error::unknown_value(value)),
+        };
+        Ok(ColumnColor(format_ident!("{variant}")))
+    }
 }
 
 fn implement_headers(input: &FormatterInput) -> TokenStream {
@@ -159,7 +231,15 @@ fn implement_format_single_value(index: usize, field: &FormatterField) -> Option
             let with = field.with.as_ref();
             let access = format_access(index, field);
 
-            if let Some(with) = with {
+            if let Some(ColumnColor(color)) = &field.color {
+                let value = quote! {
+                    if *header == #header {
+                        shellui::format::Message::colored(&#access, shellui::format::Color::#color)
+
+                    }
+                };
+                Some(value)
+            } else if let Some(with) = with {
                 let with = format_ident!("{with}");
                 let value = quote! {
                     if *header == #header {
@@ -184,6 +264,42 @@ fn implement_format_single_value(index: usize, field: &FormatterField) -> Option
     }
 }
 
+fn implement_description(input: &FormatterInput) -> TokenStream {
+    let data = input.data.as_ref();
+    let struct_data = data.take_struct();
+    let descriptions = struct_data
+        .iter()
+        .flat_map(|i| i.fields.iter().copied())
+        .filter_map(implement_field_description);
+    quote! {
+        #(#descriptions)*
+        None
+    }
+}
+
+fn implement_field_description(field: &FormatterField) -> Option<TokenStream> {
+    match (&field.inline, &field.header, &field.description) {
+        (true, None, _) => {
+            let ty = &field.ty;
+            let value = quote! {
+                if let Some(description) = #ty::description(header) {
+                    return Some(description);
+                }
+            };
+            Some(value)
+        }
+        (false, Some(header), Some(description)) => {
+            let value = quote! {
+                if *header == #header {
+                    return Some(#description);
+                }
+            };
+            Some(value)
+        }
+        _ => None,
+    }
+}
+
 fn format_access(index: usize, field: &FormatterField) -> TokenStream {
     if let Some(ident) = &field.ident {
         let ident = ident.clone();
@@ -0,0 +1,94 @@
+use darling::ast::Data;
+use darling::util::Ignored;
+use darling::{FromDeriveInput, FromField};
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{GenericArgument, Ident, Path, PathArguments, Type};
+
+#[derive(Debug, FromDeriveInput)]
+#[darling(attributes(shell_parser), supports(struct_named))]
+pub struct ShellParserInput {
+    ident: Ident,
+    data: Data<Ignored, ShellParserField>,
+    context: Type,
+    run: Path,
+}
+
+#[derive(Debug, FromField)]
+#[darling(attributes(shell_parser))]
+struct ShellParserField {
+    ident: Option<Ident>,
+    ty: Type,
+
+    #[darling(default)]
+    command: bool,
+    #[darling(default)]
+    global_options: bool,
+}
+
+pub fn implement_shell_parser(input: ShellParserInput) -> TokenStream {
+    let name = &input.ident;
+    let context = &input.context;
+    let run = &input.run;
+    let fields = input.data.take_struct().map(|data| data.fields);
+    let fields = fields.unwrap_or_default();
+
+    let Some(command) = fields.iter().find(|field| field.command) else {
+        return quote_spanned! { name.span() => compile_error!("Missing #[shell_parser(command)] field"); };
+    };
+    let command_ident = &command.ident;
+    let (commands, try_get_command) = match option_inner_type(&command.ty) {
+        Some(inner) => (inner, quote! { self.#command_ident }),
+        None => (&command.ty, quote! { Some(self.#command_ident) }),
+    };
+
+    let global_options = fields
+        .iter()
+        .find(|field| field.global_options)
+        .map(|field| {
+            let ident = &field.ident;
+            quote! {
+                fn global_options(&self) -> Option<&shellui::options::GlobalOptions> {
+                    Some(&self.#ident)
+                }
+            }
+        });
+
+    quote! {
+        impl shellui::ShellParser for #name {
+            type Context = #context;
+            type Commands = #commands;
+
+            fn try_get_command(self) -> Option<Self::Commands> {
+                #try_get_command
+            }
+
+            #global_options
+
+            fn run_command(
+                context: &mut Self::Context,
+                command: &Self::Commands,
+            ) -> shellui::errors::ShellUiResult<()> {
+                #run(context, command)
+            }
+        }
+    }
+}
+
+/// Get `T` from `Option<T>`
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
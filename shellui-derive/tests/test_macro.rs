@@ -1,8 +1,9 @@
-use shellui::format::{Message, ObjectFormatter};
+use shellui::format::{Color, Message, ObjectFormatter};
+use std::path::PathBuf;
 
 #[derive(ObjectFormatter)]
 struct Simple {
-    #[object_formatter(header = "Id")]
+    #[object_formatter(header = "Id", description = "Unique identifier")]
     id: String,
     #[object_formatter(header = "Label")]
     label: String,
@@ -35,7 +36,7 @@ fn format_port(port: &u32) -> Message {
 
 #[derive(ObjectFormatter)]
 struct Coordinates {
-    #[object_formatter(header = "Host", level = "success")]
+    #[object_formatter(header = "Host", level = "success", description = "Host name")]
     host: String,
     #[object_formatter(header = "Port", with = "format_port")]
     port: u32,
@@ -52,6 +53,14 @@ struct NoField {
     _field2: String,
     _field3: String,
 }
+#[derive(ObjectFormatter)]
+struct Colored {
+    #[object_formatter(header = "Name", color = "cyan")]
+    name: String,
+    #[object_formatter(header = "Kind", color = "bright blue")]
+    kind: String,
+}
+
 #[derive(ObjectFormatter)]
 struct Tuple(
     #[object_formatter(header = "Id")] String,
@@ -61,6 +70,14 @@ struct Tuple(
 #[derive(ObjectFormatter)]
 struct Unit;
 
+#[derive(ObjectFormatter)]
+struct Primitives {
+    #[object_formatter(header = "Ratio")]
+    ratio: f64,
+    #[object_formatter(header = "Path")]
+    path: PathBuf,
+}
+
 #[test]
 fn test_derive() {
     let headers = vec![
@@ -95,6 +112,29 @@ fn test_derive() {
     assert_eq!(value.format_value(None, &"Value"), Message::new("123"));
 }
 
+#[test]
+fn test_derive_description() {
+    assert_eq!(Simple::description(&"Id"), Some("Unique identifier"));
+    assert_eq!(Simple::description(&"Host"), Some("Host name"));
+    assert_eq!(Simple::description(&"Label"), None);
+}
+
+#[test]
+fn test_derive_color() {
+    let value = Colored {
+        name: "name".to_string(),
+        kind: "kind".to_string(),
+    };
+    assert_eq!(
+        value.format_value(None, &"Name"),
+        Message::colored("name", Color::Cyan)
+    );
+    assert_eq!(
+        value.format_value(None, &"Kind"),
+        Message::colored("kind", Color::BrightBlue)
+    );
+}
+
 #[test]
 fn test_derive_tuple() {
     let headers = vec!["Id".to_string(), "Label".to_string()];
@@ -104,3 +144,31 @@ fn test_derive_tuple() {
     assert_eq!(value.format_value(None, &"Id"), Message::new("id"));
     assert_eq!(value.format_value(None, &"Label"), Message::new("label"));
 }
+
+#[test]
+fn test_derive_no_field() {
+    assert!(NoField::default_headers().is_empty());
+
+    let value = NoField {
+        _field1: "1".to_string(),
+        _field2: "2".to_string(),
+        _field3: "3".to_string(),
+    };
+    assert_eq!(value.format_value(None, &"Field1"), Message::default());
+}
+
+#[test]
+fn test_derive_unit() {
+    assert!(Unit::default_headers().is_empty());
+    assert_eq!(Unit.format_value(None, &"Field"), Message::default());
+}
+
+#[test]
+fn test_derive_primitives() {
+    let value = Primitives {
+        ratio: 0.25,
+        path: PathBuf::from("/tmp"),
+    };
+    assert_eq!(value.format_value(None, &"Ratio"), Message::new("0.25"));
+    assert_eq!(value.format_value(None, &"Path"), Message::new("/tmp"));
+}
@@ -0,0 +1,46 @@
+use clap::{Parser, Subcommand};
+use shellui::errors::ShellUiResult;
+use shellui::options::GlobalOptions;
+use shellui::{Context, ShellParser};
+use std::io::Result;
+use std::path::PathBuf;
+
+struct TestContext;
+
+impl Context for TestContext {
+    fn new() -> Result<Self> {
+        Ok(TestContext)
+    }
+
+    fn history_path(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+#[derive(Parser, ShellParser)]
+#[shell_parser(context = "TestContext", run = "run")]
+struct Cli {
+    #[command(flatten)]
+    #[shell_parser(global_options)]
+    options: GlobalOptions,
+    #[command(subcommand)]
+    #[shell_parser(command)]
+    command: Option<Commands>,
+}
+
+#[derive(Debug, Eq, PartialEq, Subcommand)]
+enum Commands {
+    Test,
+}
+
+fn run(_context: &mut TestContext, _command: &Commands) -> ShellUiResult<()> {
+    Ok(())
+}
+
+#[test]
+fn test() {
+    let cli = Cli::try_parse_from(["cli", "-v", "test"]).unwrap();
+    assert_eq!(cli.global_options().map(|options| options.verbose), Some(1));
+    assert_eq!(cli.try_get_command(), Some(Commands::Test));
+    assert!(Cli::run_command(&mut TestContext, &Commands::Test).is_ok());
+}
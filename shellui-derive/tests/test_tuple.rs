@@ -1,4 +1,4 @@
-use shellui::format::ObjectFormatter;
+use shellui::format::{AsFormatted, ObjectFormatter};
 
 #[derive(ObjectFormatter)]
 struct Tuple(
@@ -7,4 +7,7 @@ struct Tuple(
 );
 
 #[test]
-fn test() {}
+fn test() {
+    let value = Tuple("id".to_string(), "label".to_string());
+    assert_eq!(value.format_value(None, &"Label").as_unformatted(), "label");
+}
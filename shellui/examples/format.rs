@@ -5,7 +5,7 @@ use std::io::{Error, Result};
 
 #[derive(ObjectFormatter)]
 struct Simple {
-    #[object_formatter(header = "Id")]
+    #[object_formatter(header = "Id", description = "Unique identifier")]
     id: String,
     #[object_formatter(header = "Status", level = "info")]
     status: String,
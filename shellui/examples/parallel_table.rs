@@ -0,0 +1,66 @@
+//! Benchmark of the parallel table formatting against the serial one
+//!
+//! Run with `cargo run --release --example parallel_table --features parallel`.
+use shellui::format::{Message, PrintTable, PrintTableParallel};
+use shellui_derive::ObjectFormatter;
+use std::time::{Duration, Instant};
+
+const ROWS: usize = 20_000;
+const RUNS: u32 = 5;
+
+#[derive(ObjectFormatter)]
+struct Entry {
+    #[object_formatter(header = "Id")]
+    id: usize,
+    #[object_formatter(header = "Payload", with = "format_payload")]
+    payload: String,
+}
+
+/// Expensive formatter, parsing the payload as JSON
+fn format_payload(payload: &str) -> Message {
+    let value = serde_json::from_str::<serde_json::Value>(payload).unwrap();
+    let total = value["values"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_u64)
+        .sum::<u64>();
+    Message::info(format!(
+        "{} ({total})",
+        value["name"].as_str().unwrap_or_default()
+    ))
+}
+
+fn measure<F>(f: F) -> Duration
+where
+    F: Fn() -> Vec<String>,
+{
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        assert_eq!(f().len(), ROWS + 1);
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let entries = (0..ROWS)
+        .map(|id| {
+            let values = (0..50).map(|i| (id + i).to_string()).collect::<Vec<_>>();
+            let payload = format!(
+                r#"{{"name": "entry {id}", "values": [{}]}}"#,
+                values.join(", ")
+            );
+            Entry { id, payload }
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        entries.format_table_parallel(None),
+        entries.format_table(None)
+    );
+    let serial = measure(|| entries.format_table(None));
+    let parallel = measure(|| entries.format_table_parallel(None));
+    println!("{ROWS} rows, {} threads", rayon::current_num_threads());
+    println!("serial:   {serial:?}");
+    println!("parallel: {parallel:?}");
+}
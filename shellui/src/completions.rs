@@ -0,0 +1,69 @@
+//! System shell completions
+//!
+//! Generate completion scripts for the application CLI, so that
+//! completion is also available outside of the interactive shell.
+use crate::format::print_stdout;
+use clap::{Args, CommandFactory};
+pub use clap_complete::Shell;
+
+/// Arguments of a `completions` subcommand
+///
+/// Add it to the application subcommands, and call `print` when running it.
+#[derive(Args, Clone, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+impl CompletionsArgs {
+    /// Print the completion script of the application CLI `T`
+    pub fn print<T>(&self)
+    where
+        T: CommandFactory,
+    {
+        print_completions::<T>(self.shell);
+    }
+}
+
+/// Print the completion script of the application CLI `T`
+pub fn print_completions<T>(shell: Shell)
+where
+    T: CommandFactory,
+{
+    let mut command = T::command();
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, name, &mut script);
+    print_stdout(String::from_utf8_lossy(&script).trim_end());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Parser, Subcommand};
+
+    #[derive(Parser)]
+    #[command(name = "test")]
+    struct TestArgs {
+        #[command(subcommand)]
+        command: Option<TestCommand>,
+    }
+
+    #[derive(Subcommand)]
+    enum TestCommand {
+        Completions(CompletionsArgs),
+    }
+
+    #[test]
+    fn test_completions_args() {
+        let args = TestArgs::try_parse_from(["test", "completions", "zsh"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(TestCommand::Completions(CompletionsArgs {
+                shell: Shell::Zsh
+            }))
+        ));
+        assert!(TestArgs::try_parse_from(["test", "completions", "unknown"]).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Configuration
+//!
+//! Typed configuration stored as TOML, by default in the configuration
+//! directory of the application, like `~/.config/<app>/config.toml`.
+//! Provided to shellui with `Context::config`, it can be edited with
+//! the `config get`, `config set` and `config list` builtins.
+use crate::errors::{WithContext, WithContextError};
+use crate::paths;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Configuration file
+#[derive(Clone, Debug)]
+pub struct Config {
+    path: PathBuf,
+    table: Table,
+}
+
+impl Config {
+    /// Load the configuration of an application, from its configuration directory
+    pub fn load(app_name: &str) -> Result<Self> {
+        Config::load_from(paths::for_app(app_name)?.config_path())
+    }
+
+    /// Load the configuration from a file, that might not exist yet
+    pub fn load_from<P>(path: P) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let table = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .parse::<Table>()
+                .with_context(format!("Invalid configuration {}", path.display()))?,
+            Err(error) if error.kind() == ErrorKind::NotFound => Table::new(),
+            Err(error) => {
+                return Err(error.with_context(format!("Could not read {}", path.display())))
+            }
+        };
+        Ok(Config { path, table })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(format!("Could not create {}", parent.display()))?;
+        }
+        let content = toml::to_string_pretty(&self.table).map_err(Error::other)?;
+        fs::write(&self.path, content)
+            .with_context(format!("Could not write {}", self.path.display()))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get a value, with a dotted key like `section.key`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        let mut keys = key.split('.');
+        let mut value = self.table.get(keys.next()?)?;
+        for key in keys {
+            value = value.as_table()?.get(key)?;
+        }
+        Some(value)
+    }
+
+    /// Set a value, with a dotted key like `section.key`
+    ///
+    /// The value is parsed as a TOML value, like `true` or `[1, 2]`,
+    /// and is used as a string otherwise.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = format!("value = {value}")
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| Value::String(value.to_string()));
+
+        let (last, parents) = match key.rsplit_once('.') {
+            Some((parents, last)) => (last, Some(parents)),
+            None => (key, None),
+        };
+        let mut table = &mut self.table;
+        for parent in parents.into_iter().flat_map(|parents| parents.split('.')) {
+            table = table
+                .entry(parent)
+                .or_insert_with(|| Value::Table(Table::new()))
+                .as_table_mut()
+                .with_context(format!("{parent} is not a section"))?;
+        }
+        table.insert(last.to_string(), value);
+        Ok(())
+    }
+
+    /// List all the values, with their dotted keys
+    pub fn list(&self) -> Vec<(String, &Value)> {
+        let mut values = Vec::new();
+        list_values(&self.table, "", &mut values);
+        values
+    }
+
+    /// Deserialize the configuration
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.table
+            .clone()
+            .try_into()
+            .with_context(format!("Invalid configuration {}", self.path.display()))
+    }
+
+    /// Replace the configuration with a serialized value
+    pub fn serialize<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: Serialize,
+    {
+        self.table = Table::try_from(value).map_err(Error::other)?;
+        Ok(())
+    }
+}
+
+fn list_values<'a>(table: &'a Table, prefix: &str, values: &mut Vec<(String, &'a Value)>) {
+    for (key, value) in table {
+        let key = format!("{prefix}{key}");
+        match value {
+            Value::Table(table) => list_values(table, &format!("{key}."), values),
+            _ => values.push((key, value)),
+        }
+    }
+}
+
+/// Display a value, without quotes for strings
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_dir;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+    struct TestConfig {
+        name: String,
+        server: TestServer,
+    }
+
+    #[derive(Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+    struct TestServer {
+        port: u16,
+        secure: bool,
+    }
+
+    #[test]
+    fn test_config() {
+        let path = test_dir("config").join("config.toml");
+
+        let mut config = Config::load_from(&path).unwrap();
+        assert!(config.list().is_empty());
+        config.set("name", "test").unwrap();
+        config.set("server.port", "8080").unwrap();
+        config.set("server.secure", "true").unwrap();
+        assert!(config.set("name.other", "value").is_err());
+        config.save().unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.get("server.port"), Some(&Value::Integer(8080)));
+        assert_eq!(display_value(config.get("name").unwrap()), "test");
+        assert_eq!(
+            config
+                .list()
+                .into_iter()
+                .map(|(key, value)| format!("{key} = {value}"))
+                .collect::<Vec<_>>(),
+            vec![
+                "name = \"test\"",
+                "server.port = 8080",
+                "server.secure = true"
+            ]
+        );
+        assert_eq!(
+            config.deserialize::<TestConfig>().unwrap(),
+            TestConfig {
+                name: "test".to_string(),
+                server: TestServer {
+                    port: 8080,
+                    secure: true
+                }
+            }
+        );
+    }
+}
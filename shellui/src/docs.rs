@@ -0,0 +1,126 @@
+//! Reference documentation
+//!
+//! Render the command tree of the application, including the shell
+//! builtins, to man pages or to a markdown reference.
+use crate::shell::builtin_commands;
+use clap::{Args, Command, CommandFactory, Subcommand};
+use clap_mangen::Man;
+use std::fmt::Write;
+use std::io::{stdout, Result};
+use std::path::PathBuf;
+
+/// Arguments of a `docs` subcommand
+///
+/// Add it to the application subcommands, and call `run` when running it.
+#[derive(Args, Clone, Debug)]
+pub struct DocsArgs {
+    #[command(subcommand)]
+    pub format: DocsFormat,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum DocsFormat {
+    /// Render man pages
+    Man {
+        /// Directory to write a man page per command to, instead of printing the main page
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+    /// Render a markdown reference
+    Markdown,
+}
+
+impl DocsArgs {
+    /// Render the documentation of the application CLI `T`
+    pub fn run<T>(&self) -> Result<()>
+    where
+        T: CommandFactory,
+    {
+        let command = reference_command::<T>();
+        match &self.format {
+            DocsFormat::Man {
+                out_dir: Some(out_dir),
+            } => clap_mangen::generate_to(command, out_dir),
+            DocsFormat::Man { out_dir: None } => Man::new(command).render(&mut stdout()),
+            DocsFormat::Markdown => {
+                print!("{}", render_markdown(&command));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Command tree of the application CLI `T`, with the shell builtins
+pub fn reference_command<T>() -> Command
+where
+    T: CommandFactory,
+{
+    T::command().subcommands(builtin_commands())
+}
+
+/// Render a command tree to markdown
+pub fn render_markdown(command: &Command) -> String {
+    let mut output = String::new();
+    write_markdown(&mut output, command, &[]).unwrap();
+    output
+}
+
+fn write_markdown(output: &mut String, command: &Command, parents: &[&str]) -> std::fmt::Result {
+    let mut path = parents.to_vec();
+    path.push(command.get_name());
+    let title = path.join(" ");
+
+    writeln!(output, "{} `{title}`\n", "#".repeat(path.len().min(6)))?;
+    if let Some(about) = command.get_long_about().or(command.get_about()) {
+        writeln!(output, "{about}\n")?;
+    }
+    let usage = command.clone().bin_name(&title).render_usage();
+    writeln!(output, "```\n{usage}\n```\n")?;
+
+    let args = command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .collect::<Vec<_>>();
+    if !args.is_empty() {
+        for arg in args {
+            let name = match (arg.get_long(), arg.get_short()) {
+                (Some(long), Some(short)) => format!("-{short}, --{long}"),
+                (Some(long), None) => format!("--{long}"),
+                (None, Some(short)) => format!("-{short}"),
+                (None, None) => format!("<{}>", arg.get_id()),
+            };
+            match arg.get_help() {
+                Some(help) => writeln!(output, "- `{name}`: {help}")?,
+                None => writeln!(output, "- `{name}`")?,
+            }
+        }
+        writeln!(output)?;
+    }
+
+    for subcommand in command.get_subcommands().filter(|c| !c.is_hide_set()) {
+        write_markdown(output, subcommand, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Arg;
+
+    #[test]
+    fn test_render_markdown() {
+        let command = Command::new("test").about("Test CLI").subcommand(
+            Command::new("run")
+                .about("Run")
+                .arg(Arg::new("name").help("Name"))
+                .arg(Arg::new("force").long("force").short('f')),
+        );
+        assert_eq!(
+            render_markdown(&command),
+            "# `test`\n\nTest CLI\n\n```\nUsage: test [COMMAND]\n```\n\n\
+             ## `test run`\n\nRun\n\n```\nUsage: test run [OPTIONS] [name]\n```\n\n\
+             - `<name>`: Name\n- `-f, --force`\n\n"
+        );
+    }
+}
@@ -1,6 +1,17 @@
+use crate::shell::active_config;
+use inquire::InquireError;
+use serde_json::{json, Value};
+#[cfg(feature = "backtrace")]
+use std::backtrace::{Backtrace, BacktraceStatus};
 use std::error::Error as StdError;
 use std::fmt;
 use std::io::{Error, ErrorKind};
+#[cfg(feature = "backtrace")]
+use std::panic::Location;
+#[cfg(feature = "backtrace")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
 use thiserror::Error;
 
 pub type ShellUiResult<T> = Result<T, ShellUiError>;
@@ -13,6 +24,20 @@ pub enum ShellUiError {
     Warning(String),
     #[error("Interrupt")]
     Interrupt,
+    #[error("{}", .1)]
+    WithCode(u8, Error),
+    #[error("{} errors occurred", reported(.0).count())]
+    Multiple(Vec<ShellUiError>),
+    /// Transient error, that can be retried
+    #[error(transparent)]
+    Retryable(Box<ShellUiError>),
+}
+
+/// Errors of [`ShellUiError::Multiple`] that are reported, skipping interrupts
+pub(crate) fn reported(errors: &[ShellUiError]) -> impl Iterator<Item = &ShellUiError> {
+    errors
+        .iter()
+        .filter(|error| !matches!(error, ShellUiError::Interrupt))
 }
 
 impl From<Error> for ShellUiError {
@@ -24,6 +49,20 @@ impl From<Error> for ShellUiError {
     }
 }
 
+impl From<InquireError> for ShellUiError {
+    fn from(error: InquireError) -> Self {
+        match error {
+            InquireError::NotTTY => ShellUiError::Error(Error::other("Not a TTY")),
+            InquireError::InvalidConfiguration(error) => ShellUiError::Error(Error::other(error)),
+            InquireError::IO(error) => error.into(),
+            InquireError::OperationCanceled | InquireError::OperationInterrupted => {
+                ShellUiError::Interrupt
+            }
+            InquireError::Custom(error) => ShellUiError::Error(Error::other(error)),
+        }
+    }
+}
+
 impl ShellUiError {
     pub fn warning<S>(message: S) -> Self
     where
@@ -35,6 +74,242 @@ impl ShellUiError {
     pub fn interrupt() -> Self {
         ShellUiError::Interrupt
     }
+
+    /// Error with a specific exit code
+    ///
+    /// The exit code is used as the process exit status in CLI mode.
+    pub fn with_code<E>(code: u8, error: E) -> Self
+    where
+        E: Into<Error>,
+    {
+        ShellUiError::WithCode(code, error.into())
+    }
+
+    /// Transient error
+    ///
+    /// The command is run again if a `RetryPolicy` is configured. Any
+    /// error can be retryable, including errors with an exit code.
+    pub fn retryable<E>(error: E) -> Self
+    where
+        E: Into<ShellUiError>,
+    {
+        ShellUiError::Retryable(Box::new(error.into()))
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ShellUiError::Retryable(_))
+    }
+
+    /// Machine-readable code of the error, if any
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            ShellUiError::Error(error) | ShellUiError::WithCode(_, error) => error_code(error),
+            ShellUiError::Retryable(error) => error.code(),
+            ShellUiError::Warning(_) | ShellUiError::Interrupt | ShellUiError::Multiple(_) => None,
+        }
+    }
+
+    /// Machine-readable representation of the error
+    ///
+    /// Formatted as `{"error": ..., "causes": [...], "code": ...}`.
+    pub fn as_json(&self) -> Value {
+        let causes = match self {
+            ShellUiError::Retryable(error) => return error.as_json(),
+            ShellUiError::Error(error) | ShellUiError::WithCode(_, error) => {
+                let mut causes = Vec::new();
+                let mut current = error.source();
+                while let Some(error) = current {
+                    causes.push(error.to_string());
+                    current = error.source();
+                }
+                causes
+            }
+            ShellUiError::Warning(_) | ShellUiError::Interrupt | ShellUiError::Multiple(_) => {
+                Vec::new()
+            }
+        };
+        let mut json = json!({
+            "error": self.to_string(),
+            "causes": causes,
+            "code": self.code(),
+        });
+        if let ShellUiError::Multiple(errors) = self {
+            json["errors"] = reported(errors).map(ShellUiError::as_json).collect();
+        }
+        json
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ShellUiError::Error(_) | ShellUiError::Interrupt => 1,
+            ShellUiError::Warning(_) => active_config(|config| config.warning_exit_code),
+            ShellUiError::WithCode(code, _) => *code,
+            ShellUiError::Retryable(error) => error.exit_code(),
+            ShellUiError::Multiple(errors) => errors
+                .iter()
+                .max_by_key(|error| error.severity())
+                .map(ShellUiError::exit_code)
+                .unwrap_or(1),
+        }
+    }
+
+    fn severity(&self) -> u8 {
+        match self {
+            ShellUiError::Interrupt => 0,
+            ShellUiError::Warning(_) => 1,
+            ShellUiError::Error(_) | ShellUiError::WithCode(_, _) => 2,
+            ShellUiError::Retryable(error) => error.severity(),
+            ShellUiError::Multiple(errors) => errors
+                .iter()
+                .map(ShellUiError::severity)
+                .max()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Retry policy for retryable errors
+///
+/// The delay between attempts starts at `backoff` and doubles after each attempt.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: usize,
+    /// Delay before the first retry
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: usize, backoff: Duration) -> Self {
+        RetryPolicy {
+            max_attempts,
+            backoff,
+        }
+    }
+
+    /// Delay before retrying after the given failed attempt, starting at 1
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let exponent = u32::try_from(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.backoff.saturating_mul(2_u32.saturating_pow(exponent))
+    }
+
+    /// Check if a command failing with an error at the given attempt should be retried
+    pub fn should_retry(&self, error: &ShellUiError, attempt: usize) -> bool {
+        error.is_retryable() && attempt < self.max_attempts
+    }
+
+    /// Track the attempts of a function run with this policy
+    ///
+    /// Shared by [`RetryPolicy::run`] and the async commands, that only
+    /// differ in how they sleep between attempts.
+    pub(crate) fn attempts(&self) -> Attempts<'_> {
+        Attempts {
+            policy: self,
+            attempt: 1,
+        }
+    }
+
+    /// Run a function, retrying it while it fails with a retryable error
+    ///
+    /// `on_retry` is called with the error, the failed attempt and the delay
+    /// before each retry.
+    pub fn run<T, F, R>(&self, mut f: F, mut on_retry: R) -> ShellUiResult<T>
+    where
+        F: FnMut() -> ShellUiResult<T>,
+        R: FnMut(&ShellUiError, usize, Duration),
+    {
+        let mut attempts = self.attempts();
+        loop {
+            let result = f();
+            match attempts.retry_after(&result, &mut on_retry) {
+                Some(delay) => thread::sleep(delay),
+                None => return result,
+            }
+        }
+    }
+}
+
+/// Attempts of a function run with a [`RetryPolicy`]
+pub(crate) struct Attempts<'a> {
+    policy: &'a RetryPolicy,
+    attempt: usize,
+}
+
+impl Attempts<'_> {
+    /// Check the result of an attempt
+    ///
+    /// If it failed with an error that should be retried, `on_retry` is
+    /// called with the error, the failed attempt and the delay, and the
+    /// delay before the next attempt is returned.
+    pub(crate) fn retry_after<T, R>(
+        &mut self,
+        result: &ShellUiResult<T>,
+        on_retry: R,
+    ) -> Option<Duration>
+    where
+        R: FnOnce(&ShellUiError, usize, Duration),
+    {
+        let error = result.as_ref().err()?;
+        if !self.policy.should_retry(error, self.attempt) {
+            return None;
+        }
+        let delay = self.policy.delay(self.attempt);
+        on_retry(error, self.attempt, delay);
+        self.attempt += 1;
+        Some(delay)
+    }
+}
+
+/// Collect the errors of batch operations
+///
+/// Errors are collected so that all the operations are run, and
+/// reported together at the end.
+#[derive(Debug, Default)]
+pub struct ErrorCollector {
+    errors: Vec<ShellUiError>,
+}
+
+impl ErrorCollector {
+    pub fn new() -> Self {
+        ErrorCollector::default()
+    }
+
+    /// Collect the error of `result`, if any
+    pub fn collect<T, E>(&mut self, result: Result<T, E>) -> Option<T>
+    where
+        E: Into<ShellUiError>,
+    {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.push(error);
+                None
+            }
+        }
+    }
+
+    pub fn push<E>(&mut self, error: E)
+    where
+        E: Into<ShellUiError>,
+    {
+        self.errors.push(error.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Report the collected errors
+    ///
+    /// A single error is returned as is, while several errors are
+    /// returned as [`ShellUiError::Multiple`].
+    pub fn finish(mut self) -> ShellUiResult<()> {
+        match self.errors.len() {
+            0 => Ok(()),
+            1 => Err(self.errors.remove(0)),
+            _ => Err(ShellUiError::Multiple(self.errors)),
+        }
+    }
 }
 
 pub trait WithContext {
@@ -42,17 +317,41 @@ pub trait WithContext {
     fn with_context<S>(self, context: S) -> Self::Output
     where
         S: ToString;
+    /// Add context with a machine-readable code, like `E_NOT_FOUND`
+    fn with_context_code<S>(self, code: &'static str, context: S) -> Self::Output
+    where
+        S: ToString;
 }
 
 impl<T> WithContext for Option<T> {
     type Output = Result<T, Error>;
+    #[track_caller]
     fn with_context<S>(self, context: S) -> Self::Output
     where
         S: ToString,
     {
         match self {
             Some(value) => Ok(value),
-            None => Err(Error::other(context.to_string())),
+            None => Err(Error::other(ErrorWrapper::new(
+                context.to_string(),
+                None,
+                None,
+            ))),
+        }
+    }
+
+    #[track_caller]
+    fn with_context_code<S>(self, code: &'static str, context: S) -> Self::Output
+    where
+        S: ToString,
+    {
+        match self {
+            Some(value) => Ok(value),
+            None => Err(Error::other(ErrorWrapper::new(
+                context.to_string(),
+                Some(code),
+                None,
+            ))),
         }
     }
 }
@@ -62,11 +361,27 @@ where
     E: StdError + Send + Sync + 'static,
 {
     type Output = Result<T, Error>;
+    // Closures are not `#[track_caller]`, so `map_err` would lose the location
+    #[track_caller]
     fn with_context<S>(self, context: S) -> Self::Output
     where
         S: ToString,
     {
-        self.map_err(|error| error.with_context(context))
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(error.with_context(context)),
+        }
+    }
+
+    #[track_caller]
+    fn with_context_code<S>(self, code: &'static str, context: S) -> Self::Output
+    where
+        S: ToString,
+    {
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(error.with_context_code(code, context)),
+        }
     }
 }
 
@@ -74,43 +389,284 @@ pub trait WithContextError {
     fn with_context<S>(self, context: S) -> Error
     where
         S: ToString;
+    /// Add context with a machine-readable code, like `E_NOT_FOUND`
+    fn with_context_code<S>(self, code: &'static str, context: S) -> Error
+    where
+        S: ToString;
 }
 
 impl<E> WithContextError for E
 where
     E: StdError + Send + Sync + 'static,
 {
+    #[track_caller]
     fn with_context<S>(self, context: S) -> Error
     where
         S: ToString,
     {
-        Error::other(ErrorWrapper::new(context.to_string(), self))
+        Error::other(ErrorWrapper::new(
+            context.to_string(),
+            None,
+            Some(Box::new(self)),
+        ))
+    }
+
+    #[track_caller]
+    fn with_context_code<S>(self, code: &'static str, context: S) -> Error
+    where
+        S: ToString,
+    {
+        Error::other(ErrorWrapper::new(
+            context.to_string(),
+            Some(code),
+            Some(Box::new(self)),
+        ))
+    }
+}
+
+#[cfg(feature = "backtrace")]
+static SHOW_BACKTRACE: AtomicBool = AtomicBool::new(false);
+
+/// Capture backtraces of errors and show them with the errors
+///
+/// Disabled by default, whatever the value of `RUST_BACKTRACE`. This is
+/// meant to be enabled by a verbose flag, like `-vv`.
+#[cfg(feature = "backtrace")]
+pub fn set_show_backtrace(show: bool) {
+    SHOW_BACKTRACE.store(show, Ordering::Relaxed);
+}
+
+#[cfg(feature = "backtrace")]
+pub(crate) fn show_backtrace() -> bool {
+    SHOW_BACKTRACE.load(Ordering::Relaxed)
+}
+
+/// Get where the innermost context of an error was added, with its backtrace
+///
+/// Returns `None` if no backtrace was captured.
+#[cfg(feature = "backtrace")]
+pub fn error_trace<'a>(
+    error: &'a (dyn StdError + 'static),
+) -> Option<(&'static Location<'static>, &'a Backtrace)> {
+    let mut trace = None;
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(wrapper) = as_wrapper(error) {
+            if wrapper.backtrace.status() == BacktraceStatus::Captured {
+                trace = Some((wrapper.location, &wrapper.backtrace));
+            }
+        }
+        current = error.source();
+    }
+    trace
+}
+
+/// Get the machine-readable code of an error
+///
+/// Returns the outermost code set with `with_context_code` in the error chain.
+pub fn error_code(error: &(dyn StdError + 'static)) -> Option<&'static str> {
+    let mut current = Some(error);
+    while let Some(error) = current {
+        if let Some(code) = as_wrapper(error).and_then(|wrapper| wrapper.code) {
+            return Some(code);
+        }
+        current = error.source();
+    }
+    None
+}
+
+fn as_wrapper<'a>(error: &'a (dyn StdError + 'static)) -> Option<&'a ErrorWrapper> {
+    if let Some(wrapper) = error.downcast_ref::<ErrorWrapper>() {
+        Some(wrapper)
+    } else {
+        error
+            .downcast_ref::<Error>()?
+            .get_ref()?
+            .downcast_ref::<ErrorWrapper>()
     }
 }
 
 #[derive(Debug)]
-struct ErrorWrapper<E> {
+struct ErrorWrapper {
     message: String,
-    source: E,
+    code: Option<&'static str>,
+    source: Option<Box<dyn StdError + Send + Sync>>,
+    #[cfg(feature = "backtrace")]
+    location: &'static Location<'static>,
+    #[cfg(feature = "backtrace")]
+    backtrace: Backtrace,
 }
 
-impl<E> ErrorWrapper<E> {
-    fn new(message: String, source: E) -> Self {
-        ErrorWrapper { message, source }
+impl ErrorWrapper {
+    #[track_caller]
+    fn new(
+        message: String,
+        code: Option<&'static str>,
+        source: Option<Box<dyn StdError + Send + Sync>>,
+    ) -> Self {
+        ErrorWrapper {
+            message,
+            code,
+            source,
+            #[cfg(feature = "backtrace")]
+            location: Location::caller(),
+            #[cfg(feature = "backtrace")]
+            backtrace: if show_backtrace() {
+                Backtrace::force_capture()
+            } else {
+                Backtrace::disabled()
+            },
+        }
     }
 }
 
-impl<E> fmt::Display for ErrorWrapper<E> {
+impl fmt::Display for ErrorWrapper {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.message)
     }
 }
 
-impl<E> StdError for ErrorWrapper<E>
-where
-    E: StdError + Send + Sync + 'static,
-{
+impl StdError for ErrorWrapper {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        Some(&self.source)
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn StdError + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::ActiveConfig;
+    use crate::ShellConfig;
+
+    #[test]
+    fn test_as_json() {
+        let error: ShellUiError = Err::<(), _>(Error::other("Error 2"))
+            .with_context_code("E_NOT_FOUND", "Error 1")
+            .with_context("Failure")
+            .unwrap_err()
+            .into();
+        assert_eq!(
+            error.as_json(),
+            json!({
+                "error": "Failure",
+                "causes": ["Error 1", "Error 2"],
+                "code": "E_NOT_FOUND",
+            })
+        );
+
+        let error = ShellUiError::warning("Warning");
+        assert_eq!(error.exit_code(), 0);
+        let config = ShellConfig {
+            warning_exit_code: 2,
+            ..ShellConfig::default()
+        };
+        let guard = ActiveConfig::install(config);
+        assert_eq!(error.exit_code(), 2);
+        drop(guard);
+        assert_eq!(error.exit_code(), 0);
+        assert_eq!(
+            error.as_json(),
+            json!({
+                "error": "Warning",
+                "causes": [],
+                "code": null,
+            })
+        );
+    }
+
+    #[cfg(feature = "backtrace")]
+    #[test]
+    fn test_error_location() {
+        let line = line!() + 1;
+        let error = Err::<(), _>(Error::other("Error 2")).with_context("Error 1");
+        let error = error.unwrap_err();
+        let location = as_wrapper(&error).unwrap().location;
+        assert_eq!(location.file(), file!());
+        assert_eq!(location.line(), line);
+    }
+
+    #[test]
+    fn test_collector() {
+        let collector = ErrorCollector::new();
+        assert!(collector.finish().is_ok());
+
+        let mut collector = ErrorCollector::new();
+        assert_eq!(collector.collect(Ok::<_, ShellUiError>(1)), Some(1));
+        collector.collect(Err::<(), _>(ShellUiError::warning("Warning")));
+        assert!(matches!(collector.finish(), Err(ShellUiError::Warning(_))));
+
+        let mut collector = ErrorCollector::new();
+        collector.push(ShellUiError::warning("Warning"));
+        collector.push(ShellUiError::with_code(3, Error::other("Not found")));
+        collector.push(ShellUiError::Interrupt);
+        let error = collector.finish().unwrap_err();
+        assert!(matches!(&error, ShellUiError::Multiple(errors) if errors.len() == 3));
+        assert_eq!(error.to_string(), "2 errors occurred");
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy::new(3, Duration::ZERO);
+        let mut attempts = 0;
+        let mut retries = Vec::new();
+        let result = policy.run(
+            || {
+                attempts += 1;
+                Err::<(), _>(ShellUiError::retryable(Error::other("Timeout")))
+            },
+            |_, attempt, _| retries.push(attempt),
+        );
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(attempts, 3);
+        assert_eq!(retries, vec![1, 2]);
+
+        let mut attempts = 0;
+        let result = policy.run(
+            || {
+                attempts += 1;
+                Err::<(), _>(ShellUiError::warning("Warning"))
+            },
+            |_, _, _| {},
+        );
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let policy = RetryPolicy::new(3, Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+
+        let mut attempts = policy.attempts();
+        let result = Err::<(), _>(ShellUiError::retryable(Error::other("Timeout")));
+        assert_eq!(attempts.retry_after(&Ok(()), |_, _, _| {}), None);
+        assert_eq!(
+            attempts.retry_after(&result, |_, _, _| {}),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            attempts.retry_after(&result, |_, _, _| {}),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(attempts.retry_after(&result, |_, _, _| {}), None);
+    }
+
+    #[test]
+    fn test_retryable_with_code() {
+        let error = ShellUiError::retryable(ShellUiError::with_code(
+            4,
+            Error::other("Service unavailable"),
+        ));
+        assert!(error.is_retryable());
+        assert_eq!(error.exit_code(), 4);
+        assert_eq!(error.to_string(), "Service unavailable");
+        assert_eq!(error.as_json()["error"], "Service unavailable");
+
+        let error = ShellUiError::retryable(
+            Error::other("Timeout").with_context_code("E_TIMEOUT", "Request failed"),
+        );
+        assert_eq!(error.code(), Some("E_TIMEOUT"));
+        assert_eq!(error.exit_code(), 1);
     }
 }
@@ -1,26 +1,403 @@
-use crate::errors::{ShellUiError, WithContext};
+mod escape;
+mod map;
+
+pub use self::escape::FieldEscaper;
+pub use self::map::{KeyCell, KeyValue, Keyed, KeyedHeader, MapTable};
+use crate::errors::{error_code, reported, ShellUiError, WithContext};
+use crate::i18n::{tr, tr_args};
+use crate::shell::active_config;
+#[cfg(feature = "parallel")]
+use crate::shell::ActiveConfig;
+use crate::spinner;
+use clap::ValueEnum;
+use colored::control::{set_override, SHOULD_COLORIZE};
+pub use colored::Color;
 use colored::Colorize;
 use colored_json::to_colored_json_auto;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+#[cfg(feature = "xlsx")]
+use rust_xlsxwriter::{Format, Workbook};
 use serde::Serialize;
 pub use shellui_derive::ObjectFormatter;
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::cmp::max;
+use std::env;
 use std::error::Error as StdError;
-use std::io::{Error, Result};
+use std::fmt::Display;
+use std::io::{self, BufWriter, Error, IsTerminal, Result, Write};
 use std::iter;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+use terminal_size::{terminal_size, Width};
+
+/// Output format of commands
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable output
+    #[default]
+    Human,
+    /// Pretty-printed JSON
+    Json,
+    /// Newline-delimited JSON
+    Ndjson,
+    /// Stable output for scripts: no colors, no padding, and
+    /// tab-separated values
+    Porcelain,
+}
+
+/// Output format, set by `ShellConfig::output_format`
+pub fn output_format() -> OutputFormat {
+    active_config(|config| config.output_format)
+}
+
+fn is_porcelain() -> bool {
+    output_format() == OutputFormat::Porcelain
+}
+
+/// Check if the headers of tables are printed, set by `ShellConfig::show_headers`
+pub fn show_headers() -> bool {
+    active_config(|config| config.show_headers)
+}
+
+/// Output stream
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Stream {
+    Stdout,
+    #[default]
+    Stderr,
+}
+
+/// Stream used by `AsFormatted::print_formatted`, set by `ShellConfig::message_stream`
+pub fn message_stream() -> Stream {
+    active_config(|config| config.message_stream)
+}
+
+// Output is routed by kind: data, like tables and JSON, is printed to
+// stdout, and diagnostics, like messages, errors and progress, to stderr.
+// Captures are nested, so that the data of a command can be captured
+// while the shell itself is captured.
+thread_local! {
+    static CAPTURES: RefCell<Vec<Capture>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Output captured instead of being printed
+#[derive(Debug, Default)]
+pub(crate) struct Capture {
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+    /// Only capture the data, diagnostics being printed
+    data_only: bool,
+}
+
+/// Capture the output of the current thread, until `stop_capture` is called
+pub(crate) fn start_capture() {
+    CAPTURES.with_borrow_mut(|captures| captures.push(Capture::default()));
+}
+
+pub(crate) fn stop_capture() -> Capture {
+    CAPTURES.with_borrow_mut(Vec::pop).unwrap_or_default()
+}
+
+pub(crate) fn is_capturing() -> bool {
+    CAPTURES.with_borrow(|captures| !captures.is_empty())
+}
+
+/// Capture the data printed by the current thread, until `stop_capture` is called
+pub(crate) fn start_data_capture() {
+    CAPTURES.with_borrow_mut(|captures| {
+        captures.push(Capture {
+            data_only: true,
+            ..Capture::default()
+        })
+    });
+}
+
+fn is_capturing_data() -> bool {
+    CAPTURES.with_borrow(|captures| captures.last().is_some_and(|capture| capture.data_only))
+}
+
+/// Call a function, capturing the data it prints
+///
+/// Only the data printed to stdout, like tables and JSON, is captured,
+/// so that it can be redirected or piped. Diagnostics are still printed,
+/// and messages are printed to stderr whatever `message_stream` is.
+pub fn capture_data<F, R>(f: F) -> (R, String)
+where
+    F: FnOnce() -> R,
+{
+    start_data_capture();
+    let result = f();
+    (result, stop_capture().stdout)
+}
+
+/// Enable colors if stderr is a terminal, even if stdout is redirected
+///
+/// colored only checks stdout, which disables the colors of prompts and
+/// diagnostics when the data is piped. The colors of the data are stripped
+/// instead when it is printed.
+pub(crate) fn init_colors() {
+    if !colors_forced() && !io::stdout().is_terminal() && io::stderr().is_terminal() {
+        set_override(true);
+    }
+}
+
+/// Check if colors are enabled or disabled by the environment
+fn colors_forced() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        || env::var("CLICOLOR").is_ok_and(|value| value == "0")
+        || env::var_os("CLICOLOR_FORCE").is_some()
+}
+
+/// Check if colors are enabled for the terminal while stdout is redirected
+fn strip_stdout_colors() -> bool {
+    SHOULD_COLORIZE.should_colorize() && !colors_forced() && !io::stdout().is_terminal()
+}
+
+/// Remove the ANSI escape sequences of a text
+pub(crate) fn strip_colors(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+        } else if chars.next() == Some('[') {
+            // Parameters, until the final byte
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    Cow::Owned(stripped)
+}
+
+thread_local! {
+    static NULL_DISPLAY_OVERRIDE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Text displayed for missing values, set by `ShellConfig::null_display`
+///
+/// It is used for `None` values, including in tables.
+pub fn null_display() -> String {
+    NULL_DISPLAY_OVERRIDE
+        .with_borrow(Clone::clone)
+        .unwrap_or_else(|| active_config(|config| config.null_display.clone()))
+}
+
+/// Call a function with a different text for missing values
+///
+/// The text is only overridden for the current thread.
+pub fn with_null_display<S, F, R>(display: S, f: F) -> R
+where
+    S: ToString,
+    F: FnOnce() -> R,
+{
+    let previous = NULL_DISPLAY_OVERRIDE.replace(Some(display.to_string()));
+    let result = f();
+    NULL_DISPLAY_OVERRIDE.set(previous);
+    result
+}
+
+/// Print a line to stdout, or to the captured output
+///
+/// The spinner is stopped before writing to the terminal.
+pub(crate) fn print_stdout<T>(line: T)
+where
+    T: Display,
+{
+    CAPTURES.with_borrow_mut(|captures| match captures.last_mut() {
+        // Captured data is redirected or piped, and never colored
+        Some(capture) if capture.data_only => {
+            let line = line.to_string();
+            capture.stdout.push_str(&strip_colors(&line));
+            capture.stdout.push('\n');
+        }
+        Some(capture) => capture.stdout.push_str(&format!("{line}\n")),
+        None if strip_stdout_colors() => {
+            spinner::stop();
+            println!("{}", strip_colors(&line.to_string()));
+        }
+        None => {
+            spinner::stop();
+            println!("{line}");
+        }
+    })
+}
+
+/// Print a line to stderr, or to the captured output
+///
+/// The spinner is stopped before writing to the terminal.
+pub(crate) fn print_stderr<T>(line: T)
+where
+    T: Display,
+{
+    // Diagnostics skip the captures of data
+    CAPTURES.with_borrow_mut(|captures| {
+        match captures.iter_mut().rev().find(|capture| !capture.data_only) {
+            Some(capture) => capture.stderr.push_str(&format!("{line}\n")),
+            None => {
+                spinner::stop();
+                eprintln!("{line}");
+            }
+        }
+    })
+}
 
+/// Value that can be printed, with or without colors
+///
+/// Text is borrowed when possible, to avoid allocating when formatting
+/// large tables.
 pub trait AsFormatted {
     fn unformatted_len(&self) -> usize {
         self.as_unformatted().len()
     }
-    fn as_unformatted(&self) -> String;
-    fn as_formatted(&self) -> String {
+    fn as_unformatted(&self) -> Cow<'_, str>;
+    fn as_formatted(&self) -> Cow<'_, str> {
         self.as_unformatted()
     }
     fn print_formatted(&self) {
-        eprintln!("{}", self.as_formatted());
+        // Messages are diagnostics, and never captured with the data
+        let stream = if is_capturing_data() {
+            Stream::Stderr
+        } else {
+            message_stream()
+        };
+        self.print_to(stream);
+    }
+    fn print_to(&self, stream: Stream) {
+        match stream {
+            Stream::Stdout => print_stdout(self.as_formatted()),
+            Stream::Stderr => print_stderr(self.as_formatted()),
+        }
+    }
+}
+
+/// Colors used by shellui
+///
+/// The theme is used by messages, tables and prompts.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Theme {
+    pub info: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub error: Color,
+    pub hint: Color,
+    pub header: Color,
+    /// Icons prefixed to printed messages
+    pub icons: Icons,
+    /// Commands, like `network create`, in the shell line
+    pub command: Color,
+    /// Flags, like `--force`, in the shell line
+    pub flag: Color,
+    /// Values of flags, like `json` in `--output json`, in the shell line
+    pub flag_value: Color,
+    /// Quoted strings in the shell line
+    pub string: Color,
+    /// Variables, like `$ids`, in the shell line
+    pub variable: Color,
+}
+
+impl Theme {
+    const DEFAULT: Theme = Theme {
+        info: Color::BrightCyan,
+        success: Color::BrightGreen,
+        warning: Color::BrightYellow,
+        error: Color::BrightRed,
+        hint: Color::White,
+        header: Color::White,
+        icons: Icons::None,
+        command: Color::BrightBlue,
+        flag: Color::Yellow,
+        flag_value: Color::BrightYellow,
+        string: Color::Green,
+        variable: Color::Magenta,
+    };
+}
+
+/// Icons prefixed to printed info, success, warning and error messages
+///
+/// Makes the status of messages visible without colors.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Icons {
+    #[default]
+    None,
+    /// Unicode symbols, like `✔`
+    Unicode,
+    /// ASCII fallback, like `[ok]`
+    Ascii,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DEFAULT
+    }
+}
+
+/// Style of a table cell, applied at print time
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Style {
+    color: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+}
+
+impl Style {
+    pub fn new() -> Self {
+        Style::default()
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn dimmed(mut self) -> Self {
+        self.dimmed = true;
+        self
+    }
+
+    fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut styled = text.normal();
+        if let Some(color) = self.color {
+            styled = styled.color(color);
+        }
+        if self.bold {
+            styled = styled.bold();
+        }
+        if self.dimmed {
+            styled = styled.dimmed();
+        }
+        styled.to_string()
     }
 }
 
+static THEME: RwLock<Theme> = RwLock::new(Theme::DEFAULT);
+
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap() = theme;
+}
+
+pub fn theme() -> Theme {
+    *THEME.read().unwrap()
+}
+
 #[derive(Debug, Default, Eq, PartialEq)]
 enum MessageKind {
     #[default]
@@ -30,12 +407,15 @@ enum MessageKind {
     Warning,
     Error,
     Hint,
+    Colored(Color),
 }
 
 #[derive(Debug, Default, Eq, PartialEq)]
 pub struct Message {
     kind: MessageKind,
     message: String,
+    /// Segments of a message built with `Message::builder`
+    segments: Vec<Message>,
 }
 
 impl Message {
@@ -45,7 +425,8 @@ impl Message {
     {
         Message {
             kind: MessageKind::Default,
-            message: value.as_unformatted(),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
         }
     }
 
@@ -55,7 +436,8 @@ impl Message {
     {
         Message {
             kind: MessageKind::Info,
-            message: value.as_unformatted(),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
         }
     }
 
@@ -65,7 +447,8 @@ impl Message {
     {
         Message {
             kind: MessageKind::Success,
-            message: value.as_unformatted(),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
         }
     }
 
@@ -75,7 +458,8 @@ impl Message {
     {
         Message {
             kind: MessageKind::Warning,
-            message: value.as_unformatted(),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
         }
     }
 
@@ -85,7 +469,8 @@ impl Message {
     {
         Message {
             kind: MessageKind::Error,
-            message: value.as_unformatted(),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
         }
     }
 
@@ -95,7 +480,97 @@ impl Message {
     {
         Message {
             kind: MessageKind::Hint,
-            message: value.as_unformatted(),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// Message with a fixed color, independent of the theme
+    pub fn colored<T>(value: T, color: Color) -> Self
+    where
+        T: AsFormatted,
+    {
+        Message {
+            kind: MessageKind::Colored(color),
+            message: value.as_unformatted().into_owned(),
+            segments: Vec::new(),
+        }
+    }
+}
+
+impl Message {
+    /// Build a message from segments of different kinds
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::default()
+    }
+}
+
+/// Builder of a message with mixed kinds
+///
+/// The unformatted length of the message is the length of its text,
+/// so messages with mixed kinds are aligned properly in tables.
+#[derive(Debug, Default)]
+pub struct MessageBuilder {
+    segments: Vec<Message>,
+}
+
+impl MessageBuilder {
+    pub fn push(mut self, message: Message) -> Self {
+        self.segments.push(message);
+        self
+    }
+
+    pub fn text<T>(self, value: T) -> Self
+    where
+        T: AsFormatted,
+    {
+        self.push(Message::new(value))
+    }
+
+    pub fn info<T>(self, value: T) -> Self
+    where
+        T: AsFormatted,
+    {
+        self.push(Message::info(value))
+    }
+
+    pub fn success<T>(self, value: T) -> Self
+    where
+        T: AsFormatted,
+    {
+        self.push(Message::success(value))
+    }
+
+    pub fn warning<T>(self, value: T) -> Self
+    where
+        T: AsFormatted,
+    {
+        self.push(Message::warning(value))
+    }
+
+    pub fn error<T>(self, value: T) -> Self
+    where
+        T: AsFormatted,
+    {
+        self.push(Message::error(value))
+    }
+
+    pub fn hint<T>(self, value: T) -> Self
+    where
+        T: AsFormatted,
+    {
+        self.push(Message::hint(value))
+    }
+
+    pub fn build(self) -> Message {
+        Message {
+            kind: MessageKind::Default,
+            message: self
+                .segments
+                .iter()
+                .map(|segment| &*segment.message)
+                .collect(),
+            segments: self.segments,
         }
     }
 }
@@ -105,36 +580,256 @@ impl AsFormatted for Message {
         self.message.len()
     }
 
-    fn as_unformatted(&self) -> String {
-        self.message.clone()
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.message)
+    }
+
+    fn as_formatted(&self) -> Cow<'_, str> {
+        if !self.segments.is_empty() {
+            let formatted = self
+                .segments
+                .iter()
+                .map(AsFormatted::as_formatted)
+                .collect();
+            return Cow::Owned(formatted);
+        }
+
+        self.kind.format(&self.message)
+    }
+
+    /// Print the message, wrapped to the width of the terminal
+    fn print_to(&self, stream: Stream) {
+        let formatted = match (self.segments.is_empty(), wrap_width()) {
+            (true, Some(width)) => self
+                .kind
+                .format(&wrap(&self.message, width, ""))
+                .into_owned(),
+            _ => self.as_formatted().into_owned(),
+        };
+        let formatted = self.kind.with_icon(formatted);
+        match stream {
+            Stream::Stdout => print_stdout(formatted),
+            Stream::Stderr => print_stderr(formatted),
+        }
+    }
+}
+
+/// Marker of the items of a `MessageList`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ListStyle {
+    #[default]
+    Bulleted,
+    Numbered,
+}
+
+/// List of messages, like the results of the steps of a command
+///
+/// Items are prefixed with a bullet or a number, and lines of multi-line
+/// items are aligned with the first one.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct MessageList {
+    style: ListStyle,
+    items: Vec<Message>,
+}
+
+impl MessageList {
+    pub fn bulleted() -> Self {
+        MessageList::default()
+    }
+
+    pub fn numbered() -> Self {
+        MessageList {
+            style: ListStyle::Numbered,
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push(mut self, item: Message) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    fn markers(&self) -> Vec<String> {
+        let count_width = self.items.len().to_string().len();
+        (1..=self.items.len())
+            .map(|i| match self.style {
+                ListStyle::Bulleted => "-".to_string(),
+                ListStyle::Numbered => format!("{i:>count_width$}."),
+            })
+            .collect()
+    }
+
+    fn render<'a, F>(&'a self, format: F) -> String
+    where
+        F: Fn(&'a Message) -> Cow<'a, str>,
+    {
+        self.items
+            .iter()
+            .zip(self.markers())
+            .map(|(item, marker)| {
+                let indent = format!("\n{}", " ".repeat(marker.len() + 1));
+                format!("{marker} {}", format(item).replace('\n', &indent))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Extend<Message> for MessageList {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Message>,
+    {
+        self.items.extend(iter);
+    }
+}
+
+impl AsFormatted for MessageList {
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Owned(self.render(AsFormatted::as_unformatted))
+    }
+
+    fn as_formatted(&self) -> Cow<'_, str> {
+        Cow::Owned(self.render(AsFormatted::as_formatted))
+    }
+}
+
+impl MessageKind {
+    fn icon(&self, icons: Icons) -> Option<&'static str> {
+        let icon = match (icons, self) {
+            (Icons::None, _)
+            | (_, MessageKind::Default | MessageKind::Hint | MessageKind::Colored(_)) => {
+                return None
+            }
+            (Icons::Unicode, MessageKind::Info) => "ℹ",
+            (Icons::Unicode, MessageKind::Success) => "✔",
+            (Icons::Unicode, MessageKind::Warning) => "⚠",
+            (Icons::Unicode, MessageKind::Error) => "✖",
+            (Icons::Ascii, MessageKind::Info) => "[info]",
+            (Icons::Ascii, MessageKind::Success) => "[ok]",
+            (Icons::Ascii, MessageKind::Warning) => "[warning]",
+            (Icons::Ascii, MessageKind::Error) => "[error]",
+        };
+        Some(icon)
     }
 
-    fn as_formatted(&self) -> String {
-        match &self.kind {
-            MessageKind::Default => self.message.clone(),
-            MessageKind::Info => self.message.bright_cyan().to_string(),
-            MessageKind::Success => self.message.bright_green().to_string(),
-            MessageKind::Warning => self.message.bright_yellow().to_string(),
-            MessageKind::Error => self.message.bright_red().to_string(),
-            MessageKind::Hint => self.message.white().dimmed().to_string(),
+    /// Prefix a formatted message with the icon of the theme
+    fn with_icon(&self, formatted: String) -> String {
+        match self.icon(theme().icons) {
+            Some(icon) => format!("{} {formatted}", self.format(icon)),
+            None => formatted,
         }
     }
+
+    fn format<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let theme = theme();
+        let formatted = match self {
+            MessageKind::Default => return Cow::Borrowed(text),
+            MessageKind::Info => text.color(theme.info),
+            MessageKind::Success => text.color(theme.success),
+            MessageKind::Warning => text.color(theme.warning),
+            MessageKind::Error => text.color(theme.error),
+            MessageKind::Hint => text.color(theme.hint).dimmed(),
+            MessageKind::Colored(color) => text.color(*color),
+        };
+        Cow::Owned(formatted.to_string())
+    }
+}
+
+/// Width to wrap printed messages to
+///
+/// Messages are only wrapped when printed to a terminal.
+fn wrap_width() -> Option<usize> {
+    if is_capturing() || is_porcelain() {
+        return None;
+    }
+    let (Width(width), _) = terminal_size()?;
+    Some(usize::from(width))
+}
+
+/// Wrap a text, indenting the lines after the first one with `indent`
+fn wrap<'a>(text: &'a str, width: usize, indent: &str) -> Cow<'a, str> {
+    if text.len() <= width && !text.contains('\n') {
+        return Cow::Borrowed(text);
+    }
+    let options = textwrap::Options::new(width).subsequent_indent(indent);
+    Cow::Owned(textwrap::fill(text, options))
 }
 
 macro_rules! impl_as_formatted {
     ($ty:ty) => {
         impl AsFormatted for $ty {
-            fn as_unformatted(&self) -> String {
-                self.to_string()
+            fn as_unformatted(&self) -> Cow<'_, str> {
+                Cow::Owned(self.to_string())
             }
         }
     };
 }
 
+impl_as_formatted!(i8);
+impl_as_formatted!(i16);
 impl_as_formatted!(i32);
 impl_as_formatted!(i64);
+impl_as_formatted!(i128);
+impl_as_formatted!(isize);
+impl_as_formatted!(u8);
+impl_as_formatted!(u16);
 impl_as_formatted!(u32);
 impl_as_formatted!(u64);
+impl_as_formatted!(u128);
+impl_as_formatted!(usize);
+impl_as_formatted!(char);
+impl_as_formatted!(IpAddr);
+impl_as_formatted!(Ipv4Addr);
+impl_as_formatted!(Ipv6Addr);
+impl_as_formatted!(SocketAddr);
+
+/// Format a float with at most 3 decimals, without trailing zeros
+fn format_float(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let formatted = format!("{value:.3}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    match formatted {
+        "-0" => "0".to_string(),
+        _ => formatted.to_string(),
+    }
+}
+
+impl AsFormatted for f32 {
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Owned(format_float(f64::from(*self)))
+    }
+}
+
+impl AsFormatted for f64 {
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Owned(format_float(*self))
+    }
+}
+
+impl AsFormatted for Duration {
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("{self:?}"))
+    }
+}
+
+/// Paths that are not valid UTF-8 are escaped instead of being altered
+impl AsFormatted for Path {
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        match self.to_str() {
+            Some(path) => Cow::Borrowed(path),
+            None => Cow::Owned(format!("{self:?}")),
+        }
+    }
+}
+
+impl AsFormatted for PathBuf {
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        self.as_path().as_unformatted()
+    }
+}
 
 macro_rules! impl_as_formatted_str {
     ($ty:ty) => {
@@ -142,8 +837,8 @@ macro_rules! impl_as_formatted_str {
             fn unformatted_len(&self) -> usize {
                 self.len()
             }
-            fn as_unformatted(&self) -> String {
-                self.to_string()
+            fn as_unformatted(&self) -> Cow<'_, str> {
+                Cow::Borrowed(self)
             }
         }
     };
@@ -151,6 +846,7 @@ macro_rules! impl_as_formatted_str {
 
 impl_as_formatted_str!(String);
 impl_as_formatted_str!(&str);
+impl_as_formatted_str!(Cow<'_, str>);
 
 impl AsFormatted for bool {
     fn unformatted_len(&self) -> usize {
@@ -161,11 +857,11 @@ impl AsFormatted for bool {
         }
     }
 
-    fn as_unformatted(&self) -> String {
+    fn as_unformatted(&self) -> Cow<'_, str> {
         if *self {
-            "*".to_string()
+            Cow::Borrowed("*")
         } else {
-            String::new()
+            Cow::Borrowed("")
         }
     }
 }
@@ -174,35 +870,87 @@ impl<T> AsFormatted for Option<T>
 where
     T: AsFormatted,
 {
-    fn as_unformatted(&self) -> String {
+    fn as_unformatted(&self) -> Cow<'_, str> {
         match self {
             Some(value) => value.as_unformatted(),
-            None => String::new(),
+            None => Cow::Owned(null_display()),
         }
     }
 }
 
+#[cfg(feature = "backtrace")]
+fn with_trace(error: &Error, message: String) -> String {
+    if !crate::errors::show_backtrace() {
+        return message;
+    }
+
+    match crate::errors::error_trace(error) {
+        Some((location, backtrace)) => format!(
+            "{message}\n{}\n{}",
+            Message::hint(tr_args(
+                "shellui-error-context-location",
+                "Error context added at { $location }",
+                &[("location", &location.to_string())],
+            ))
+            .as_formatted(),
+            Message::hint(backtrace.to_string()).as_formatted()
+        ),
+        None => message,
+    }
+}
+
+#[cfg(not(feature = "backtrace"))]
+fn with_trace(_error: &Error, message: String) -> String {
+    message
+}
+
 impl AsFormatted for Error {
-    fn as_unformatted(&self) -> String {
-        self.to_string()
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Owned(self.to_string())
     }
 
-    fn as_formatted(&self) -> String {
-        let message = Message::error(self.to_string()).as_formatted();
+    fn as_formatted(&self) -> Cow<'_, str> {
+        let width = wrap_width();
+        let wrap_line = |line: String, indent: &str| match width {
+            Some(width) => wrap(&line, width, indent).into_owned(),
+            None => line,
+        };
+
+        let message = Message::error(wrap_line(self.to_string(), ""))
+            .as_formatted()
+            .into_owned();
+        let message = MessageKind::Error.with_icon(message);
+        let message = match error_code(self) {
+            Some(code) => format!(
+                "{message} {}",
+                Message::hint(format!("[{code}]")).as_formatted()
+            ),
+            None => message,
+        };
 
         let source = self.source();
         if let Some(source) = source {
             let errors = ErrorIterator::new(Some(source))
                 .enumerate()
-                .map(|(i, error)| Message::hint(format!("  ({}) {error}", i + 1)).as_formatted());
+                .map(|(i, error)| {
+                    let prefix = format!("  ({}) ", i + 1);
+                    let indent = " ".repeat(prefix.len());
+                    Message::hint(wrap_line(format!("{prefix}{error}"), &indent))
+                        .as_formatted()
+                        .into_owned()
+                });
 
             let errors = iter::once(message)
-                .chain(iter::once(Message::hint("Caused by:").as_formatted()))
+                .chain(iter::once(
+                    Message::hint(tr("shellui-caused-by", "Caused by:"))
+                        .as_formatted()
+                        .into_owned(),
+                ))
                 .chain(errors)
                 .collect::<Vec<_>>();
-            errors.join("\n")
+            Cow::Owned(with_trace(self, errors.join("\n")))
         } else {
-            message
+            Cow::Owned(with_trace(self, message))
         }
     }
 }
@@ -232,44 +980,77 @@ impl<'a> Iterator for ErrorIterator<'a> {
 }
 
 impl AsFormatted for ShellUiError {
-    fn as_unformatted(&self) -> String {
-        self.to_string()
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        Cow::Owned(self.to_string())
     }
 
-    fn as_formatted(&self) -> String {
+    fn as_formatted(&self) -> Cow<'_, str> {
         match self {
             ShellUiError::Error(error) => error.as_formatted(),
-            ShellUiError::Warning(warning) => Message::warning(warning).as_formatted(),
-            ShellUiError::Interrupt => String::new(),
+            ShellUiError::Warning(warning) => {
+                let message = Message::warning(warning).as_formatted().into_owned();
+                Cow::Owned(MessageKind::Warning.with_icon(message))
+            }
+            ShellUiError::Interrupt => Cow::Borrowed(""),
+            ShellUiError::WithCode(_, error) => error.as_formatted(),
+            ShellUiError::Retryable(error) => error.as_formatted(),
+            ShellUiError::Multiple(errors) => {
+                let errors = reported(errors).collect::<Vec<_>>();
+                let count = errors.len().to_string();
+                let errors = errors
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, error)| format!("({}) {}", i + 1, error.as_formatted()));
+                iter::once(
+                    Message::error(tr_args(
+                        "shellui-errors-occurred",
+                        "{ $count } errors occurred",
+                        &[("count", &count)],
+                    ))
+                    .as_formatted()
+                    .into_owned(),
+                )
+                .chain(errors)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .into()
+            }
         }
     }
 }
 
-impl<'a, T> AsFormatted for &'a T
+impl<T> AsFormatted for &T
 where
-    T: AsFormatted,
+    T: AsFormatted + ?Sized,
 {
     fn unformatted_len(&self) -> usize {
         AsFormatted::unformatted_len(*self)
     }
 
-    fn as_unformatted(&self) -> String {
+    fn as_unformatted(&self) -> Cow<'_, str> {
         AsFormatted::as_unformatted(*self)
     }
 
-    fn as_formatted(&self) -> String {
+    fn as_formatted(&self) -> Cow<'_, str> {
         AsFormatted::as_formatted(*self)
     }
 
     fn print_formatted(&self) {
         AsFormatted::print_formatted(*self)
     }
+
+    fn print_to(&self, stream: Stream) {
+        AsFormatted::print_to(*self, stream)
+    }
 }
 
 pub trait ObjectFormatter {
     type Header: 'static + Clone + AsRef<str>;
     type Mode: 'static + Clone;
-    type Output: AsFormatted;
+    /// Value of a cell, that can borrow from the object
+    type Output<'a>: AsFormatted
+    where
+        Self: 'a;
 
     fn headers(mode: Option<Self::Mode>) -> Vec<Self::Header>;
     fn default_headers() -> Vec<Self::Header> {
@@ -278,92 +1059,693 @@ pub trait ObjectFormatter {
     fn headers_with_mode(mode: Self::Mode) -> Vec<Self::Header> {
         Self::headers(Some(mode))
     }
-    fn format_value(&self, mode: Option<Self::Mode>, header: &Self::Header) -> Self::Output;
-}
-
-pub trait PrintTable {
-    type Item: ObjectFormatter;
-    fn format_table(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) -> Vec<String>;
-    fn print_table(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>);
-    fn print_table_default(&self) {
-        self.print_table(None)
-    }
-    fn print_table_with_mode(&self, mode: <Self::Item as ObjectFormatter>::Mode) {
-        self.print_table(Some(mode))
+    fn format_value(&self, mode: Option<Self::Mode>, header: &Self::Header) -> Self::Output<'_>;
+    /// Description of a field, displayed next to its value by `print_single`,
+    /// and by `print_describe`
+    fn description(_header: &Self::Header) -> Option<&'static str> {
+        None
     }
 }
 
-impl<T> PrintTable for Vec<T>
+impl<T> ObjectFormatter for &T
 where
     T: ObjectFormatter,
 {
-    type Item = T;
+    type Header = T::Header;
+    type Mode = T::Mode;
+    type Output<'a>
+        = T::Output<'a>
+    where
+        Self: 'a;
+
+    fn headers(mode: Option<Self::Mode>) -> Vec<Self::Header> {
+        T::headers(mode)
+    }
+
+    fn format_value(&self, mode: Option<Self::Mode>, header: &Self::Header) -> Self::Output<'_> {
+        T::format_value(self, mode, header)
+    }
+
+    fn description(header: &Self::Header) -> Option<&'static str> {
+        T::description(header)
+    }
+}
+
+pub trait PrintTable {
+    type Item: ObjectFormatter;
+    fn format_table(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) -> Vec<String>;
+    fn print_table(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>);
+    fn print_table_default(&self) {
+        self.print_table(None)
+    }
+    fn print_table_with_mode(&self, mode: <Self::Item as ObjectFormatter>::Mode) {
+        self.print_table(Some(mode))
+    }
+    /// Format a table without its headers, whatever `show_headers` is
+    fn format_table_no_headers(
+        &self,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+    ) -> Vec<String> {
+        let mut lines = self.format_table(mode);
+        if show_headers() && !lines.is_empty() {
+            lines.remove(0);
+        }
+        lines
+    }
+    fn print_table_no_headers(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) {
+        for line in self.format_table_no_headers(mode) {
+            print_stdout(line)
+        }
+    }
+    /// Format a table with a bold title above it
+    fn format_table_titled(
+        &self,
+        title: &str,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+    ) -> Vec<String> {
+        if is_porcelain() {
+            return self.format_table(mode);
+        }
+        let title = title.bold().to_string();
+        iter::once(title).chain(self.format_table(mode)).collect()
+    }
+    fn print_table_titled(&self, title: &str, mode: Option<<Self::Item as ObjectFormatter>::Mode>) {
+        for line in self.format_table_titled(title, mode) {
+            print_stdout(line)
+        }
+    }
+    /// Format a table with a dimmed caption below it
+    fn format_table_captioned(
+        &self,
+        caption: &str,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+    ) -> Vec<String> {
+        if is_porcelain() {
+            return self.format_table(mode);
+        }
+        let caption = Message::hint(caption).as_formatted().into_owned();
+        let mut lines = self.format_table(mode);
+        lines.push(caption);
+        lines
+    }
+    fn print_table_captioned(
+        &self,
+        caption: &str,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+    ) {
+        for line in self.format_table_captioned(caption, mode) {
+            print_stdout(line)
+        }
+    }
+    /// Format a table, styling cells with a function of the header and the value
+    ///
+    /// The style replaces the formatting of the cell, unless it is `Style::default()`.
+    fn format_table_styled<F>(
+        &self,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+        style: F,
+    ) -> Vec<String>
+    where
+        F: Fn(&<Self::Item as ObjectFormatter>::Header, &str) -> Style;
+    fn print_table_styled<F>(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>, style: F)
+    where
+        F: Fn(&<Self::Item as ObjectFormatter>::Header, &str) -> Style,
+    {
+        for line in self.format_table_styled(mode, style) {
+            print_stdout(line)
+        }
+    }
+}
+
+impl<T> PrintTable for [T]
+where
+    T: ObjectFormatter,
+{
+    type Item = T;
 
     fn format_table(&self, mode: Option<T::Mode>) -> Vec<String> {
+        format_table::<T, fn(&T::Header, &str) -> Style>(self, mode, None)
+    }
+
+    fn format_table_styled<F>(&self, mode: Option<T::Mode>, style: F) -> Vec<String>
+    where
+        F: Fn(&T::Header, &str) -> Style,
+    {
+        format_table(self, mode, Some(style))
+    }
+
+    fn print_table(&self, mode: Option<T::Mode>) {
+        for line in self.format_table(mode) {
+            print_stdout(line)
+        }
+    }
+}
+
+/// Format a table, the cells being only styled if there is a style function
+fn format_table<T, F>(elements: &[T], mode: Option<T::Mode>, style: Option<F>) -> Vec<String>
+where
+    T: ObjectFormatter,
+    F: Fn(&T::Header, &str) -> Style,
+{
+    let headers = T::headers(mode.clone());
+    if is_porcelain() {
+        return show_headers()
+            .then(|| porcelain_header::<T>(&headers))
+            .into_iter()
+            .chain(
+                elements
+                    .iter()
+                    .map(|e| porcelain_row(e, mode.clone(), &headers)),
+            )
+            .collect();
+    }
+
+    let values = elements
+        .iter()
+        .map(|e| extract_line(e, mode.clone(), &headers))
+        .collect::<Vec<_>>();
+
+    // Widths are computed while formatting the cells, that are then rendered
+    let mut widths = header_widths::<T>(&headers);
+    let cells = values
+        .iter()
+        .map(|line| {
+            let mut cells = line_cells(line, &mut widths);
+            if let Some(style) = &style {
+                for ((cell, value), header) in cells.iter_mut().zip(line).zip(&headers) {
+                    let unformatted = value.as_unformatted();
+                    let style = style(header, &unformatted);
+                    if !style.is_plain() {
+                        cell.0 = Cow::Owned(style.apply(&unformatted));
+                    }
+                }
+            }
+            cells
+        })
+        .collect::<Vec<_>>();
+
+    show_headers()
+        .then(|| header_cells::<T>(&headers))
+        .into_iter()
+        .chain(cells)
+        .map(|line| render_line(&line, &widths))
+        .collect()
+}
+
+/// Header and rows of a table, whatever the output format and `show_headers` are
+///
+/// Used to display the rows of a table as the options of a prompt.
+pub(crate) fn table_rows<T>(elements: &[T], mode: Option<T::Mode>) -> (String, Vec<String>)
+where
+    T: ObjectFormatter,
+{
+    let headers = T::headers(mode.clone());
+    let values = elements
+        .iter()
+        .map(|e| extract_line(e, mode.clone(), &headers))
+        .collect::<Vec<_>>();
+
+    let mut widths = header_widths::<T>(&headers);
+    let cells = values
+        .iter()
+        .map(|line| line_cells(line, &mut widths))
+        .collect::<Vec<_>>();
+    let header = render_line(&header_cells::<T>(&headers), &widths);
+    let rows = cells
+        .iter()
+        .map(|line| render_line(line, &widths))
+        .collect();
+    (header, rows)
+}
+
+macro_rules! impl_print_table_as_slice {
+    ($ty:ty, $($generics:tt)*) => {
+        impl<T, $($generics)*> PrintTable for $ty
+        where
+            T: ObjectFormatter,
+        {
+            type Item = T;
+
+            fn format_table(&self, mode: Option<T::Mode>) -> Vec<String> {
+                self.as_slice().format_table(mode)
+            }
+
+            fn format_table_styled<F>(&self, mode: Option<T::Mode>, style: F) -> Vec<String>
+            where
+                F: Fn(&T::Header, &str) -> Style,
+            {
+                self.as_slice().format_table_styled(mode, style)
+            }
+
+            fn print_table(&self, mode: Option<T::Mode>) {
+                self.as_slice().print_table(mode)
+            }
+        }
+    };
+}
+
+impl_print_table_as_slice!(Vec<T>,);
+impl_print_table_as_slice!([T; N], const N: usize);
+
+/// Table printing for iterators over borrowed values
+///
+/// Like `PrintTable`, for example with `values.iter().filter(...).print_table(None)`.
+pub trait PrintTableIter<T>
+where
+    T: ObjectFormatter,
+{
+    fn format_table(self, mode: Option<T::Mode>) -> Vec<String>;
+    fn print_table(self, mode: Option<T::Mode>);
+    fn print_table_default(self)
+    where
+        Self: Sized,
+    {
+        self.print_table(None)
+    }
+}
+
+impl<'a, T, I> PrintTableIter<T> for I
+where
+    T: 'a + ObjectFormatter,
+    I: Iterator<Item = &'a T>,
+{
+    fn format_table(self, mode: Option<T::Mode>) -> Vec<String> {
+        self.collect::<Vec<_>>().format_table(mode)
+    }
+
+    fn print_table(self, mode: Option<T::Mode>) {
+        self.collect::<Vec<_>>().print_table(mode)
+    }
+}
+
+/// Export of tables to Excel files
+///
+/// Headers are bold, and values that are numbers are written as numbers,
+/// so that they can be used in formulas.
+#[cfg(feature = "xlsx")]
+pub trait ExportXlsx: PrintTable {
+    fn export_xlsx(
+        &self,
+        path: &Path,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+    ) -> Result<()>;
+}
+
+#[cfg(feature = "xlsx")]
+impl<T> ExportXlsx for [T]
+where
+    T: ObjectFormatter,
+{
+    fn export_xlsx(&self, path: &Path, mode: Option<T::Mode>) -> Result<()> {
+        let headers = T::headers(mode.clone());
+        let mut workbook = Workbook::new();
+        let worksheet = workbook.add_worksheet();
+        let bold = Format::new().set_bold();
+
+        for (column, header) in (0..).zip(&headers) {
+            worksheet
+                .write_string_with_format(0, column, header.as_ref(), &bold)
+                .map_err(Error::other)?;
+        }
+        for (row, element) in (1..).zip(self) {
+            for (column, value) in (0..).zip(extract_line(element, mode.clone(), &headers)) {
+                let value = value.as_unformatted();
+                match xlsx_number(&value) {
+                    Some(number) => worksheet.write_number(row, column, number),
+                    None => worksheet.write_string(row, column, value),
+                }
+                .map_err(Error::other)?;
+            }
+        }
+        worksheet.autofit();
+
+        workbook
+            .save(path)
+            .with_context(format!("Failed to export to {}", path.display()))
+    }
+}
+
+#[cfg(feature = "xlsx")]
+impl<T> ExportXlsx for Vec<T>
+where
+    T: ObjectFormatter,
+{
+    fn export_xlsx(&self, path: &Path, mode: Option<T::Mode>) -> Result<()> {
+        self.as_slice().export_xlsx(path, mode)
+    }
+}
+
+/// Value of a cell, if it is a number
+///
+/// Values with leading zeros, like identifiers, are kept as text.
+#[cfg(feature = "xlsx")]
+fn xlsx_number(value: &str) -> Option<f64> {
+    let digits = value.trim_start_matches('-');
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return None;
+    }
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+}
+
+/// Parallel table formatting
+///
+/// Rows are formatted with rayon, which is faster than `PrintTable` for
+/// tables with expensive formatters, like `with` functions doing regex or
+/// serde work per cell. The order of the rows is preserved. Benchmarked
+/// against the serial path by the `parallel_table` example.
+#[cfg(feature = "parallel")]
+pub trait PrintTableParallel: PrintTable {
+    fn format_table_parallel(
+        &self,
+        mode: Option<<Self::Item as ObjectFormatter>::Mode>,
+    ) -> Vec<String>;
+    fn print_table_parallel(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) {
+        for line in self.format_table_parallel(mode) {
+            print_stdout(line)
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<T> PrintTableParallel for [T]
+where
+    T: ObjectFormatter + Sync,
+    T::Header: Sync,
+    T::Mode: Send + Sync,
+    for<'a> T::Output<'a>: Send + Sync,
+{
+    fn format_table_parallel(&self, mode: Option<T::Mode>) -> Vec<String> {
+        // The configuration is thread-local, and is installed in the workers
+        let config = active_config(Clone::clone);
+        let install = || ActiveConfig::install(config.clone());
         let headers = T::headers(mode.clone());
+        if is_porcelain() {
+            let rows = self
+                .par_iter()
+                .map_init(install, |_, e| porcelain_row(e, mode.clone(), &headers))
+                .collect::<Vec<_>>();
+            return show_headers()
+                .then(|| porcelain_header::<T>(&headers))
+                .into_iter()
+                .chain(rows)
+                .collect();
+        }
+
         let values = self
+            .par_iter()
+            .map_init(install, |_, e| extract_line(e, mode.clone(), &headers))
+            .collect::<Vec<_>>();
+        let cells = values
+            .par_iter()
+            .map_init(install, |_, line| {
+                line.iter()
+                    .map(|value| (value.as_formatted(), value.unformatted_len()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut widths = header_widths::<T>(&headers);
+        for line in &cells {
+            for ((_, len), width) in line.iter().zip(widths.iter_mut()) {
+                *width = max(*width, *len);
+            }
+        }
+
+        let lines = cells
+            .par_iter()
+            .map(|line| render_line(line, &widths))
+            .collect::<Vec<_>>();
+        show_headers()
+            .then(|| render_line(&header_cells::<T>(&headers), &widths))
+            .into_iter()
+            .chain(lines)
+            .collect()
+    }
+}
+
+/// Table renderer for large tables
+///
+/// Column widths are computed from the first rows only, and the table is
+/// written as it is rendered, without keeping all the lines in memory.
+/// Values longer than the sampled ones are not truncated.
+#[derive(Clone, Copy, Debug)]
+pub struct TableRenderer {
+    sample_rows: usize,
+    buffer_size: usize,
+}
+
+impl Default for TableRenderer {
+    fn default() -> Self {
+        TableRenderer {
+            sample_rows: 100,
+            buffer_size: 64 * 1024,
+        }
+    }
+}
+
+impl TableRenderer {
+    pub fn new() -> Self {
+        TableRenderer::default()
+    }
+
+    /// Number of rows used to compute the column widths
+    pub fn with_sample_rows(mut self, sample_rows: usize) -> Self {
+        self.sample_rows = sample_rows;
+        self
+    }
+
+    /// Size of the write buffer, in bytes
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Render a table to a writer
+    pub fn render<T, I, W>(&self, rows: I, mode: Option<T::Mode>, writer: W) -> Result<()>
+    where
+        T: ObjectFormatter,
+        I: IntoIterator<Item = T>,
+        W: Write,
+    {
+        let mut writer = BufWriter::with_capacity(self.buffer_size, writer);
+        self.render_lines(rows, mode, |line| writeln!(writer, "{line}"))?;
+        writer.flush()
+    }
+
+    /// Print a table to stdout
+    pub fn print<T, I>(&self, rows: I, mode: Option<T::Mode>) -> Result<()>
+    where
+        T: ObjectFormatter,
+        I: IntoIterator<Item = T>,
+    {
+        if is_capturing() || strip_stdout_colors() {
+            self.render_lines(rows, mode, |line| {
+                print_stdout(line);
+                Ok(())
+            })
+        } else {
+            spinner::stop();
+            self.render(rows, mode, io::stdout().lock())
+        }
+    }
+
+    fn render_lines<T, I, F>(&self, rows: I, mode: Option<T::Mode>, mut emit: F) -> Result<()>
+    where
+        T: ObjectFormatter,
+        I: IntoIterator<Item = T>,
+        F: FnMut(&str) -> Result<()>,
+    {
+        let headers = T::headers(mode.clone());
+        let mut rows = rows.into_iter();
+        if is_porcelain() {
+            if show_headers() {
+                emit(&porcelain_header::<T>(&headers))?;
+            }
+            for row in rows {
+                emit(&porcelain_row(&row, mode.clone(), &headers))?;
+            }
+            return Ok(());
+        }
+
+        let sample = rows.by_ref().take(self.sample_rows).collect::<Vec<_>>();
+        let sample = sample
             .iter()
-            .map(|e| extract_line(e, mode.clone(), &headers))
+            .map(|row| extract_line(row, mode.clone(), &headers))
             .collect::<Vec<_>>();
 
-        let column_count = compute_column_count::<T>(&headers, &values);
-        let headers = column_count
+        let mut widths = header_widths::<T>(&headers);
+        let cells = sample
             .iter()
-            .zip(headers.iter())
-            .map(|(size, k)| {
-                let header = format!("{:<1$}", k.as_ref(), size);
-                header.white().bold().to_string()
-            })
+            .map(|line| line_cells(line, &mut widths))
             .collect::<Vec<_>>();
-        let headers = headers.join("   ");
 
-        iter::once(headers)
-            .chain(values.into_iter().map(|line| {
-                let line = column_count
-                    .iter()
-                    .zip(line)
-                    .map(|(size, v)| {
-                        let formatted = v.as_formatted();
-                        let spacing = size - v.unformatted_len() + formatted.len();
-                        format!("{:<1$}", formatted, spacing)
-                    })
-                    .collect::<Vec<_>>();
-                line.join("   ")
-            }))
-            .collect()
+        if show_headers() {
+            emit(&render_line(&header_cells::<T>(&headers), &widths))?;
+        }
+        for line in cells {
+            emit(&render_line(&line, &widths))?;
+        }
+        for row in rows {
+            let line = extract_line(&row, mode.clone(), &headers);
+            let cells = line
+                .iter()
+                .map(|value| (value.as_formatted(), value.unformatted_len()))
+                .collect::<Vec<_>>();
+            emit(&render_line(&cells, &widths))?;
+        }
+        Ok(())
     }
+}
 
-    fn print_table(&self, mode: Option<T::Mode>) {
-        for line in self.format_table(mode) {
-            println!("{line}")
+/// Table refreshed in place, for `watch`-like commands
+///
+/// Each update moves the cursor up over the previous table and clears it
+/// before printing the new one. When stdout is not a terminal, the tables
+/// are printed one after the other.
+#[derive(Clone, Debug, Default)]
+pub struct LiveTable {
+    /// Number of terminal rows used by the previous table
+    rows: usize,
+}
+
+impl LiveTable {
+    pub fn new() -> Self {
+        LiveTable::default()
+    }
+
+    /// Replace the previous table with the given elements
+    pub fn update<T>(&mut self, items: &[T], mode: Option<T::Mode>) -> Result<()>
+    where
+        T: ObjectFormatter,
+    {
+        let lines = items.format_table(mode);
+        if is_capturing() || is_porcelain() || !io::stdout().is_terminal() {
+            for line in lines {
+                print_stdout(line);
+            }
+            return Ok(());
         }
+
+        spinner::stop();
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(self.refresh(&lines, wrap_width()).as_bytes())?;
+        stdout.flush()
+    }
+
+    fn refresh(&mut self, lines: &[String], width: Option<usize>) -> String {
+        let mut output = String::new();
+        if self.rows > 0 {
+            // Move up to the first line of the previous table, and clear until the end
+            output.push_str(&format!("\x1b[{}A\x1b[J", self.rows));
+        }
+        for line in lines {
+            output.push_str(line);
+            output.push('\n');
+        }
+
+        // Lines longer than the terminal are wrapped over several rows
+        self.rows = lines
+            .iter()
+            .map(|line| match width {
+                Some(width) if width > 0 => {
+                    max(1, textwrap::core::display_width(line).div_ceil(width))
+                }
+                _ => 1,
+            })
+            .sum();
+        output
     }
 }
 
-fn compute_column_count<T>(headers: &[T::Header], values: &[Vec<T::Output>]) -> Vec<usize>
+fn header_widths<T>(headers: &[T::Header]) -> Vec<usize>
 where
     T: ObjectFormatter,
 {
-    let zeroes = headers.iter().map(|_| 0).collect::<Vec<_>>();
-    let header_sizes = headers
-        .iter()
-        .map(AsRef::as_ref)
-        .map(str::len)
-        .collect::<Vec<_>>();
-    let value_sizes = values
+    headers.iter().map(|header| header.as_ref().len()).collect()
+}
+
+fn header_cells<T>(headers: &[T::Header]) -> Vec<(Cow<'_, str>, usize)>
+where
+    T: ObjectFormatter,
+{
+    let header_color = theme().header;
+    headers
         .iter()
-        .map(|line| line.iter().map(|v| v.unformatted_len()).collect());
-    iter::once(header_sizes)
-        .chain(value_sizes)
-        .fold(zeroes, |prev, current| {
-            prev.into_iter()
-                .zip(current.iter())
-                .map(|(x, y)| max(x, *y))
-                .collect()
+        .map(|header| {
+            let header = header.as_ref();
+            let formatted = header.color(header_color).bold().to_string();
+            (Cow::Owned(formatted), header.len())
+        })
+        .collect()
+}
+
+/// Format the cells of a line, updating the column widths
+fn line_cells<'a, O>(line: &'a [O], widths: &mut [usize]) -> Vec<(Cow<'a, str>, usize)>
+where
+    O: AsFormatted,
+{
+    line.iter()
+        .zip(widths.iter_mut())
+        .map(|(value, width)| {
+            let len = value.unformatted_len();
+            *width = max(*width, len);
+            (value.as_formatted(), len)
         })
+        .collect()
+}
+
+/// Render a line of formatted cells, with their unformatted length
+fn render_line(cells: &[(Cow<'_, str>, usize)], widths: &[usize]) -> String {
+    let capacity = cells
+        .iter()
+        .map(|(formatted, _)| formatted.len())
+        .sum::<usize>()
+        + widths.iter().sum::<usize>()
+        + 3 * widths.len();
+    let mut line = String::with_capacity(capacity);
+    for (i, ((formatted, len), width)) in cells.iter().zip(widths).enumerate() {
+        if i > 0 {
+            line.push_str("   ");
+        }
+        line.push_str(formatted);
+        line.extend(iter::repeat_n(' ', width.saturating_sub(*len)));
+    }
+    line
+}
+
+/// Header of a porcelain table
+fn porcelain_header<T>(headers: &[T::Header]) -> String
+where
+    T: ObjectFormatter,
+{
+    porcelain_escaper().record(headers.iter().map(AsRef::as_ref))
 }
 
-fn extract_line<T>(element: &T, mode: Option<T::Mode>, headers: &[T::Header]) -> Vec<T::Output>
+/// Row of a porcelain table, with unformatted values
+fn porcelain_row<T>(element: &T, mode: Option<T::Mode>, headers: &[T::Header]) -> String
+where
+    T: ObjectFormatter,
+{
+    let line = extract_line(element, mode, headers);
+    porcelain_escaper().record(line.iter().map(AsFormatted::as_unformatted))
+}
+
+/// Values are quoted only if they contain tabs, quotes or line breaks
+fn porcelain_escaper() -> FieldEscaper {
+    FieldEscaper::tsv().with_formula_protection(false)
+}
+
+fn extract_line<'a, T>(
+    element: &'a T,
+    mode: Option<T::Mode>,
+    headers: &[T::Header],
+) -> Vec<T::Output<'a>>
 where
     T: ObjectFormatter,
 {
@@ -377,6 +1759,9 @@ pub trait PrintSingle {
     type Item: ObjectFormatter;
     fn format_single(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) -> Vec<String>;
     fn print_single(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>);
+    /// Format the fields with their descriptions, without the values
+    fn format_describe(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) -> Vec<String>;
+    fn print_describe(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>);
     fn print_single_default(&self) {
         self.print_single(None)
     }
@@ -393,26 +1778,98 @@ where
 
     fn format_single(&self, mode: Option<T::Mode>) -> Vec<String> {
         let headers = Self::headers(mode.clone());
+        if is_porcelain() {
+            let escaper = porcelain_escaper();
+            return headers
+                .iter()
+                .map(|k| {
+                    let value = self.format_value(mode.clone(), k);
+                    escaper.record([k.as_ref(), &value.as_unformatted()])
+                })
+                .collect();
+        }
+
         let size = headers
             .iter()
             .map(AsRef::as_ref)
             .map(str::len)
             .max()
             .unwrap_or_default();
+        let values = headers
+            .iter()
+            .map(|k| self.format_value(mode.clone(), k))
+            .collect::<Vec<_>>();
+        // Descriptions are aligned after the widest described value
+        let value_size = headers
+            .iter()
+            .zip(&values)
+            .filter(|(k, _)| Self::description(k).is_some())
+            .map(|(_, value)| value.unformatted_len())
+            .max()
+            .unwrap_or_default();
+        let header_color = theme().header;
         headers
             .iter()
-            .map(|k| {
-                let header = k.as_ref().white().bold();
+            .zip(&values)
+            .map(|(k, value)| {
+                let header = k.as_ref().color(header_color).bold();
                 let header = format!("{:<1$}", header, size);
-                let value = self.format_value(mode.clone(), k);
-                format!("{header}   {}", value.as_formatted())
+                match Self::description(k) {
+                    Some(description) => format!(
+                        "{header}   {}{}   {}",
+                        value.as_formatted(),
+                        " ".repeat(value_size - value.unformatted_len()),
+                        Message::hint(description).as_formatted()
+                    ),
+                    None => format!("{header}   {}", value.as_formatted()),
+                }
+            })
+            .collect()
+    }
+
+    fn format_describe(&self, mode: Option<T::Mode>) -> Vec<String> {
+        let headers = Self::headers(mode);
+        if is_porcelain() {
+            let escaper = porcelain_escaper();
+            return headers
+                .iter()
+                .map(|k| escaper.record([k.as_ref(), Self::description(k).unwrap_or_default()]))
+                .collect();
+        }
+
+        let size = headers
+            .iter()
+            .map(AsRef::as_ref)
+            .map(str::len)
+            .max()
+            .unwrap_or_default();
+        let header_color = theme().header;
+        headers
+            .iter()
+            .map(|k| {
+                let header = k.as_ref().color(header_color).bold();
+                match Self::description(k) {
+                    Some(description) => format!(
+                        "{:<2$}   {}",
+                        header,
+                        Message::hint(description).as_formatted(),
+                        size
+                    ),
+                    None => header.to_string(),
+                }
             })
             .collect()
     }
 
+    fn print_describe(&self, mode: Option<T::Mode>) {
+        for line in self.format_describe(mode) {
+            print_stdout(line)
+        }
+    }
+
     fn print_single(&self, mode: Option<<Self::Item as ObjectFormatter>::Mode>) {
         for line in self.format_single(mode) {
-            println!("{line}")
+            print_stdout(line)
         }
     }
 }
@@ -427,7 +1884,7 @@ where
 {
     fn print_json(&self) -> Result<()> {
         let formatted = to_colored_json_auto(self).with_context("Failed to format to JSON")?;
-        println!("{formatted}");
+        print_stdout(formatted);
         Ok(())
     }
 }
@@ -435,6 +1892,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::{BTreeMap, HashMap};
     use std::env;
 
     struct TestValue(&'static str, &'static str, &'static str);
@@ -442,18 +1900,18 @@ mod tests {
     impl ObjectFormatter for TestValue {
         type Header = &'static str;
         type Mode = ();
-        type Output = String;
+        type Output<'a> = &'a str;
 
         fn headers(_mode: Option<()>) -> Vec<Self::Header> {
             vec!["id", "label", "a very long header"]
         }
 
-        fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> String {
+        fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> &str {
             match *header {
-                "id" => self.0.to_string(),
-                "label" => self.1.to_string(),
-                "a very long header" => self.2.to_string(),
-                _ => String::new(),
+                "id" => self.0,
+                "label" => self.1,
+                "a very long header" => self.2,
+                _ => "",
             }
         }
     }
@@ -475,6 +1933,319 @@ mod tests {
         assert_eq!(table, expected);
     }
 
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_format_list_parallel() {
+        use crate::ShellConfig;
+
+        env::set_var("NO_COLOR", "1");
+
+        let elements = (0..100)
+            .map(|i| TestValue(["1", "a very long id"][i % 2], "label\t1", "value"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            elements.format_table_parallel(None),
+            elements.format_table(None)
+        );
+
+        // The configuration is used by the workers
+        let _config = ActiveConfig::install(ShellConfig {
+            output_format: OutputFormat::Porcelain,
+            show_headers: false,
+            ..ShellConfig::default()
+        });
+        assert_eq!(
+            elements.format_table_parallel(None),
+            elements.format_table(None)
+        );
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn test_export_xlsx() {
+        assert_eq!(xlsx_number("12"), Some(12.0));
+        assert_eq!(xlsx_number("-0.5"), Some(-0.5));
+        assert_eq!(xlsx_number("007"), None);
+        assert_eq!(xlsx_number("inf"), None);
+        assert_eq!(xlsx_number("+1"), None);
+        assert_eq!(xlsx_number("label"), None);
+
+        let path = crate::testing::test_dir("export").join("export.xlsx");
+        let elements = vec![TestValue("1", "label", "value")];
+        elements.export_xlsx(&path, None).unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 0);
+    }
+
+    #[test]
+    fn test_format_list_no_headers() {
+        env::set_var("NO_COLOR", "1");
+
+        let elements = vec![TestValue("1", "label 1", "value")];
+        assert_eq!(
+            elements.format_table_no_headers(None),
+            vec!["1    label 1   value             "]
+        );
+    }
+
+    #[test]
+    fn test_null_display() {
+        env::set_var("NO_COLOR", "1");
+
+        assert_eq!(None::<i32>.as_unformatted(), "");
+        assert_eq!(
+            with_null_display("<none>", || None::<i32>.as_unformatted()),
+            "<none>"
+        );
+
+        struct OptionalValue(Option<&'static str>, bool);
+
+        impl ObjectFormatter for OptionalValue {
+            type Header = &'static str;
+            type Mode = ();
+            type Output<'a> = Message;
+
+            fn headers(_mode: Option<()>) -> Vec<Self::Header> {
+                vec!["label", "flag"]
+            }
+
+            fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> Message {
+                match *header {
+                    "label" => Message::new(self.0),
+                    _ => Message::new(self.1),
+                }
+            }
+        }
+
+        // Only missing values are null, not values displayed empty
+        let elements = vec![OptionalValue(None, true), OptionalValue(Some(""), false)];
+        let table = with_null_display("-", || elements.format_table(None));
+        let expected = vec!["label   flag", "-       *   ", "            "];
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_format_list_titled() {
+        env::set_var("NO_COLOR", "1");
+
+        let elements = vec![TestValue("1", "label 1", "value")];
+        let table = elements.format_table_titled("Values", None);
+        assert_eq!(table[0], "Values");
+        assert_eq!(table[1..], elements.format_table(None));
+
+        let table = elements.format_table_captioned("1 value", None);
+        assert_eq!(table[2], "1 value");
+    }
+
+    #[test]
+    fn test_format_list_borrowed() {
+        env::set_var("NO_COLOR", "1");
+
+        let elements = [
+            TestValue("1", "label 1", "value"),
+            TestValue("a very long id", "l2", "value2"),
+        ];
+        let expected = vec![
+            "id               label     a very long header",
+            "1                label 1   value             ",
+            "a very long id   l2        value2            ",
+        ];
+        assert_eq!(elements.format_table(None), expected);
+        assert_eq!(elements[..].format_table(None), expected);
+        assert_eq!(
+            elements
+                .iter()
+                .filter(|element| element.0 == "1")
+                .format_table(None),
+            vec![
+                "id   label     a very long header",
+                "1    label 1   value             ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_map() {
+        env::set_var("NO_COLOR", "1");
+
+        let map = HashMap::from([("b", "value 2"), ("a", "value 1")]);
+        let expected = vec!["Key   Value  ", "a     value 1", "b     value 2"];
+        assert_eq!(map.key_values().format_table(None), expected);
+
+        let map = BTreeMap::from([("k", TestValue("1", "label", "value"))]);
+        let expected = vec![
+            "Key   id   label   a very long header",
+            "k     1    label   value             ",
+        ];
+        assert_eq!(map.keyed_rows().format_table(None), expected);
+    }
+
+    #[test]
+    fn test_table_renderer() {
+        env::set_var("NO_COLOR", "1");
+
+        let elements = vec![
+            TestValue("1", "label 1", "value"),
+            TestValue("2", "a longer label", "value2"),
+        ];
+        let mut output = Vec::new();
+        TableRenderer::new()
+            .with_sample_rows(1)
+            .render(&elements, None, &mut output)
+            .unwrap();
+        let expected = [
+            "id   label     a very long header",
+            "1    label 1   value             ",
+            "2    a longer label   value2            ",
+        ];
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            expected.join("\n") + "\n"
+        );
+    }
+
+    #[test]
+    fn test_live_table() {
+        let mut table = LiveTable::new();
+        let lines = ["id   label".to_string(), "1    a long label".to_string()];
+        assert_eq!(
+            table.refresh(&lines, Some(80)),
+            "id   label\n1    a long label\n"
+        );
+        assert_eq!(
+            table.refresh(&lines[..1], Some(10)),
+            "\x1b[2A\x1b[Jid   label\n"
+        );
+        table.refresh(&lines, Some(10));
+        assert_eq!(table.refresh(&[], Some(10)), "\x1b[3A\x1b[J");
+    }
+
+    #[test]
+    fn test_format_list_styled() {
+        env::set_var("NO_COLOR", "1");
+
+        let elements = vec![TestValue("1", "ok", "value"), TestValue("2", "ko", "value")];
+        let styled = RefCell::new(Vec::new());
+        let table = elements.format_table_styled(None, |header, value| {
+            styled.borrow_mut().push(format!("{header}={value}"));
+            Style::new().color(Color::Red)
+        });
+        assert_eq!(table, elements.format_table(None));
+        assert_eq!(
+            styled.into_inner(),
+            vec![
+                "id=1",
+                "label=ok",
+                "a very long header=value",
+                "id=2",
+                "label=ko",
+                "a very long header=value"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_primitives() {
+        assert_eq!(1.0.as_unformatted(), "1");
+        assert_eq!((0.1 + 0.2).as_unformatted(), "0.3");
+        assert_eq!(1.23456f32.as_unformatted(), "1.235");
+        assert_eq!((-0.0001).as_unformatted(), "0");
+        assert_eq!(f64::NAN.as_unformatted(), "NaN");
+        assert_eq!(42usize.as_unformatted(), "42");
+        assert_eq!(Duration::from_millis(1500).as_unformatted(), "1.5s");
+        assert_eq!(IpAddr::from([127, 0, 0, 1]).as_unformatted(), "127.0.0.1");
+        assert_eq!(PathBuf::from("/tmp/file").as_unformatted(), "/tmp/file");
+        assert_eq!(Path::new("/tmp").as_unformatted(), "/tmp");
+    }
+
+    #[test]
+    fn test_message_builder() {
+        env::set_var("NO_COLOR", "1");
+
+        let message = Message::builder()
+            .text("Deployed ")
+            .success("api")
+            .text(" to ")
+            .info("prod")
+            .build();
+        assert_eq!(message.as_unformatted(), "Deployed api to prod");
+        assert_eq!(message.unformatted_len(), 20);
+        assert_eq!(message.as_formatted(), "Deployed api to prod");
+    }
+
+    #[test]
+    fn test_print_to() {
+        env::set_var("NO_COLOR", "1");
+
+        start_capture();
+        Message::info("out").print_to(Stream::Stdout);
+        Message::info("err").print_to(Stream::Stderr);
+        let capture = stop_capture();
+        assert_eq!(capture.stdout, "out\n");
+        assert_eq!(capture.stderr, "err\n");
+    }
+
+    #[test]
+    fn test_capture_data() {
+        env::set_var("NO_COLOR", "1");
+
+        start_capture();
+        let (result, data) = capture_data(|| {
+            print_stdout("data");
+            Message::info("diagnostic").print_to(Stream::Stdout);
+            Message::info("progress").print_formatted();
+            42
+        });
+        let capture = stop_capture();
+        assert_eq!(result, 42);
+        assert_eq!(data, "data\ndiagnostic\n");
+        assert_eq!(capture.stdout, "");
+        assert_eq!(capture.stderr, "progress\n");
+    }
+
+    #[test]
+    fn test_strip_colors() {
+        assert!(matches!(strip_colors("plain"), Cow::Borrowed("plain")));
+        assert_eq!(strip_colors("\x1b[1;32mok\x1b[0m done"), "ok done");
+        assert_eq!(strip_colors("\x1b[2K\x1b[38;5;208mé\x1b[0m"), "é");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("short", 10, ""), "short");
+        assert_eq!(
+            wrap("  (1) a long error message", 16, "      "),
+            "  (1) a long\n      error\n      message"
+        );
+    }
+
+    #[test]
+    fn test_icons() {
+        assert_eq!(MessageKind::Success.icon(Icons::Unicode), Some("✔"));
+        assert_eq!(MessageKind::Error.icon(Icons::Ascii), Some("[error]"));
+        assert_eq!(MessageKind::Hint.icon(Icons::Unicode), None);
+        assert_eq!(MessageKind::Info.icon(Icons::None), None);
+    }
+
+    #[test]
+    fn test_message_list() {
+        env::set_var("NO_COLOR", "1");
+
+        let list = MessageList::bulleted()
+            .push(Message::success("Built"))
+            .push(Message::error("Tests failed\nwith 2 errors"));
+        assert_eq!(
+            list.as_formatted(),
+            "- Built\n- Tests failed\n  with 2 errors"
+        );
+
+        let mut list = MessageList::numbered();
+        list.extend((1..=10).map(|i| Message::new(format!("Step {i}"))));
+        let formatted = list.as_formatted();
+        let lines = formatted.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], " 1. Step 1");
+        assert_eq!(lines[9], "10. Step 10");
+    }
+
     #[test]
     fn test_format_single() {
         env::set_var("NO_COLOR", "1");
@@ -488,26 +2259,114 @@ mod tests {
         assert_eq!(table, expected);
     }
 
+    struct DescribedValue(&'static str, &'static str, &'static str);
+
+    impl ObjectFormatter for DescribedValue {
+        type Header = &'static str;
+        type Mode = ();
+        type Output<'a> = &'a str;
+
+        fn headers(_mode: Option<()>) -> Vec<Self::Header> {
+            vec!["id", "name", "comment"]
+        }
+
+        fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> &str {
+            match *header {
+                "id" => self.0,
+                "name" => self.1,
+                _ => self.2,
+            }
+        }
+
+        fn description(header: &Self::Header) -> Option<&'static str> {
+            match *header {
+                "id" => Some("Identifier"),
+                "name" => Some("Display name"),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_single_descriptions() {
+        env::set_var("NO_COLOR", "1");
+
+        let value = DescribedValue("1", "a long name", "a comment longer than the name");
+        let expected = vec![
+            "id        1             Identifier",
+            "name      a long name   Display name",
+            "comment   a comment longer than the name",
+        ];
+        assert_eq!(value.format_single(None), expected);
+
+        let expected = vec!["id        Identifier", "name      Display name", "comment"];
+        assert_eq!(value.format_describe(None), expected);
+    }
+
+    #[test]
+    fn test_format_porcelain() {
+        let headers = TestValue::default_headers();
+        assert_eq!(
+            porcelain_header::<TestValue>(&headers),
+            "id\tlabel\ta very long header"
+        );
+        assert_eq!(
+            porcelain_row(&TestValue("1", "a\tb", ""), None, &headers),
+            "1\t\"a\tb\"\t"
+        );
+    }
+
+    #[test]
+    fn test_format_multiple_errors() {
+        env::set_var("NO_COLOR", "1");
+
+        let error = ShellUiError::Multiple(vec![
+            Error::other("Error 1").into(),
+            ShellUiError::Interrupt,
+            ShellUiError::warning("Warning 2"),
+        ]);
+        assert_eq!(
+            error.as_formatted(),
+            "2 errors occurred\n(1) Error 1\n(2) Warning 2"
+        );
+    }
+
     #[test]
     fn test_format_errors() {
         env::set_var("NO_COLOR", "1");
+        #[cfg(feature = "backtrace")]
+        crate::errors::set_show_backtrace(false);
 
         {
-            let result: Result<()> = Err(Error::other("Test"));
-            let error = result.unwrap_err().as_formatted();
+            let error = Error::other("Test").as_formatted().into_owned();
             assert_eq!(error, "Test")
         }
         {
             let result: Result<()> = Err(Error::other("Test")).with_context("Failure");
-            let error = result.unwrap_err().as_formatted();
+            let error = result.unwrap_err().as_formatted().into_owned();
             assert_eq!(error, "Failure\nCaused by:\n  (1) Test")
         }
         {
             let result: Result<()> = Err(Error::other("Error 2"))
                 .with_context("Error 1")
                 .with_context("Failure");
-            let error = result.unwrap_err().as_formatted();
+            let error = result.unwrap_err().as_formatted().into_owned();
             assert_eq!(error, "Failure\nCaused by:\n  (1) Error 1\n  (2) Error 2")
         }
+        {
+            let result: Result<()> = Err(Error::other("Error 2"))
+                .with_context_code("E_NOT_FOUND", "Error 1")
+                .with_context("Failure");
+            let error = result.unwrap_err().as_formatted().into_owned();
+            assert_eq!(
+                error,
+                "Failure [E_NOT_FOUND]\nCaused by:\n  (1) Error 1\n  (2) Error 2"
+            )
+        }
+        {
+            let result: Result<()> = None.with_context_code("E_AUTH", "Failure");
+            let error = result.unwrap_err().as_formatted().into_owned();
+            assert_eq!(error, "Failure [E_AUTH]")
+        }
     }
 }
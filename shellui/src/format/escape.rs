@@ -0,0 +1,131 @@
+//! Escaping of fields for machine-readable formats
+//!
+//! Fields are quoted following RFC 4180: fields containing the delimiter,
+//! a quote or a line break are enclosed in quotes, and quotes are doubled.
+//! Fields that would be evaluated as formulas by spreadsheets, like
+//! `=HYPERLINK(...)`, can be prefixed with a `'` to be kept as text.
+use std::borrow::Cow;
+
+/// Escaper of the fields of delimited records, like CSV or TSV
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FieldEscaper {
+    delimiter: char,
+    protect_formulas: bool,
+}
+
+impl FieldEscaper {
+    /// Escaper for comma-separated values
+    pub fn csv() -> Self {
+        FieldEscaper {
+            delimiter: ',',
+            protect_formulas: true,
+        }
+    }
+
+    /// Escaper for tab-separated values
+    pub fn tsv() -> Self {
+        FieldEscaper {
+            delimiter: '\t',
+            protect_formulas: true,
+        }
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Prefix fields starting like a formula with a `'`
+    ///
+    /// Enabled by default. Numbers, like `-1`, are never prefixed.
+    pub fn with_formula_protection(mut self, protect_formulas: bool) -> Self {
+        self.protect_formulas = protect_formulas;
+        self
+    }
+
+    pub fn delimiter(&self) -> char {
+        self.delimiter
+    }
+
+    /// Escape a field, borrowing it if it does not need to be escaped
+    pub fn escape<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        let field = if self.protect_formulas && is_formula(field) {
+            Cow::Owned(format!("'{field}"))
+        } else {
+            Cow::Borrowed(field)
+        };
+
+        let needs_quotes = field
+            .chars()
+            .any(|c| c == self.delimiter || matches!(c, '"' | '\n' | '\r'));
+        if needs_quotes {
+            Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+        } else {
+            field
+        }
+    }
+
+    /// Escape and join fields into a record, without line terminator
+    pub fn record<I, S>(&self, fields: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut record = String::new();
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                record.push(self.delimiter);
+            }
+            record.push_str(&self.escape(field.as_ref()));
+        }
+        record
+    }
+}
+
+fn is_formula(field: &str) -> bool {
+    match field.chars().next() {
+        Some('=' | '@' | '\t' | '\r') => true,
+        Some('+' | '-') => field.parse::<f64>().is_err(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let csv = FieldEscaper::csv();
+        assert!(matches!(csv.escape("plain"), Cow::Borrowed("plain")));
+        assert_eq!(csv.escape("a,b"), "\"a,b\"");
+        assert_eq!(csv.escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv.escape("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv.escape(""), "");
+
+        let tsv = FieldEscaper::tsv();
+        assert_eq!(tsv.escape("a,b"), "a,b");
+        assert_eq!(tsv.escape("a\tb"), "\"a\tb\"");
+    }
+
+    #[test]
+    fn test_escape_formulas() {
+        let csv = FieldEscaper::csv();
+        assert_eq!(csv.escape("=1+1"), "'=1+1");
+        assert_eq!(csv.escape("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv.escape("+33 6 12"), "'+33 6 12");
+        assert_eq!(csv.escape("-1.5"), "-1.5");
+        assert_eq!(csv.escape("=A1,B1"), "\"'=A1,B1\"");
+        assert_eq!(csv.with_formula_protection(false).escape("=1+1"), "=1+1");
+    }
+
+    #[test]
+    fn test_record() {
+        let csv = FieldEscaper::csv();
+        assert_eq!(csv.record(["id", "a, b", "c"]), "id,\"a, b\",c");
+        assert_eq!(
+            FieldEscaper::tsv().with_delimiter(';').record(["a;b", "c"]),
+            "\"a;b\";c"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Tables for maps
+//!
+//! Maps are printed through adapters, either as a two-column key and
+//! value table, or as rows with the key as an extra first column.
+use super::{AsFormatted, ObjectFormatter};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Key and value of a map, printed as a two-column table
+pub struct KeyValue<'a, K, V>(pub &'a K, pub &'a V);
+
+/// Cell of a `KeyValue` or `Keyed` table
+pub enum KeyCell<'a, K, V> {
+    Key(&'a K),
+    Value(V),
+}
+
+impl<K, V> AsFormatted for KeyCell<'_, K, V>
+where
+    K: AsFormatted,
+    V: AsFormatted,
+{
+    fn unformatted_len(&self) -> usize {
+        match self {
+            KeyCell::Key(key) => key.unformatted_len(),
+            KeyCell::Value(value) => value.unformatted_len(),
+        }
+    }
+
+    fn as_unformatted(&self) -> Cow<'_, str> {
+        match self {
+            KeyCell::Key(key) => key.as_unformatted(),
+            KeyCell::Value(value) => value.as_unformatted(),
+        }
+    }
+
+    fn as_formatted(&self) -> Cow<'_, str> {
+        match self {
+            KeyCell::Key(key) => key.as_formatted(),
+            KeyCell::Value(value) => value.as_formatted(),
+        }
+    }
+}
+
+impl<'a, K, V> ObjectFormatter for KeyValue<'a, K, V>
+where
+    K: AsFormatted,
+    V: AsFormatted,
+{
+    type Header = &'static str;
+    type Mode = ();
+    type Output<'b>
+        = KeyCell<'a, K, &'a V>
+    where
+        Self: 'b;
+
+    fn headers(_mode: Option<()>) -> Vec<Self::Header> {
+        vec!["Key", "Value"]
+    }
+
+    fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> Self::Output<'_> {
+        match *header {
+            "Key" => KeyCell::Key(self.0),
+            _ => KeyCell::Value(self.1),
+        }
+    }
+}
+
+/// Header of a `Keyed` table
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KeyedHeader<H> {
+    Key,
+    Value(H),
+}
+
+impl<H> AsRef<str> for KeyedHeader<H>
+where
+    H: AsRef<str>,
+{
+    fn as_ref(&self) -> &str {
+        match self {
+            KeyedHeader::Key => "Key",
+            KeyedHeader::Value(header) => header.as_ref(),
+        }
+    }
+}
+
+/// Value of a map with its key, printed as a row with the key as first column
+pub struct Keyed<'a, K, V>(pub &'a K, pub &'a V);
+
+impl<'a, K, V> ObjectFormatter for Keyed<'a, K, V>
+where
+    K: AsFormatted,
+    V: ObjectFormatter,
+{
+    type Header = KeyedHeader<V::Header>;
+    type Mode = V::Mode;
+    type Output<'b>
+        = KeyCell<'a, K, V::Output<'a>>
+    where
+        Self: 'b;
+
+    fn headers(mode: Option<Self::Mode>) -> Vec<Self::Header> {
+        let headers = V::headers(mode).into_iter().map(KeyedHeader::Value);
+        [KeyedHeader::Key].into_iter().chain(headers).collect()
+    }
+
+    fn format_value(&self, mode: Option<Self::Mode>, header: &Self::Header) -> Self::Output<'_> {
+        match header {
+            KeyedHeader::Key => KeyCell::Key(self.0),
+            KeyedHeader::Value(header) => KeyCell::Value(self.1.format_value(mode, header)),
+        }
+    }
+
+    fn description(header: &Self::Header) -> Option<&'static str> {
+        match header {
+            KeyedHeader::Key => None,
+            KeyedHeader::Value(header) => V::description(header),
+        }
+    }
+}
+
+/// Table adapters for maps, sorted by key
+///
+/// `map.key_values().print_table_default()` prints a key and value table,
+/// and `map.keyed_rows().print_table_default()` prints values as rows.
+pub trait MapTable<K, V> {
+    fn key_values(&self) -> Vec<KeyValue<'_, K, V>>;
+    fn keyed_rows(&self) -> Vec<Keyed<'_, K, V>>;
+}
+
+impl<K, V> MapTable<K, V> for BTreeMap<K, V> {
+    fn key_values(&self) -> Vec<KeyValue<'_, K, V>> {
+        self.iter()
+            .map(|(key, value)| KeyValue(key, value))
+            .collect()
+    }
+
+    fn keyed_rows(&self) -> Vec<Keyed<'_, K, V>> {
+        self.iter().map(|(key, value)| Keyed(key, value)).collect()
+    }
+}
+
+impl<K, V, S> MapTable<K, V> for HashMap<K, V, S>
+where
+    K: Eq + Hash + Ord,
+{
+    fn key_values(&self) -> Vec<KeyValue<'_, K, V>> {
+        let mut values = self
+            .iter()
+            .map(|(key, value)| KeyValue(key, value))
+            .collect::<Vec<_>>();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        values
+    }
+
+    fn keyed_rows(&self) -> Vec<Keyed<'_, K, V>> {
+        let mut values = self
+            .iter()
+            .map(|(key, value)| Keyed(key, value))
+            .collect::<Vec<_>>();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        values
+    }
+}
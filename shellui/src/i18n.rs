@@ -0,0 +1,137 @@
+//! Localization
+//!
+//! Shellui's own strings are identified by a key prefixed by `shellui-`,
+//! and fall back to English if no translation is available. Applications
+//! can translate their messages with the same mechanism, by installing a
+//! `Translator` with `set_translator` or `Context::translator`.
+//!
+//! Default strings use Fluent syntax for arguments, like `{ $label }`. With
+//! the `i18n` feature, `FluentTranslator` loads Fluent resources.
+#[cfg(feature = "i18n")]
+use fluent_bundle::concurrent::FluentBundle;
+#[cfg(feature = "i18n")]
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::env;
+#[cfg(feature = "i18n")]
+use std::io::{Error, Result};
+use std::sync::RwLock;
+#[cfg(feature = "i18n")]
+use unic_langid::LanguageIdentifier;
+
+static TRANSLATOR: RwLock<Option<Box<dyn Translator>>> = RwLock::new(None);
+
+/// Message translation
+pub trait Translator: Send + Sync {
+    /// Translate a message, returning `None` if there is no translation
+    fn translate(&self, key: &str, args: &[(&str, &str)]) -> Option<String>;
+}
+
+/// Set the translator used for all messages
+pub fn set_translator(translator: Box<dyn Translator>) {
+    *TRANSLATOR.write().unwrap() = Some(translator);
+}
+
+/// Translate a message, using `default` if there is no translation
+pub fn tr(key: &str, default: &str) -> String {
+    tr_args(key, default, &[])
+}
+
+/// Translate a message with arguments, using `default` if there is no translation
+pub fn tr_args(key: &str, default: &str, args: &[(&str, &str)]) -> String {
+    let translator = TRANSLATOR.read().unwrap();
+    if let Some(message) = translator
+        .as_ref()
+        .and_then(|translator| translator.translate(key, args))
+    {
+        return message;
+    }
+
+    args.iter()
+        .fold(default.to_string(), |message, (name, value)| {
+            message.replace(&format!("{{ ${name} }}"), value)
+        })
+}
+
+/// Language of the user, like `fr-FR`
+///
+/// Read from `LC_ALL`, `LC_MESSAGES` or `LANG`.
+pub fn env_language() -> Option<String> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|locale| !locale.is_empty())?;
+    let language = locale.split(['.', '@']).next()?.replace('_', "-");
+    match language.as_str() {
+        "" | "C" | "POSIX" => None,
+        _ => Some(language),
+    }
+}
+
+/// Translator based on Fluent resources
+#[cfg(feature = "i18n")]
+pub struct FluentTranslator {
+    bundle: FluentBundle<FluentResource>,
+}
+
+#[cfg(feature = "i18n")]
+impl FluentTranslator {
+    /// Create a translator for a language, like `fr-FR`, from Fluent sources
+    pub fn new(language: &str, sources: &[&str]) -> Result<Self> {
+        let language = language
+            .parse::<LanguageIdentifier>()
+            .map_err(Error::other)?;
+        let mut bundle = FluentBundle::new_concurrent(vec![language]);
+        // Unicode isolation marks are not rendered properly by terminals
+        bundle.set_use_isolating(false);
+        for source in sources {
+            let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+                Error::other(format!("Invalid Fluent resource: {errors:?}"))
+            })?;
+            bundle
+                .add_resource(resource)
+                .map_err(|errors| Error::other(format!("Invalid Fluent resource: {errors:?}")))?;
+        }
+        Ok(FluentTranslator { bundle })
+    }
+}
+
+#[cfg(feature = "i18n")]
+impl Translator for FluentTranslator {
+    fn translate(&self, key: &str, args: &[(&str, &str)]) -> Option<String> {
+        let pattern = self.bundle.get_message(key)?.value()?;
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, *value);
+        }
+        let mut errors = Vec::new();
+        let message = self
+            .bundle
+            .format_pattern(pattern, Some(&fluent_args), &mut errors);
+        errors.is_empty().then(|| message.into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tr_default() {
+        assert_eq!(
+            tr_args("test-missing", "Invalid { $name }", &[("name", "value")]),
+            "Invalid value"
+        );
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn test_fluent_translator() {
+        let translator =
+            FluentTranslator::new("fr-FR", &["test-invalid = { $name } invalide"]).unwrap();
+        assert_eq!(
+            translator.translate("test-invalid", &[("name", "Valeur")]),
+            Some("Valeur invalide".to_string())
+        );
+        assert_eq!(translator.translate("test-missing", &[]), None);
+    }
+}
@@ -1,9 +1,150 @@
-use inquire::{InquireError, Text};
-use std::io::{Error, ErrorKind, Result};
+mod form;
+pub mod result;
+
+pub use self::form::Form;
+use crate::format::{table_rows, theme, AsFormatted, Icons, Message, ObjectFormatter};
+use crate::i18n::{tr, tr_args};
+use crate::shell::active_config;
+use crate::spinner;
+use clap::ValueEnum;
+use colored::control::SHOULD_COLORIZE;
+use colored::Colorize;
+use crossterm::cursor;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::queue;
+use crossterm::style::Print;
+use crossterm::terminal::{self, ClearType};
+use inquire::error::InquireResult;
+use inquire::ui::{Color, ErrorMessageRenderConfig, RenderConfig, StyleSheet, Styled};
+use inquire::validator::Validation;
+use inquire::{
+    Confirm, CustomUserError, Editor, InquireError, MultiSelect, Password, PasswordDisplayMode,
+    Select, Text,
+};
+pub use secrecy::{ExposeSecret, SecretString};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, stdin, Error, ErrorKind, IsTerminal, Result, Write};
+use std::ops::RangeInclusive;
+use std::path::{is_separator, Path, PathBuf, MAIN_SEPARATOR_STR};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+static NON_INTERACTIVE_INPUT: Mutex<Option<VecDeque<String>>> = Mutex::new(None);
+
+const INPUT_ENV: &str = "SHELLUI_INPUT";
+
+const MULTI_SELECT_PAGE_SIZE: usize = 10;
+
+/// Behavior of prompts when the input is not interactive
+///
+/// Set by `ShellConfig::non_interactive_policy`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum NonInteractivePolicy {
+    /// Fail with an error naming the missing value
+    #[default]
+    Fail,
+    /// Read the answers, one per line, from the file set in `SHELLUI_INPUT`
+    ReadInput,
+}
+
+thread_local! {
+    /// Answers of the prompts of the current thread, read instead of the terminal
+    static SCRIPTED_INPUT: RefCell<Option<VecDeque<String>>> = const { RefCell::new(None) };
+}
+
+/// Check if prompts can be displayed
+pub fn is_interactive() -> bool {
+    SCRIPTED_INPUT.with_borrow(Option::is_none) && stdin().is_terminal()
+}
+
+/// Call a function, answering its prompts with `lines`
+///
+/// Prompts are not interactive while the function runs.
+pub(crate) fn with_scripted_input<F, R>(lines: &[&str], f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let lines = lines.iter().map(ToString::to_string).collect();
+    let previous = SCRIPTED_INPUT.replace(Some(lines));
+    let result = f();
+    SCRIPTED_INPUT.set(previous);
+    result
+}
+
+/// Optional parameters of prompts
+#[derive(Clone, Debug, Default)]
+pub struct PromptOptions<'a> {
+    placeholder: Option<&'a str>,
+    help_message: Option<&'a str>,
+}
+
+impl<'a> PromptOptions<'a> {
+    pub fn new() -> Self {
+        PromptOptions::default()
+    }
+
+    /// Text displayed when the input is empty, e.g. the expected format
+    pub fn with_placeholder(mut self, placeholder: &'a str) -> Self {
+        self.placeholder = Some(placeholder);
+        self
+    }
+
+    /// Help message displayed below the prompt
+    pub fn with_help_message(mut self, help_message: &'a str) -> Self {
+        self.help_message = Some(help_message);
+        self
+    }
+}
+
+/// Requirement on the path asked by [`get_path_input`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PathRequirement {
+    /// The path is an existing file
+    ExistingFile,
+    /// The path is an existing directory
+    ExistingDir,
+    /// Nothing exists at the path
+    New,
+}
+
+impl PathRequirement {
+    fn validate(&self, path: &Path) -> std::result::Result<(), String> {
+        if path.as_os_str().is_empty() {
+            return Err(tr("shellui-type-path", "Please type a path"));
+        }
+
+        match self {
+            PathRequirement::ExistingFile if !path.is_file() => Err(tr_args(
+                "shellui-path-not-file",
+                "{ $path } is not an existing file",
+                &[("path", &path.display().to_string())],
+            )),
+            PathRequirement::ExistingDir if !path.is_dir() => Err(tr_args(
+                "shellui-path-not-dir",
+                "{ $path } is not an existing directory",
+                &[("path", &path.display().to_string())],
+            )),
+            PathRequirement::New if path.exists() => Err(tr_args(
+                "shellui-path-exists",
+                "{ $path } already exists",
+                &[("path", &path.display().to_string())],
+            )),
+            _ => Ok(()),
+        }
+    }
+}
 
 pub trait OrElseQuery {
     type Output;
     fn or_else_query(self, label: &str) -> Result<Self::Output>;
+    fn or_else_query_with_default(self, label: &str, default: &str) -> Result<Self::Output>;
+    fn or_else_query_optional(self, label: &str) -> Result<Option<Self::Output>>;
     fn or_else_query_with<F>(self, request: F) -> Result<Self::Output>
     where
         F: FnOnce(fn(&str) -> Result<Self::Output>) -> Result<Self::Output>;
@@ -22,6 +163,20 @@ where
         }
     }
 
+    fn or_else_query_with_default(self, label: &str, default: &str) -> Result<Self::Output> {
+        match self {
+            Some(value) => Ok(value.to_string()),
+            None => get_string_input_with_default(label, default),
+        }
+    }
+
+    fn or_else_query_optional(self, label: &str) -> Result<Option<Self::Output>> {
+        match self {
+            Some(value) => Ok(Some(value.to_string())),
+            None => get_optional_string_input(label),
+        }
+    }
+
     fn or_else_query_with<F>(self, request: F) -> Result<Self::Output>
     where
         F: FnOnce(fn(&str) -> Result<Self::Output>) -> Result<Self::Output>,
@@ -33,18 +188,1145 @@ where
     }
 }
 
+pub trait OrElseQueryParse {
+    type Output;
+    fn or_else_query_parse(self, label: &str) -> Result<Self::Output>;
+}
+
+impl<T> OrElseQueryParse for Option<T>
+where
+    T: FromStr,
+{
+    type Output = T;
+
+    fn or_else_query_parse(self, label: &str) -> Result<Self::Output> {
+        match self {
+            Some(value) => Ok(value),
+            None => get_parsed_input(label),
+        }
+    }
+}
+
+pub trait OrElseSelect {
+    type Output;
+    fn or_else_select(self, label: &str) -> Result<Self::Output>;
+}
+
+impl<T> OrElseSelect for Option<T>
+where
+    T: ValueEnum + Display,
+{
+    type Output = T;
+
+    fn or_else_select(self, label: &str) -> Result<Self::Output> {
+        match self {
+            Some(value) => Ok(value),
+            None => {
+                let options = T::value_variants()
+                    .iter()
+                    .filter(|value| {
+                        value
+                            .to_possible_value()
+                            .is_some_and(|value| !value.is_hide_set())
+                    })
+                    .cloned()
+                    .collect();
+                get_select_input(label, options)
+            }
+        }
+    }
+}
+
 pub fn get_string_input(label: &str) -> Result<String> {
-    let name = Text::new(label).prompt();
-    match name {
-        Ok(value) => Ok(value),
-        Err(error) => match error {
-            InquireError::NotTTY => Err(Error::other("Not a TTY")),
-            InquireError::InvalidConfiguration(error) => Err(Error::other(error)),
-            InquireError::IO(error) => Err(error),
-            InquireError::OperationCanceled | InquireError::OperationInterrupted => {
-                Err(Error::new(ErrorKind::Interrupted, "Interrupted"))
-            }
-            InquireError::Custom(error) => Err(Error::other(error)),
-        },
+    get_string_input_with_options(label, &PromptOptions::default())
+}
+
+pub fn get_string_input_with_options(label: &str, options: &PromptOptions) -> Result<String> {
+    if let Some(input) = non_interactive_input(label) {
+        return input;
+    }
+    run_prompt(|| text_prompt(label, options).prompt())
+}
+
+/// Ask for an optional string
+///
+/// Returns `None` if nothing is typed.
+pub fn get_optional_string_input(label: &str) -> Result<Option<String>> {
+    let input = get_string_input(label)?;
+    Ok(Some(input).filter(|input| !input.is_empty()))
+}
+
+/// Ask for a value parsed into `T`
+///
+/// Asks again if the input cannot be parsed.
+pub fn get_parsed_input<T>(label: &str) -> Result<T>
+where
+    T: FromStr,
+{
+    get_parsed_input_with_options(label, &PromptOptions::default())
+}
+
+pub fn get_parsed_input_with_options<T>(label: &str, options: &PromptOptions) -> Result<T>
+where
+    T: FromStr,
+{
+    let input = match non_interactive_input(label) {
+        Some(input) => input?,
+        None => get_validated_input(label, options, |input| {
+            input
+                .parse::<T>()
+                .map(|_| ())
+                .map_err(|_| tr("shellui-invalid-value", "Invalid value"))
+        })?,
+    };
+    input
+        .parse()
+        .map_err(|_| Error::other(invalid_value(label)))
+}
+
+/// Ask for a string, returning `default` if nothing is typed
+pub fn get_string_input_with_default(label: &str, default: &str) -> Result<String> {
+    if let Some(input) = non_interactive_input(label) {
+        return input.map(|input| {
+            if input.is_empty() {
+                default.to_string()
+            } else {
+                input
+            }
+        });
+    }
+    run_prompt(|| {
+        text_prompt(label, &PromptOptions::default())
+            .with_default(default)
+            .prompt()
+    })
+}
+
+/// Ask for a string, validated by `validator`
+///
+/// Asks again, displaying the validation error, until the input is valid.
+pub fn get_string_input_with_validator<F>(label: &str, validator: F) -> Result<String>
+where
+    F: 'static + Clone + Fn(&str) -> std::result::Result<(), String>,
+{
+    if let Some(input) = non_interactive_input(label) {
+        let input = input?;
+        validator(&input).map_err(Error::other)?;
+        return Ok(input);
+    }
+    get_validated_input(label, &PromptOptions::default(), move |input| {
+        validator(input).map_err(|error| Message::error(error).as_formatted().into_owned())
+    })
+}
+
+/// Ask for a string, with autocompletion
+///
+/// `suggester` provides the suggestions matching the current input.
+pub fn get_string_input_with_suggestions<F>(label: &str, suggester: F) -> Result<String>
+where
+    F: 'static + Clone + Fn(&str) -> Vec<String>,
+{
+    if let Some(input) = non_interactive_input(label) {
+        return input;
+    }
+    run_prompt(|| {
+        text_prompt(label, &PromptOptions::default())
+            .with_autocomplete(move |input: &str| Ok::<_, CustomUserError>(suggester(input)))
+            .prompt()
+    })
+}
+
+pub fn get_select_input<T>(label: &str, options: Vec<T>) -> Result<T>
+where
+    T: Display,
+{
+    if let Some(input) = non_interactive_input(label) {
+        let input = input?;
+        return options
+            .into_iter()
+            .find(|option| option.to_string() == input)
+            .ok_or_else(|| Error::other(invalid_value(label)));
+    }
+    run_prompt(|| {
+        Select::new(label, options)
+            .with_render_config(render_config())
+            .prompt()
+    })
+}
+
+/// Ask to select any number of options
+///
+/// When the input is not interactive, the answer is the comma-separated
+/// list of the selected options.
+pub fn get_multi_select_input<T>(label: &str, options: Vec<T>) -> Result<Vec<T>>
+where
+    T: Display,
+{
+    if let Some(input) = non_interactive_input(label) {
+        let input = input?;
+        let selected = input
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>();
+        let mut options = options.into_iter().map(Some).collect::<Vec<_>>();
+        return selected
+            .into_iter()
+            .map(|value| {
+                options
+                    .iter_mut()
+                    .find(|option| {
+                        option
+                            .as_ref()
+                            .is_some_and(|option| option.to_string() == value)
+                    })
+                    .and_then(Option::take)
+                    .ok_or_else(|| Error::other(invalid_value(label)))
+            })
+            .collect();
+    }
+
+    let help_message = tr(
+        "shellui-multi-select-help",
+        "↑↓ to move, space to select, → to select all, ← to select none, type to filter",
+    );
+    run_prompt(|| {
+        MultiSelect::new(label, options)
+            .with_render_config(render_config())
+            .with_page_size(MULTI_SELECT_PAGE_SIZE)
+            .with_help_message(&help_message)
+            .prompt()
+    })
+}
+
+/// Row of a table displayed by `pick_from_table`
+struct TableRow {
+    index: usize,
+    line: String,
+}
+
+impl Display for TableRow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.line)
+    }
+}
+
+/// Ask to pick an element displayed as a table row
+///
+/// When the input is not interactive, the answer is matched against the
+/// first column of the table.
+pub fn pick_from_table<'a, T>(label: &str, items: &'a [T], mode: Option<T::Mode>) -> Result<&'a T>
+where
+    T: ObjectFormatter,
+{
+    if let Some(input) = non_interactive_input(label) {
+        let input = input?;
+        return find_row(items, mode, &input).ok_or_else(|| Error::other(invalid_value(label)));
+    }
+
+    let (label, rows) = picker_rows(label, items, mode);
+    run_prompt(|| {
+        Select::new(&label, rows)
+            .with_render_config(render_config())
+            .prompt()
+    })
+    .map(|row| &items[row.index])
+}
+
+/// Label and options of `pick_from_table`, with one option per element
+///
+/// The header is displayed below the label, aligned with the rows.
+fn picker_rows<T>(label: &str, items: &[T], mode: Option<T::Mode>) -> (String, Vec<TableRow>)
+where
+    T: ObjectFormatter,
+{
+    let (header, lines) = table_rows(items, mode);
+    let rows = lines
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| TableRow { index, line })
+        .collect();
+    (format!("{label}\n  {header}"), rows)
+}
+
+fn find_row<'a, T>(items: &'a [T], mode: Option<T::Mode>, input: &str) -> Option<&'a T>
+where
+    T: ObjectFormatter,
+{
+    let header = T::headers(mode.clone()).into_iter().next()?;
+    items
+        .iter()
+        .find(|item| item.format_value(mode.clone(), &header).as_unformatted() == input)
+}
+
+/// Ask for a number in a range
+///
+/// The range is displayed in the empty input. Asks again if the input
+/// cannot be parsed or is out of range, so the returned number is always
+/// in the range.
+pub fn get_number_input<T>(label: &str, range: RangeInclusive<T>) -> Result<T>
+where
+    T: 'static + Clone + Display + FromStr + PartialOrd,
+{
+    let (start, end) = (range.start().to_string(), range.end().to_string());
+    let bounds = [("start", start.as_str()), ("end", end.as_str())];
+    let input = match non_interactive_input(label) {
+        Some(input) => input?,
+        None => {
+            let placeholder = tr_args("shellui-number-range", "{ $start } to { $end }", &bounds);
+            let error_message = tr_args(
+                "shellui-type-number",
+                "Please type a number between { $start } and { $end }",
+                &bounds,
+            );
+            let options = PromptOptions::new().with_placeholder(&placeholder);
+            let range = range.clone();
+            get_validated_input(label, &options, move |input| {
+                parse_bounded(input, &range)
+                    .map(|_| ())
+                    .ok_or_else(|| error_message.clone())
+            })?
+        }
+    };
+    parse_bounded(&input, &range).ok_or_else(|| Error::other(invalid_value(label)))
+}
+
+fn parse_bounded<T>(input: &str, range: &RangeInclusive<T>) -> Option<T>
+where
+    T: FromStr + PartialOrd,
+{
+    input
+        .trim()
+        .parse()
+        .ok()
+        .filter(|value| range.contains(value))
+}
+
+/// Ask for a path
+///
+/// Paths are completed from the filesystem, and validated
+/// against `requirement`.
+pub fn get_path_input(label: &str, requirement: PathRequirement) -> Result<PathBuf> {
+    let validator = move |input: &str| requirement.validate(Path::new(input));
+    let input = match non_interactive_input(label) {
+        Some(input) => {
+            let input = input?;
+            validator(&input).map_err(Error::other)?;
+            input
+        }
+        None => {
+            let prompt = text_prompt(label, &PromptOptions::default()).with_autocomplete(
+                move |input: &str| Ok::<_, CustomUserError>(suggest_paths(input, requirement)),
+            );
+            prompt_validated(prompt, validator)?
+        }
+    };
+    Ok(PathBuf::from(input))
+}
+
+/// Paths starting with the input, directories ending with a separator
+pub(crate) fn suggest_paths(input: &str, requirement: PathRequirement) -> Vec<String> {
+    let (dir, prefix) = split_path(input);
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut suggestions = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().ok()?.is_dir();
+            if !name.starts_with(prefix) || (requirement == PathRequirement::ExistingDir && !is_dir)
+            {
+                return None;
+            }
+            let separator = if is_dir { MAIN_SEPARATOR_STR } else { "" };
+            Some(format!("{dir}{name}{separator}"))
+        })
+        .collect::<Vec<_>>();
+    suggestions.sort();
+    suggestions
+}
+
+/// Split a path being typed into its directory and the prefix of a name
+///
+/// On Windows, a drive letter without separator, like `C:`, is the
+/// current directory of that drive.
+fn split_path(input: &str) -> (&str, &str) {
+    match input.rfind(is_separator) {
+        Some(index) => input.split_at(index + 1),
+        None if cfg!(windows) && is_drive(input) => input.split_at(2),
+        None => ("", input),
+    }
+}
+
+fn is_drive(input: &str) -> bool {
+    let bytes = input.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Ask for a long text using an external editor
+///
+/// Opens `$VISUAL` or `$EDITOR` with the initial text. When the input is
+/// not interactive, the text is a single line, the initial text being
+/// kept if it is empty.
+pub fn get_editor_input(label: &str, initial: &str) -> Result<String> {
+    if let Some(input) = non_interactive_input(label) {
+        return input.map(|input| {
+            if input.is_empty() {
+                initial.to_string()
+            } else {
+                input
+            }
+        });
+    }
+    run_prompt(|| {
+        Editor::new(label)
+            .with_render_config(render_config())
+            .with_predefined_text(initial)
+            .prompt()
+    })
+}
+
+/// Ask for a secret
+///
+/// The input is not echoed, and is never recorded in the shell history.
+pub fn get_secret_input(label: &str) -> Result<SecretString> {
+    if let Some(input) = non_interactive_input(label) {
+        return input.map(SecretString::from);
+    }
+    prompt_secret(Password::new(label).without_confirmation())
+}
+
+/// Ask for a secret, asking for it a second time as a confirmation
+///
+/// The secret is only read once when the input is not interactive.
+pub fn get_secret_input_with_confirmation(label: &str) -> Result<SecretString> {
+    if let Some(input) = non_interactive_input(label) {
+        return input.map(SecretString::from);
+    }
+    prompt_secret(Password::new(label))
+}
+
+/// Ask for a secret, displaying a mask for each typed character
+///
+/// The mask is `•`, or `*` with ASCII icons, unless a custom one is
+/// passed. With `reveal_last`, the last typed character is shown
+/// briefly before being masked, to avoid typos in long tokens.
+pub fn get_masked_input(
+    label: &str,
+    mask: Option<char>,
+    reveal_last: bool,
+) -> Result<SecretString> {
+    if let Some(input) = non_interactive_input(label) {
+        return input.map(SecretString::from);
+    }
+    let mask = mask.unwrap_or(match theme().icons {
+        Icons::Ascii => '*',
+        Icons::None | Icons::Unicode => '•',
+    });
+    if reveal_last {
+        return read_masked(label, mask).map(SecretString::from);
+    }
+
+    let mut render_config = render_config();
+    render_config.password_mask = mask;
+    run_prompt(|| {
+        Password::new(label)
+            .without_confirmation()
+            .with_display_mode(PasswordDisplayMode::Masked)
+            .with_render_config(render_config)
+            .prompt()
+    })
+    .map(SecretString::from)
+}
+
+/// Read a masked secret, the last typed character being shown for
+/// `REVEAL_DURATION`
+///
+/// Inquire masks every character, so the prompt is drawn the same way
+/// with crossterm instead.
+fn read_masked(label: &str, mask: char) -> Result<String> {
+    spinner::stop();
+    let mut input = MaskedInput::default();
+    let _raw_mode = RawMode::enable()?;
+    let mut stderr = io::stderr();
+    draw_masked(&mut stderr, "?", theme().info, label, &input.display(mask))?;
+    loop {
+        if input.revealed && !event::poll(REVEAL_DURATION)? {
+            input.revealed = false;
+        } else if let Event::Key(key) = event::read()? {
+            match input.handle_key(key) {
+                MaskedKey::Edited => {}
+                MaskedKey::Submit => break,
+                MaskedKey::Interrupt => {
+                    write!(stderr, "\r\n")?;
+                    return Err(map_error(InquireError::OperationInterrupted));
+                }
+            }
+        }
+        draw_masked(&mut stderr, "?", theme().info, label, &input.display(mask))?;
+    }
+
+    input.revealed = false;
+    draw_masked(
+        &mut stderr,
+        ">",
+        theme().success,
+        label,
+        &input.display(mask),
+    )?;
+    write!(stderr, "\r\n")?;
+    Ok(input.value)
+}
+
+fn draw_masked(
+    stderr: &mut io::Stderr,
+    prefix: &str,
+    color: colored::Color,
+    label: &str,
+    masked: &str,
+) -> Result<()> {
+    queue!(
+        stderr,
+        cursor::MoveToColumn(0),
+        terminal::Clear(ClearType::CurrentLine),
+        Print(format!(
+            "{} {label} {}",
+            prefix.color(color),
+            masked.color(theme().info)
+        ))
+    )?;
+    stderr.flush()
+}
+
+const REVEAL_DURATION: Duration = Duration::from_millis(800);
+
+/// Raw mode, disabled when dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// State of a masked input
+#[derive(Default)]
+struct MaskedInput {
+    value: String,
+    /// If the last typed character is shown
+    revealed: bool,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum MaskedKey {
+    Edited,
+    Submit,
+    Interrupt,
+}
+
+impl MaskedInput {
+    fn handle_key(&mut self, key: KeyEvent) -> MaskedKey {
+        if key.kind == KeyEventKind::Release {
+            return MaskedKey::Edited;
+        }
+        match key.code {
+            KeyCode::Enter => MaskedKey::Submit,
+            KeyCode::Esc => MaskedKey::Interrupt,
+            KeyCode::Char('c' | 'd') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                MaskedKey::Interrupt
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.value.push(c);
+                self.revealed = true;
+                MaskedKey::Edited
+            }
+            KeyCode::Backspace => {
+                self.value.pop();
+                self.revealed = false;
+                MaskedKey::Edited
+            }
+            _ => MaskedKey::Edited,
+        }
+    }
+
+    fn display(&self, mask: char) -> String {
+        let mut chars = self.value.chars();
+        let last = if self.revealed {
+            chars.next_back()
+        } else {
+            None
+        };
+        chars.map(|_| mask).chain(last).collect()
+    }
+}
+
+fn prompt_secret(prompt: Password) -> Result<SecretString> {
+    run_prompt(|| {
+        prompt
+            .with_display_mode(PasswordDisplayMode::Hidden)
+            .with_render_config(render_config())
+            .prompt()
+    })
+    .map(SecretString::from)
+}
+
+/// Check if yes is answered to all confirmations, set by `ShellConfig::assume_yes`
+fn assume_yes() -> bool {
+    active_config(|config| config.assume_yes)
+}
+
+/// Ask for a confirmation
+///
+/// Returns `true` without prompting if yes is assumed. When the input is
+/// not interactive, the answer is read with
+/// [`NonInteractivePolicy::ReadInput`], and an error is returned otherwise.
+pub fn confirm(label: &str, default: bool) -> Result<bool> {
+    if assume_yes() {
+        return Ok(true);
+    }
+    if let Some(answer) = non_interactive_confirmation(label, default) {
+        return answer;
+    }
+
+    let error_message = tr("shellui-confirm-invalid", "Type y for yes or n for no");
+    run_prompt(|| {
+        Confirm::new(label)
+            .with_render_config(render_config())
+            .with_default(default)
+            .with_error_message(&error_message)
+            .prompt()
+    })
+}
+
+/// Ask for a confirmation of a destructive operation
+///
+/// The prompt is styled as an error, and the answer is no unless the full
+/// word `yes` is typed. Returns `true` without prompting if yes is assumed.
+/// When the input is not interactive, the answer is read with
+/// [`NonInteractivePolicy::ReadInput`], and is no otherwise.
+pub fn confirm_destructive(label: &str) -> Result<bool> {
+    if assume_yes() {
+        return Ok(true);
+    }
+    confirm_typed(label, &tr("shellui-confirm-yes", "yes"))
+}
+
+/// Ask for a confirmation of a dangerous operation, by typing a word
+///
+/// The word is typically the name of the resource to delete. Any other
+/// answer is no, like with [`confirm_destructive`]. Yes is never assumed,
+/// so that the word is always typed, or read from the non-interactive input.
+pub fn confirm_destructive_typed(label: &str, expected: &str) -> Result<bool> {
+    confirm_typed(label, expected)
+}
+
+fn confirm_typed(label: &str, expected: &str) -> Result<bool> {
+    if !is_interactive() {
+        return match active_config(|config| config.non_interactive_policy) {
+            NonInteractivePolicy::Fail => Ok(false),
+            NonInteractivePolicy::ReadInput => {
+                read_next_input(label).map(|input| input.trim() == expected)
+            }
+        };
+    }
+
+    let help_message = tr_args(
+        "shellui-confirm-type",
+        "Type \"{ $expected }\" to confirm",
+        &[("expected", expected)],
+    );
+    let input = run_prompt(|| {
+        Text::new(label)
+            .with_render_config(danger_render_config())
+            .with_help_message(&help_message)
+            .prompt()
+    })?;
+    Ok(input.trim() == expected)
+}
+
+fn invalid_value(label: &str) -> String {
+    tr_args(
+        "shellui-invalid-value-for",
+        "Invalid value for \"{ $label }\"",
+        &[("label", label)],
+    )
+}
+
+fn get_validated_input<F>(label: &str, options: &PromptOptions, validator: F) -> Result<String>
+where
+    F: 'static + Clone + Fn(&str) -> std::result::Result<(), String>,
+{
+    prompt_validated(text_prompt(label, options), validator)
+}
+
+fn prompt_validated<F>(prompt: Text, validator: F) -> Result<String>
+where
+    F: 'static + Clone + Fn(&str) -> std::result::Result<(), String>,
+{
+    let max_retries = active_config(|config| config.max_retries);
+    let attempts = Rc::new(Cell::new(0));
+    let error_label = prompt.message.to_string();
+    let prompt = prompt.with_validator(move |input: &str| match validator(input) {
+        Ok(()) => Ok(Validation::Valid),
+        Err(error) => {
+            attempts.set(attempts.get() + 1);
+            match max_retries {
+                Some(max_retries) if attempts.get() > max_retries => Err(tr_args(
+                    "shellui-too-many-invalid-answers",
+                    "Too many invalid answers for \"{ $label }\"",
+                    &[("label", &error_label)],
+                )
+                .into()),
+                _ => Ok(Validation::Invalid(error.into())),
+            }
+        }
+    });
+    run_prompt(|| prompt.prompt())
+}
+
+fn text_prompt<'a>(label: &'a str, options: &PromptOptions<'a>) -> Text<'a> {
+    let mut prompt = Text::new(label).with_render_config(render_config());
+    prompt.placeholder = options.placeholder;
+    prompt.help_message = options.help_message;
+    prompt
+}
+
+/// Run an interactive prompt
+///
+/// The spinner is stopped first, as it would draw over the prompt.
+fn run_prompt<T, F>(prompt: F) -> Result<T>
+where
+    F: FnOnce() -> InquireResult<T>,
+{
+    spinner::stop();
+    prompt().map_err(map_error)
+}
+
+fn render_config() -> RenderConfig<'static> {
+    if !SHOULD_COLORIZE.should_colorize() {
+        return RenderConfig::empty();
+    }
+
+    let theme = theme();
+    let info = to_inquire_color(theme.info);
+    let success = to_inquire_color(theme.success);
+    let error = to_inquire_color(theme.error);
+    let hint = to_inquire_color(theme.hint);
+    RenderConfig::default_colored()
+        .with_prompt_prefix(Styled::new("?").with_fg(info))
+        .with_answered_prompt_prefix(Styled::new(">").with_fg(success))
+        .with_answer(StyleSheet::new().with_fg(info))
+        .with_help_message(StyleSheet::new().with_fg(hint))
+        .with_highlighted_option_prefix(Styled::new(">").with_fg(info))
+        .with_selected_option(Some(StyleSheet::new().with_fg(info)))
+        .with_selected_checkbox(Styled::new("[x]").with_fg(success))
+        .with_canceled_prompt_indicator(Styled::new("<canceled>").with_fg(error))
+        .with_error_message(
+            ErrorMessageRenderConfig::default_colored()
+                .with_prefix(Styled::new("#").with_fg(error))
+                .with_message(StyleSheet::new().with_fg(error)),
+        )
+}
+
+/// Configuration of confirmations for destructive operations
+fn danger_render_config() -> RenderConfig<'static> {
+    let render_config = render_config().with_prompt_prefix(Styled::new("!"));
+    if !SHOULD_COLORIZE.should_colorize() {
+        return render_config;
+    }
+
+    let error = to_inquire_color(theme().error);
+    let mut render_config = render_config
+        .with_prompt_prefix(Styled::new("!").with_fg(error))
+        .with_answer(StyleSheet::new().with_fg(error));
+    render_config.prompt = StyleSheet::new().with_fg(error);
+    render_config
+}
+
+fn to_inquire_color(color: colored::Color) -> Color {
+    match color {
+        colored::Color::Black => Color::Black,
+        colored::Color::Red => Color::DarkRed,
+        colored::Color::Green => Color::DarkGreen,
+        colored::Color::Yellow => Color::DarkYellow,
+        colored::Color::Blue => Color::DarkBlue,
+        colored::Color::Magenta => Color::DarkMagenta,
+        colored::Color::Cyan => Color::DarkCyan,
+        colored::Color::White => Color::Grey,
+        colored::Color::BrightBlack => Color::DarkGrey,
+        colored::Color::BrightRed => Color::LightRed,
+        colored::Color::BrightGreen => Color::LightGreen,
+        colored::Color::BrightYellow => Color::LightYellow,
+        colored::Color::BrightBlue => Color::LightBlue,
+        colored::Color::BrightMagenta => Color::LightMagenta,
+        colored::Color::BrightCyan => Color::LightCyan,
+        colored::Color::BrightWhite => Color::White,
+        colored::Color::TrueColor { r, g, b } => Color::Rgb { r, g, b },
+    }
+}
+
+fn non_interactive_input(label: &str) -> Option<Result<String>> {
+    if is_interactive() {
+        return None;
+    }
+
+    let policy = active_config(|config| config.non_interactive_policy);
+    let input = match policy {
+        NonInteractivePolicy::Fail => Err(not_interactive(label)),
+        NonInteractivePolicy::ReadInput => read_next_input(label),
+    };
+    Some(input)
+}
+
+fn not_interactive(label: &str) -> Error {
+    Error::other(tr_args(
+        "shellui-input-not-interactive",
+        "\"{ $label }\" is required, but the input is not interactive",
+        &[("label", label)],
+    ))
+}
+
+fn read_next_input(label: &str) -> Result<String> {
+    let scripted = SCRIPTED_INPUT.with_borrow_mut(|input| input.as_mut().map(VecDeque::pop_front));
+    let mut input = match scripted {
+        Some(line) => return line.ok_or_else(|| input_exhausted(label)),
+        None => NON_INTERACTIVE_INPUT.lock().unwrap(),
+    };
+    if input.is_none() {
+        let path = env::var(INPUT_ENV).map_err(|_| {
+            Error::other(tr_args(
+                "shellui-input-not-set",
+                "\"{ $label }\" is required, but { $variable } is not set",
+                &[("label", label), ("variable", INPUT_ENV)],
+            ))
+        })?;
+        let content = fs::read_to_string(path)?;
+        *input = Some(content.lines().map(ToString::to_string).collect());
+    }
+
+    input
+        .as_mut()
+        .and_then(VecDeque::pop_front)
+        .ok_or_else(|| input_exhausted(label))
+}
+
+fn input_exhausted(label: &str) -> Error {
+    Error::other(tr_args(
+        "shellui-input-exhausted",
+        "\"{ $label }\" is required, but { $variable } has no more input",
+        &[("label", label), ("variable", INPUT_ENV)],
+    ))
+}
+
+/// Answer of a confirmation when the input is not interactive
+fn non_interactive_confirmation(label: &str, default: bool) -> Option<Result<bool>> {
+    if is_interactive() {
+        return None;
+    }
+
+    let answer = match active_config(|config| config.non_interactive_policy) {
+        NonInteractivePolicy::Fail => Err(not_interactive(label)),
+        NonInteractivePolicy::ReadInput => read_next_input(label).and_then(|answer| {
+            parse_confirmation(&answer, default).ok_or_else(|| Error::other(invalid_value(label)))
+        }),
+    };
+    Some(answer)
+}
+
+fn parse_confirmation(answer: &str, default: bool) -> Option<bool> {
+    match answer.trim().to_lowercase().as_str() {
+        "" => Some(default),
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn map_error(error: InquireError) -> Error {
+    match error {
+        InquireError::NotTTY => Error::other("Not a TTY"),
+        InquireError::InvalidConfiguration(error) => Error::other(error),
+        InquireError::IO(error) => error,
+        InquireError::OperationCanceled | InquireError::OperationInterrupted => {
+            Error::new(ErrorKind::Interrupted, "Interrupted")
+        }
+        InquireError::Custom(error) => Error::other(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{strip_colors, OutputFormat};
+    use crate::shell::ActiveConfig;
+    use crate::testing::test_dir;
+    use crate::ShellConfig;
+
+    #[test]
+    fn test_path_requirement() {
+        let dir = test_dir("path_requirement");
+        fs::create_dir_all(dir.join("subdir")).unwrap();
+        fs::write(dir.join("file.txt"), "").unwrap();
+
+        assert!(PathRequirement::ExistingFile
+            .validate(&dir.join("file.txt"))
+            .is_ok());
+        assert!(PathRequirement::ExistingFile
+            .validate(&dir.join("subdir"))
+            .is_err());
+        assert!(PathRequirement::ExistingDir
+            .validate(&dir.join("subdir"))
+            .is_ok());
+        assert!(PathRequirement::New
+            .validate(&dir.join("file.txt"))
+            .is_err());
+        assert!(PathRequirement::New.validate(&dir.join("other")).is_ok());
+        assert!(PathRequirement::New.validate(Path::new("")).is_err());
+
+        let input = format!("{}{MAIN_SEPARATOR_STR}", dir.display());
+        assert_eq!(
+            suggest_paths(&input, PathRequirement::ExistingFile),
+            vec![
+                format!("{input}file.txt"),
+                format!("{input}subdir{MAIN_SEPARATOR_STR}")
+            ]
+        );
+        assert_eq!(
+            suggest_paths(&format!("{input}s"), PathRequirement::ExistingFile),
+            vec![format!("{input}subdir{MAIN_SEPARATOR_STR}")]
+        );
+        assert_eq!(
+            suggest_paths(&input, PathRequirement::ExistingDir),
+            vec![format!("{input}subdir{MAIN_SEPARATOR_STR}")]
+        );
+    }
+
+    fn read_input_config() -> ShellConfig {
+        ShellConfig {
+            non_interactive_policy: NonInteractivePolicy::ReadInput,
+            ..ShellConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_confirm_non_interactive() {
+        let error = with_scripted_input(&[], || confirm("Continue?", true)).unwrap_err();
+        assert!(error.to_string().contains("not interactive"));
+
+        let _config = ActiveConfig::install(read_input_config());
+        let answers = with_scripted_input(&["y", "", "No", "maybe"], || {
+            [true, true, true, true].map(|default| confirm("Continue?", default).ok())
+        });
+        assert_eq!(answers, [Some(true), Some(true), Some(false), None]);
+        assert!(with_scripted_input(&[], || confirm("Continue?", true)).is_err());
+
+        let _config = ActiveConfig::install(ShellConfig {
+            assume_yes: true,
+            ..read_input_config()
+        });
+        assert!(with_scripted_input(&["n"], || confirm("Continue?", false)).unwrap());
+    }
+
+    #[test]
+    fn test_helpers_non_interactive() {
+        let not_interactive = |result: Result<String>| {
+            let error = result.unwrap_err().to_string();
+            assert!(error.contains("the input is not interactive"), "{error}");
+        };
+        with_scripted_input(&[], || {
+            not_interactive(get_string_input_with_validator("Name", |_| Ok(())));
+            not_interactive(get_string_input_with_suggestions("Name", |_| Vec::new()));
+            not_interactive(get_multi_select_input("Colors", vec!["red"]).map(|_| String::new()));
+            not_interactive(get_editor_input("Text", "initial"));
+            not_interactive(get_secret_input("Token").map(|_| String::new()));
+            not_interactive(get_secret_input_with_confirmation("Token").map(|_| String::new()));
+            not_interactive(get_masked_input("Token", None, true).map(|_| String::new()));
+        });
+    }
+
+    #[test]
+    fn test_helpers_read_input() {
+        let _config = ActiveConfig::install(read_input_config());
+        let is_lowercase = |input: &str| match input.chars().all(char::is_lowercase) {
+            true => Ok(()),
+            false => Err("Lowercase only".to_string()),
+        };
+        with_scripted_input(&["name", "Name"], || {
+            assert_eq!(
+                get_string_input_with_validator("Name", is_lowercase).unwrap(),
+                "name"
+            );
+            let error = get_string_input_with_validator("Name", is_lowercase).unwrap_err();
+            assert_eq!(error.to_string(), "Lowercase only");
+        });
+        with_scripted_input(&["apple"], || {
+            let suggestions = |_: &str| vec!["apricot".to_string()];
+            assert_eq!(
+                get_string_input_with_suggestions("Fruit", suggestions).unwrap(),
+                "apple"
+            );
+        });
+        with_scripted_input(&["blue, red", "", "green"], || {
+            let colors = || vec!["red", "green", "blue"];
+            assert_eq!(
+                get_multi_select_input("Colors", colors()).unwrap(),
+                vec!["blue", "red"]
+            );
+            assert!(get_multi_select_input("Colors", colors())
+                .unwrap()
+                .is_empty());
+            assert!(get_multi_select_input("Colors", vec!["red"]).is_err());
+        });
+        with_scripted_input(&["text", ""], || {
+            assert_eq!(get_editor_input("Text", "initial").unwrap(), "text");
+            assert_eq!(get_editor_input("Text", "initial").unwrap(), "initial");
+        });
+        with_scripted_input(&["secret 1", "secret 2", "secret 3"], || {
+            let secret = get_secret_input("Token").unwrap();
+            assert_eq!(secret.expose_secret(), "secret 1");
+            let secret = get_secret_input_with_confirmation("Token").unwrap();
+            assert_eq!(secret.expose_secret(), "secret 2");
+            let secret = get_masked_input("Token", Some('*'), false).unwrap();
+            assert_eq!(secret.expose_secret(), "secret 3");
+        });
+    }
+
+    #[test]
+    fn test_masked_input() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let mut input = MaskedInput::default();
+        assert_eq!(input.display('*'), "");
+        for c in "abc".chars() {
+            assert_eq!(input.handle_key(key(KeyCode::Char(c))), MaskedKey::Edited);
+        }
+        assert_eq!(input.display('*'), "**c");
+        input.revealed = false;
+        assert_eq!(input.display('*'), "***");
+
+        // The remaining characters stay masked after erasing
+        input.handle_key(key(KeyCode::Char('é')));
+        assert_eq!(input.display('•'), "•••é");
+        input.handle_key(key(KeyCode::Backspace));
+        assert_eq!(input.display('•'), "•••");
+        assert_eq!(input.value, "abc");
+
+        assert_eq!(input.handle_key(key(KeyCode::Enter)), MaskedKey::Submit);
+        assert_eq!(input.handle_key(key(KeyCode::Esc)), MaskedKey::Interrupt);
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(input.handle_key(ctrl_c), MaskedKey::Interrupt);
+        assert_eq!(input.value, "abc");
+    }
+
+    #[test]
+    fn test_confirm_destructive() {
+        // No by default, including when the input is not interactive
+        assert!(!with_scripted_input(&[], || confirm_destructive("Delete?")).unwrap());
+        assert!(
+            !with_scripted_input(&[], || confirm_destructive_typed("Delete?", "prod")).unwrap()
+        );
+
+        let _config = ActiveConfig::install(read_input_config());
+        let answers = with_scripted_input(&["", "y", "yes"], || {
+            [(); 3].map(|()| confirm_destructive("Delete?").unwrap())
+        });
+        assert_eq!(answers, [false, false, true]);
+        let answers = with_scripted_input(&["yes", "prod"], || {
+            [(); 2].map(|()| confirm_destructive_typed("Delete?", "prod").unwrap())
+        });
+        assert_eq!(answers, [false, true]);
+
+        // Yes is assumed, but the word still has to be typed
+        let _config = ActiveConfig::install(ShellConfig {
+            assume_yes: true,
+            ..ShellConfig::default()
+        });
+        with_scripted_input(&[], || {
+            assert!(confirm_destructive("Delete?").unwrap());
+            assert!(!confirm_destructive_typed("Delete?", "prod").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_parse_bounded() {
+        assert_eq!(parse_bounded(" 8080 ", &(1..=65535)), Some(8080));
+        assert_eq!(parse_bounded("0", &(1..=65535)), None);
+        assert_eq!(parse_bounded("70000", &(1..=65535)), None);
+        assert_eq!(parse_bounded::<u16>("80a", &(1..=65535)), None);
+    }
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(split_path("file"), ("", "file"));
+        assert_eq!(
+            split_path(&format!("dir{MAIN_SEPARATOR_STR}fi")),
+            (format!("dir{MAIN_SEPARATOR_STR}").as_str(), "fi")
+        );
+        if cfg!(windows) {
+            assert_eq!(split_path("C:fi"), ("C:", "fi"));
+            assert_eq!(split_path("C:/dir\\fi"), ("C:/dir\\", "fi"));
+        } else {
+            assert_eq!(split_path("C:fi"), ("", "C:fi"));
+        }
+    }
+
+    struct Item(&'static str, &'static str);
+
+    impl ObjectFormatter for Item {
+        type Header = &'static str;
+        type Mode = ();
+        type Output<'a> = Message;
+
+        fn headers(_mode: Option<()>) -> Vec<&'static str> {
+            vec!["Id", "Label"]
+        }
+
+        fn format_value(&self, _mode: Option<()>, header: &&'static str) -> Message {
+            match *header {
+                "Id" => Message::new(self.0),
+                _ => Message::new(self.1),
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_row() {
+        let items = [Item("a", "first"), Item("b", "second")];
+        assert_eq!(
+            find_row(&items, None, "b").map(|item| item.1),
+            Some("second")
+        );
+        assert!(find_row(&items, None, "second").is_none());
+    }
+
+    #[test]
+    fn test_picker_rows() {
+        let _config = ActiveConfig::install(ShellConfig {
+            show_headers: false,
+            output_format: OutputFormat::Porcelain,
+            ..ShellConfig::default()
+        });
+        let items = [Item("a", "first\tline"), Item("b", "second")];
+        let (label, rows) = picker_rows("Pick", &items, None);
+        assert_eq!(strip_colors(&label), "Pick\n  Id   Label     ");
+        let rows = rows
+            .iter()
+            .map(|row| (row.index, strip_colors(&row.line).into_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                (0, "a    first\tline".to_string()),
+                (1, "b    second    ".to_string())
+            ]
+        );
     }
 }
@@ -0,0 +1,176 @@
+use super::{confirm, get_select_input, get_string_input};
+use crate::errors::ShellUiResult;
+use std::fmt::Display;
+use std::io::Result;
+
+type FormStep<T> = Box<dyn FnOnce(&mut T) -> Result<()>>;
+
+/// Multi-step input
+///
+/// Chains several prompts, each of them filling a part of `T`.
+/// Steps can be made conditional on the previous answers using
+/// [`Form::when`].
+pub struct Form<T> {
+    steps: Vec<FormStep<T>>,
+}
+
+impl<T> Default for Form<T> {
+    fn default() -> Self {
+        Form { steps: Vec::new() }
+    }
+}
+
+impl<T> Form<T>
+where
+    T: 'static,
+{
+    pub fn new() -> Self {
+        Form::default()
+    }
+
+    /// Add a custom step
+    pub fn step<F>(mut self, step: F) -> Self
+    where
+        F: 'static + FnOnce(&mut T) -> Result<()>,
+    {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    /// Ask for a string
+    pub fn text<F>(self, label: &str, set: F) -> Self
+    where
+        F: 'static + FnOnce(&mut T, String),
+    {
+        let label = label.to_string();
+        self.step(move |value| {
+            set(value, get_string_input(&label)?);
+            Ok(())
+        })
+    }
+
+    /// Ask to select one of the options
+    pub fn select<O, F>(self, label: &str, options: Vec<O>, set: F) -> Self
+    where
+        O: 'static + Display,
+        F: 'static + FnOnce(&mut T, O),
+    {
+        let label = label.to_string();
+        self.step(move |value| {
+            set(value, get_select_input(&label, options)?);
+            Ok(())
+        })
+    }
+
+    /// Ask for a confirmation
+    pub fn confirm<F>(self, label: &str, default: bool, set: F) -> Self
+    where
+        F: 'static + FnOnce(&mut T, bool),
+    {
+        let label = label.to_string();
+        self.step(move |value| {
+            set(value, confirm(&label, default)?);
+            Ok(())
+        })
+    }
+
+    /// Add steps that are only run if `condition` is met
+    ///
+    /// The condition is evaluated against the answers of the previous steps.
+    pub fn when<C, F>(self, condition: C, build: F) -> Self
+    where
+        C: 'static + FnOnce(&T) -> bool,
+        F: FnOnce(Form<T>) -> Form<T>,
+    {
+        let form = build(Form::new());
+        self.step(move |value| {
+            if condition(value) {
+                form.run_steps(value)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Run all the steps
+    ///
+    /// Interrupting a prompt returns [`crate::errors::ShellUiError::Interrupt`].
+    pub fn run(self) -> ShellUiResult<T>
+    where
+        T: Default,
+    {
+        let mut value = T::default();
+        self.run_steps(&mut value)?;
+        Ok(value)
+    }
+
+    fn run_steps(self, value: &mut T) -> Result<()> {
+        for step in self.steps {
+            step(value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ShellUiError;
+    use std::io::{Error, ErrorKind};
+
+    #[derive(Default)]
+    struct Answers {
+        name: String,
+        advanced: bool,
+        replicas: u32,
+    }
+
+    #[test]
+    fn test_form_conditional_steps() {
+        let answers = Form::<Answers>::new()
+            .step(|answers| {
+                answers.name = "name".to_string();
+                Ok(())
+            })
+            .when(
+                |answers| answers.advanced,
+                |form| {
+                    form.step(|answers| {
+                        answers.replicas = 3;
+                        Ok(())
+                    })
+                },
+            )
+            .run()
+            .unwrap();
+        assert_eq!(answers.name, "name");
+        assert_eq!(answers.replicas, 0);
+
+        let answers = Form::<Answers>::new()
+            .step(|answers| {
+                answers.advanced = true;
+                Ok(())
+            })
+            .when(
+                |answers| answers.advanced,
+                |form| {
+                    form.step(|answers| {
+                        answers.replicas = 3;
+                        Ok(())
+                    })
+                },
+            )
+            .run()
+            .unwrap();
+        assert_eq!(answers.replicas, 3);
+    }
+
+    #[test]
+    fn test_form_interrupt() {
+        let result = Form::<Answers>::new()
+            .step(|_| Err(Error::new(ErrorKind::Interrupted, "Interrupted")))
+            .step(|_| panic!("Should not be called"))
+            .run();
+        assert!(matches!(result, Err(ShellUiError::Interrupt)));
+    }
+}
@@ -0,0 +1,116 @@
+//! Input helpers returning [`ShellUiResult`]
+//!
+//! These helpers are the same as the ones in [`crate::input`], but can be
+//! used directly in [`crate::ShellParser::run_command`]. Interrupting a
+//! prompt returns [`crate::errors::ShellUiError::Interrupt`].
+
+use super::{PathRequirement, PromptOptions, SecretString};
+use crate::errors::ShellUiResult;
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub fn get_string_input(label: &str) -> ShellUiResult<String> {
+    Ok(super::get_string_input(label)?)
+}
+
+pub fn get_string_input_with_options(
+    label: &str,
+    options: &PromptOptions,
+) -> ShellUiResult<String> {
+    Ok(super::get_string_input_with_options(label, options)?)
+}
+
+pub fn get_optional_string_input(label: &str) -> ShellUiResult<Option<String>> {
+    Ok(super::get_optional_string_input(label)?)
+}
+
+pub fn get_parsed_input<T>(label: &str) -> ShellUiResult<T>
+where
+    T: FromStr,
+{
+    Ok(super::get_parsed_input(label)?)
+}
+
+pub fn get_parsed_input_with_options<T>(label: &str, options: &PromptOptions) -> ShellUiResult<T>
+where
+    T: FromStr,
+{
+    Ok(super::get_parsed_input_with_options(label, options)?)
+}
+
+pub fn get_string_input_with_default(label: &str, default: &str) -> ShellUiResult<String> {
+    Ok(super::get_string_input_with_default(label, default)?)
+}
+
+pub fn get_string_input_with_validator<F>(label: &str, validator: F) -> ShellUiResult<String>
+where
+    F: 'static + Clone + Fn(&str) -> Result<(), String>,
+{
+    Ok(super::get_string_input_with_validator(label, validator)?)
+}
+
+pub fn get_string_input_with_suggestions<F>(label: &str, suggester: F) -> ShellUiResult<String>
+where
+    F: 'static + Clone + Fn(&str) -> Vec<String>,
+{
+    Ok(super::get_string_input_with_suggestions(label, suggester)?)
+}
+
+pub fn get_select_input<T>(label: &str, options: Vec<T>) -> ShellUiResult<T>
+where
+    T: Display,
+{
+    Ok(super::get_select_input(label, options)?)
+}
+
+pub fn get_multi_select_input<T>(label: &str, options: Vec<T>) -> ShellUiResult<Vec<T>>
+where
+    T: Display,
+{
+    Ok(super::get_multi_select_input(label, options)?)
+}
+
+pub fn get_number_input<T>(label: &str, range: RangeInclusive<T>) -> ShellUiResult<T>
+where
+    T: 'static + Clone + Display + FromStr + PartialOrd,
+{
+    Ok(super::get_number_input(label, range)?)
+}
+
+pub fn get_path_input(label: &str, requirement: PathRequirement) -> ShellUiResult<PathBuf> {
+    Ok(super::get_path_input(label, requirement)?)
+}
+
+pub fn get_editor_input(label: &str, initial: &str) -> ShellUiResult<String> {
+    Ok(super::get_editor_input(label, initial)?)
+}
+
+pub fn get_secret_input(label: &str) -> ShellUiResult<SecretString> {
+    Ok(super::get_secret_input(label)?)
+}
+
+pub fn get_masked_input(
+    label: &str,
+    mask: Option<char>,
+    reveal_last: bool,
+) -> ShellUiResult<SecretString> {
+    Ok(super::get_masked_input(label, mask, reveal_last)?)
+}
+
+pub fn get_secret_input_with_confirmation(label: &str) -> ShellUiResult<SecretString> {
+    Ok(super::get_secret_input_with_confirmation(label)?)
+}
+
+pub fn confirm(label: &str, default: bool) -> ShellUiResult<bool> {
+    Ok(super::confirm(label, default)?)
+}
+
+pub fn confirm_destructive(label: &str) -> ShellUiResult<bool> {
+    Ok(super::confirm_destructive(label)?)
+}
+
+pub fn confirm_destructive_typed(label: &str, expected: &str) -> ShellUiResult<bool> {
+    Ok(super::confirm_destructive_typed(label, expected)?)
+}
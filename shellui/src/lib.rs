@@ -1,19 +1,148 @@
+pub mod completions;
+pub mod config;
+#[cfg(feature = "docs")]
+pub mod docs;
 pub mod errors;
 pub mod format;
+pub mod i18n;
 pub mod input;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod options;
+pub mod paths;
+pub mod registry;
+mod server;
 mod shell;
+mod spinner;
+pub mod telemetry;
+pub mod testing;
+mod update;
 
-use crate::errors::{ShellUiError, ShellUiResult};
-use crate::format::AsFormatted;
-use clap::{Parser, Subcommand};
-use std::io::Result;
+use crate::config::Config;
+use crate::errors::{RetryPolicy, ShellUiError, ShellUiResult};
+use crate::format::{
+    init_colors, output_format, print_stderr, AsFormatted, Message, OutputFormat, Stream,
+};
+use crate::i18n::{tr_args, Translator};
+use crate::options::GlobalOptions;
+use crate::registry::{is_registered, run_registered, with_registered, CommandRegistry};
+pub use crate::server::{launch_server, launch_server_with, Listener};
+use crate::shell::ActiveConfig;
+pub use crate::shell::{CompletionStyle, KeyAction, KeyBinding, ShellConfig};
+use crate::telemetry::{command_name, TelemetrySink};
+use clap::{ArgMatches, Parser, Subcommand};
+pub use shellui_derive::ShellParser;
+use std::env;
+use std::ffi::OsString;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io::{Error, Result};
 use std::path::PathBuf;
-use std::process::exit;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 /// Shell context
 pub trait Context: Sized {
     fn new() -> Result<Self>;
     fn history_path(&self) -> Option<PathBuf>;
+    /// Path of the session state
+    ///
+    /// Next to the history file by default, like `.mycli_history.state`.
+    fn state_path(&self) -> Option<PathBuf> {
+        let mut path = self.history_path()?.into_os_string();
+        path.push(".state");
+        Some(PathBuf::from(path))
+    }
+    /// State to persist when the shell exits
+    ///
+    /// Use `serde_json::to_value` to save a serializable state.
+    fn save_state(&self) -> Option<serde_json::Value> {
+        None
+    }
+    /// Restore the state saved by a previous session, when the shell starts
+    ///
+    /// Use `serde_json::from_value` to load a serializable state.
+    fn load_state(&mut self, _state: serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+    /// Shell configuration
+    fn shell_config(&self) -> ShellConfig {
+        ShellConfig::default()
+    }
+    /// Check if the command set changed
+    ///
+    /// Called by the shell after each command. Returning `true`
+    /// rebuilds completion and hints.
+    fn commands_changed(&mut self) -> bool {
+        false
+    }
+    /// Commands registered at runtime
+    ///
+    /// Registering or unregistering commands also rebuilds completion and hints.
+    fn registry(&self) -> Option<&CommandRegistry<Self>> {
+        None
+    }
+    /// Category of a command, listed under its own heading by `help`
+    ///
+    /// Registered commands can also be given a category with
+    /// `CommandRegistry::register_in`.
+    fn command_category(&self, _command: &str) -> Option<String> {
+        None
+    }
+    /// Configuration, edited with the `config` builtins
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+    /// Mutable configuration, edited with the `config set` builtin
+    fn config_mut(&mut self) -> Option<&mut Config> {
+        None
+    }
+    /// Called when a configuration value is set by the `config set` builtin
+    fn config_changed(&mut self, _key: &str) {}
+    /// Translator for messages
+    ///
+    /// Installed when launching. See `i18n::env_language` to select
+    /// the language from the environment.
+    fn translator(&self) -> Option<Box<dyn Translator>> {
+        None
+    }
+    /// Receiver of usage metrics
+    ///
+    /// Metrics are only delivered if a sink is provided.
+    fn telemetry(&self) -> Option<&dyn TelemetrySink> {
+        None
+    }
+    /// Check for a newer release of the application
+    ///
+    /// The returned function runs in a background thread when the shell
+    /// starts, at most once per day, and returns the newer version if any.
+    /// An upgrade hint is then printed before the next prompt.
+    fn update_check(&self) -> Option<Box<dyn FnOnce() -> Option<String> + Send>> {
+        None
+    }
+    /// Banner printed when the shell starts, and after `clear --all`
+    fn banner(&self) -> Option<String> {
+        None
+    }
+    /// Called when the shell starts
+    fn on_start(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Called when a command fails, before the error is printed
+    ///
+    /// Not called for interrupted commands.
+    fn on_command_error(&mut self, _error: &ShellUiError) {}
+    /// Number of background jobs still running
+    ///
+    /// The shell asks for a confirmation before exiting while jobs are
+    /// running, unless `exit --force` is used.
+    fn running_jobs(&self) -> usize {
+        0
+    }
+    /// Called when the shell exits
+    fn on_exit(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// Clap extension to enable shell
@@ -35,37 +164,526 @@ pub trait ShellParser: Parser {
     /// The clap main entrypoint should contain an optional subcommand,
     /// so that it can go into shell mode if the subcommand is not passed.
     fn try_get_command(self) -> Option<Self::Commands>;
+    /// Global options
+    ///
+    /// Return the flattened `GlobalOptions`, if any, to apply them.
+    fn global_options(&self) -> Option<&GlobalOptions> {
+        None
+    }
     /// Run a command
     fn run_command(context: &mut Self::Context, command: &Self::Commands) -> ShellUiResult<()>;
 }
 
+/// Async clap extension to enable shell
+///
+/// Async twin of `ShellParser`, to be launched with `launch_async`.
+#[cfg(feature = "async")]
+pub trait AsyncShellParser: Parser {
+    /// Context
+    type Context: Context;
+    /// Subcommands
+    type Commands: Subcommand;
+    /// Try get command
+    ///
+    /// See `ShellParser::try_get_command`.
+    fn try_get_command(self) -> Option<Self::Commands>;
+    /// Global options
+    ///
+    /// See `ShellParser::global_options`.
+    fn global_options(&self) -> Option<&GlobalOptions> {
+        None
+    }
+    /// Run a command
+    ///
+    /// Can be implemented as an `async fn`. In shell mode, the command
+    /// is cancelled with Ctrl+C.
+    fn run_command(
+        context: &mut Self::Context,
+        command: &Self::Commands,
+    ) -> impl Future<Output = ShellUiResult<()>>;
+}
+
 /// Launch a command
 ///
 /// Will launch the entrypoint being passed, either running as a CLI
-/// or spawning a shell.
-pub fn launch<T>()
+/// or spawning a shell. Errors are printed, and converted to the exit
+/// code to return from `main`. In shell mode, the exit code is the one
+/// passed to the `exit` builtin, like `exit 3`.
+pub fn launch<T>() -> ExitCode
+where
+    T: ShellParser,
+{
+    match T::Context::new() {
+        Ok(context) => launch_with::<T>(context),
+        Err(error) => into_exit_code(Err(error.into())),
+    }
+}
+
+/// Launch a command with a context
+///
+/// Like `launch`, but using an already created context instead
+/// of `Context::new`.
+pub fn launch_with<T>(context: T::Context) -> ExitCode
+where
+    T: ShellParser,
+{
+    // Errors are printed with the configuration of the launch
+    match prepare_launch::<T, _, _>(&context, env::args_os(), T::global_options) {
+        Ok((launch, _config)) => into_exit_code(run_launch::<T>(context, launch)),
+        Err(error) => print_args_error(&error),
+    }
+}
+
+/// Launch a command, returning the error
+///
+/// Like `launch`, but errors are neither printed nor converted to an exit
+/// code. On success, returns the exit code passed to the `exit` builtin in
+/// shell mode, and 0 otherwise.
+///
+/// Invalid arguments, as well as `--help` and `--version`, are returned as
+/// [`ShellUiError::WithCode`], with the exit code of clap: 2 for usage errors,
+/// and 0 for the help and the version.
+pub fn try_launch<T>() -> ShellUiResult<u8>
+where
+    T: ShellParser,
+{
+    try_launch_with::<T>(T::Context::new()?)
+}
+
+/// Launch a command with a context, returning the error
+pub fn try_launch_with<T>(context: T::Context) -> ShellUiResult<u8>
+where
+    T: ShellParser,
+{
+    try_launch_from::<T, _>(context, env::args_os())
+}
+
+/// Launch a command with a context and arguments, returning the error
+///
+/// Like `try_launch_with`, but the arguments, starting with the binary
+/// name, are passed instead of read from the command line.
+pub fn try_launch_from<T, I>(context: T::Context, args: I) -> ShellUiResult<u8>
 where
     T: ShellParser,
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
 {
-    if let Err(error) = handle_launch::<T>() {
-        match error {
-            ShellUiError::Error(_) | ShellUiError::Warning(_) => error.print_formatted(),
-            ShellUiError::Interrupt => {}
+    let (launch, _config) =
+        prepare_launch::<T, _, _>(&context, args, T::global_options).map_err(args_error)?;
+    run_launch::<T>(context, launch)
+}
+
+fn run_launch<T>(mut context: T::Context, launch: Launch<T>) -> ShellUiResult<u8>
+where
+    T: ShellParser,
+{
+    let Launch {
+        args,
+        command,
+        registered,
+    } = launch;
+    let start = Instant::now();
+    let result = match (registered, args.try_get_command()) {
+        (Some((name, matches)), _) => run_registered(&mut context, &name, &matches),
+        (None, Some(commands)) => run_command::<T>(&mut context, &commands),
+        (None, None) => return Ok(shell::launch_shell::<T>(&mut context)?),
+    };
+    finish_launch(&mut context, command, start, result)
+}
+
+/// Launch an async command
+///
+/// Async twin of `launch`, to be called from a tokio runtime.
+#[cfg(feature = "async")]
+pub async fn launch_async<T>() -> ExitCode
+where
+    T: AsyncShellParser,
+{
+    match T::Context::new() {
+        Ok(context) => launch_async_with::<T>(context).await,
+        Err(error) => into_exit_code(Err(error.into())),
+    }
+}
+
+/// Launch an async command with a context
+///
+/// Async twin of `launch_with`.
+#[cfg(feature = "async")]
+pub async fn launch_async_with<T>(context: T::Context) -> ExitCode
+where
+    T: AsyncShellParser,
+{
+    match prepare_launch::<T, _, _>(&context, env::args_os(), T::global_options) {
+        Ok((launch, _config)) => into_exit_code(run_launch_async::<T>(context, launch).await),
+        Err(error) => print_args_error(&error),
+    }
+}
+
+/// Launch an async command, returning the error
+///
+/// Async twin of `try_launch`.
+#[cfg(feature = "async")]
+pub async fn try_launch_async<T>() -> ShellUiResult<u8>
+where
+    T: AsyncShellParser,
+{
+    try_launch_async_with::<T>(T::Context::new()?).await
+}
+
+/// Launch an async command with a context, returning the error
+#[cfg(feature = "async")]
+pub async fn try_launch_async_with<T>(context: T::Context) -> ShellUiResult<u8>
+where
+    T: AsyncShellParser,
+{
+    try_launch_async_from::<T, _>(context, env::args_os()).await
+}
+
+/// Launch an async command with a context and arguments, returning the error
+///
+/// Async twin of `try_launch_from`.
+#[cfg(feature = "async")]
+pub async fn try_launch_async_from<T, I>(context: T::Context, args: I) -> ShellUiResult<u8>
+where
+    T: AsyncShellParser,
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    let (launch, _config) =
+        prepare_launch::<T, _, _>(&context, args, T::global_options).map_err(args_error)?;
+    run_launch_async::<T>(context, launch).await
+}
+
+#[cfg(feature = "async")]
+async fn run_launch_async<T>(mut context: T::Context, launch: Launch<T>) -> ShellUiResult<u8>
+where
+    T: AsyncShellParser,
+{
+    let Launch {
+        args,
+        command,
+        registered,
+    } = launch;
+    let start = Instant::now();
+    let result = match (registered, args.try_get_command()) {
+        (Some((name, matches)), _) => run_registered(&mut context, &name, &matches),
+        (None, Some(commands)) => run_command_async::<T>(&mut context, &commands).await,
+        (None, None) => return Ok(shell::launch_shell_async::<T>(&mut context).await?),
+    };
+    finish_launch(&mut context, command, start, result)
+}
+
+/// Arguments parsed when launching
+struct Launch<T> {
+    args: T,
+    /// Name of the command, for telemetry
+    command: String,
+    /// Registered command to run
+    registered: Option<(String, ArgMatches)>,
+}
+
+/// Record and notify the result of a command run from the command line
+fn finish_launch<C>(
+    context: &mut C,
+    command: String,
+    start: Instant,
+    result: ShellUiResult<()>,
+) -> ShellUiResult<u8>
+where
+    C: Context,
+{
+    telemetry::record(context, command, start, &result);
+    result
+        .map(|()| 0)
+        .inspect_err(|error| notify_error(context, error))
+}
+
+/// Parse the arguments, and install the configuration of the launch
+///
+/// The configuration of the context is updated with the global options,
+/// and is active until the returned guard is dropped.
+fn prepare_launch<T, C, I>(
+    context: &C,
+    args: I,
+    global_options: fn(&T) -> Option<&GlobalOptions>,
+) -> clap::error::Result<(Launch<T>, ActiveConfig)>
+where
+    T: Parser,
+    C: Context,
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    install_translator(context);
+    init_colors();
+    let launch = parse_args::<T, _, _>(context, args)?;
+    let mut config = context.shell_config();
+    if let Some(options) = global_options(&launch.args) {
+        options.apply(&mut config);
+    }
+    Ok((launch, ActiveConfig::install(config)))
+}
+
+/// Print an error of the arguments, or the help, and return the exit code of clap
+fn print_args_error(error: &clap::Error) -> ExitCode {
+    // Like clap's `Error::exit`, failing to print is ignored
+    let _ = error.print();
+    ExitCode::from(args_exit_code(error))
+}
+
+fn args_error(error: clap::Error) -> ShellUiError {
+    ShellUiError::with_code(args_exit_code(&error), Error::other(error))
+}
+
+fn args_exit_code(error: &clap::Error) -> u8 {
+    u8::try_from(error.exit_code()).unwrap_or(2)
+}
+
+fn into_exit_code(result: ShellUiResult<u8>) -> ExitCode {
+    match result {
+        Ok(code) => ExitCode::from(code),
+        Err(error) => {
+            match error {
+                ShellUiError::Error(_)
+                | ShellUiError::Warning(_)
+                | ShellUiError::WithCode(_, _)
+                | ShellUiError::Multiple(_)
+                | ShellUiError::Retryable(_) => print_error(&error),
+                ShellUiError::Interrupt => {}
+            }
+            ExitCode::from(error.exit_code())
         }
-        exit(1);
     }
 }
 
-fn handle_launch<T>() -> ShellUiResult<()>
+pub(crate) fn print_error(error: &ShellUiError) {
+    match output_format() {
+        OutputFormat::Human => error.print_to(Stream::Stderr),
+        OutputFormat::Json => print_stderr(format!("{:#}", error.as_json())),
+        OutputFormat::Ndjson => print_stderr(error.as_json()),
+        OutputFormat::Porcelain => print_stderr(error.as_unformatted()),
+    }
+}
+
+fn print_retry(error: &ShellUiError, attempt: usize, policy: &RetryPolicy, delay: Duration) {
+    Message::warning(tr_args(
+        "shellui-retrying",
+        "{ $error }, retrying in { $delay } (attempt { $attempt }/{ $max })",
+        &[
+            ("error", &error.to_string()),
+            ("delay", &format!("{delay:?}")),
+            ("attempt", &(attempt + 1).to_string()),
+            ("max", &policy.max_attempts.to_string()),
+        ],
+    ))
+    .print_formatted()
+}
+
+/// Run a command, applying the retry policy of the shell configuration
+pub(crate) fn run_command<T>(context: &mut T::Context, command: &T::Commands) -> ShellUiResult<()>
 where
     T: ShellParser,
 {
-    let mut context = T::Context::new()?;
-    let args = T::parse();
-    if let Some(commands) = args.try_get_command() {
-        T::run_command(&mut context, &commands)
-    } else {
-        shell::launch_shell::<T>(&mut context)?;
-        Ok(())
+    match context.shell_config().retry_policy {
+        Some(policy) => policy.run(
+            || T::run_command(context, command),
+            |error, attempt, delay| print_retry(error, attempt, &policy, delay),
+        ),
+        None => T::run_command(context, command),
+    }
+}
+
+/// Run an async command, applying the retry policy of the shell configuration
+#[cfg(feature = "async")]
+pub(crate) async fn run_command_async<T>(
+    context: &mut T::Context,
+    command: &T::Commands,
+) -> ShellUiResult<()>
+where
+    T: AsyncShellParser,
+{
+    let Some(policy) = context.shell_config().retry_policy else {
+        return T::run_command(context, command).await;
+    };
+
+    let mut attempts = policy.attempts();
+    loop {
+        let result = T::run_command(context, command).await;
+        let on_retry = |error: &_, attempt, delay| print_retry(error, attempt, &policy, delay);
+        match attempts.retry_after(&result, on_retry) {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => return result,
+        }
+    }
+}
+
+pub(crate) fn notify_error<C>(context: &mut C, error: &ShellUiError)
+where
+    C: Context,
+{
+    if !matches!(error, ShellUiError::Interrupt) {
+        context.on_command_error(error);
+    }
+}
+
+/// Parse the CLI arguments, including the registered commands
+fn parse_args<T, C, I>(context: &C, args: I) -> clap::error::Result<Launch<T>>
+where
+    T: Parser,
+    C: Context,
+    I: IntoIterator,
+    I::Item: Into<OsString> + Clone,
+{
+    let mut matches = with_registered(context, T::command()).try_get_matches_from(args)?;
+    let command = command_name(&matches);
+    let registered = match matches.subcommand_name() {
+        Some(name) if is_registered(context, name) => matches.remove_subcommand(),
+        _ => None,
+    };
+    let args = T::from_arg_matches(&matches)?;
+    Ok(Launch {
+        args,
+        command,
+        registered,
+    })
+}
+
+pub(crate) fn install_translator<C>(context: &C)
+where
+    C: Context,
+{
+    if let Some(translator) = context.translator() {
+        i18n::set_translator(translator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, Command};
+    use std::cell::RefCell;
+    use std::io::Error;
+    use std::iter;
+    use std::rc::Rc;
+
+    /// Calls of the hooks, shared with the tests as the context is consumed
+    type Events = Rc<RefCell<Vec<String>>>;
+
+    #[derive(Default)]
+    struct TestContext {
+        events: Events,
+        registry: CommandRegistry<TestContext>,
+    }
+
+    impl Context for TestContext {
+        fn new() -> Result<Self> {
+            Ok(TestContext::default())
+        }
+
+        fn history_path(&self) -> Option<PathBuf> {
+            None
+        }
+
+        fn registry(&self) -> Option<&CommandRegistry<Self>> {
+            Some(&self.registry)
+        }
+
+        fn on_command_error(&mut self, error: &ShellUiError) {
+            self.events.borrow_mut().push(format!("error: {error}"));
+        }
+    }
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(subcommand)]
+        command: Option<TestCommand>,
+    }
+
+    #[derive(Subcommand)]
+    enum TestCommand {
+        Run,
+        Fail,
+        Interrupt,
+    }
+
+    impl ShellParser for TestCli {
+        type Context = TestContext;
+        type Commands = TestCommand;
+
+        fn try_get_command(self) -> Option<Self::Commands> {
+            self.command
+        }
+
+        fn run_command(context: &mut Self::Context, command: &Self::Commands) -> ShellUiResult<()> {
+            match command {
+                TestCommand::Run => {
+                    context.events.borrow_mut().push("run".to_string());
+                    Ok(())
+                }
+                TestCommand::Fail => Err(ShellUiError::with_code(3, Error::other("Failed"))),
+                TestCommand::Interrupt => Err(ShellUiError::interrupt()),
+            }
+        }
+    }
+
+    fn launch(context: TestContext, args: &[&str]) -> ShellUiResult<u8> {
+        let args = iter::once("test").chain(args.iter().copied());
+        try_launch_from::<TestCli, _>(context, args)
+    }
+
+    #[test]
+    fn test_try_launch() {
+        let context = TestContext::default();
+        let events = context.events.clone();
+        assert_eq!(launch(context, &["run"]).unwrap(), 0);
+        assert_eq!(*events.borrow(), ["run"]);
+
+        let error = launch(TestContext::default(), &["fail"]).unwrap_err();
+        assert_eq!(error.to_string(), "Failed");
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_try_launch_invalid_args() {
+        let context = TestContext::default();
+        let events = context.events.clone();
+        let error = launch(context, &["unknown"]).unwrap_err();
+        assert!(matches!(error, ShellUiError::WithCode(2, _)));
+        assert!(error.to_string().contains("unknown"));
+        assert!(events.borrow().is_empty());
+
+        // The help is returned instead of printed
+        let error = launch(TestContext::default(), &["--help"]).unwrap_err();
+        assert!(matches!(error, ShellUiError::WithCode(0, _)));
+        assert!(error.to_string().contains("Usage"));
+    }
+
+    #[test]
+    fn test_try_launch_registered() {
+        let mut context = TestContext::default();
+        context.registry.register(
+            Command::new("add").arg(Arg::new("value").required(true)),
+            |context, matches| {
+                let value = matches.get_one::<String>("value").unwrap();
+                context.events.borrow_mut().push(format!("add {value}"));
+                Ok(())
+            },
+        );
+        let events = context.events.clone();
+        assert_eq!(launch(context, &["add", "2"]).unwrap(), 0);
+        assert_eq!(*events.borrow(), ["add 2"]);
+    }
+
+    #[test]
+    fn test_on_command_error() {
+        let context = TestContext::default();
+        let events = context.events.clone();
+        assert!(launch(context, &["fail"]).is_err());
+        assert_eq!(*events.borrow(), ["error: Failed"]);
+
+        // Interrupts are not failures
+        let context = TestContext::default();
+        let events = context.events.clone();
+        assert!(launch(context, &["interrupt"]).is_err());
+        assert!(events.borrow().is_empty());
     }
 }
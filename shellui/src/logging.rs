@@ -0,0 +1,130 @@
+//! Logging
+//!
+//! Route `tracing` records to stderr, styled as messages. The level is
+//! driven by the verbosity set with the `-v` global flags: warnings by
+//! default, then info, debug and trace. Records emitted while the shell
+//! prompt is displayed are printed once the line is read, so that they
+//! are not interleaved with the prompt.
+use crate::format::{print_stderr, AsFormatted, Message};
+use crate::options::verbosity;
+use std::fmt::{Debug, Write};
+use std::io::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+static PROMPT_ACTIVE: AtomicBool = AtomicBool::new(false);
+static PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Install the global subscriber
+///
+/// Can be called before the global options are applied, as the
+/// verbosity is read for each record.
+pub fn init() -> Result<()> {
+    tracing_subscriber::registry()
+        .with(MessageLayer.with_filter(filter_fn(|metadata| {
+            *metadata.level() <= max_level(verbosity())
+        })))
+        .try_init()
+        .map_err(Error::other)
+}
+
+fn max_level(verbosity: u8) -> Level {
+    match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Hold records while the prompt is displayed, and print them after
+pub(crate) fn set_prompt_active(active: bool) {
+    PROMPT_ACTIVE.store(active, Ordering::Relaxed);
+    if !active {
+        for line in PENDING.lock().unwrap().drain(..) {
+            print_stderr(line);
+        }
+    }
+}
+
+struct MessageLayer;
+
+impl<S> Layer<S> for MessageLayer
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let message = match *event.metadata().level() {
+            Level::ERROR => Message::error(visitor.0),
+            Level::WARN => Message::warning(visitor.0),
+            Level::INFO => Message::info(visitor.0),
+            _ => Message::hint(visitor.0),
+        };
+
+        let line = message.as_formatted();
+        if PROMPT_ACTIVE.load(Ordering::Relaxed) {
+            PENDING.lock().unwrap().push(line.into_owned());
+        } else {
+            print_stderr(line);
+        }
+    }
+}
+
+/// Format the message of a record, followed by its other fields
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{value:?}");
+        } else {
+            let _ = write!(self.0, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tracing::info;
+    use tracing_subscriber::registry;
+
+    #[test]
+    fn test_max_level() {
+        assert_eq!(max_level(0), Level::WARN);
+        assert_eq!(max_level(2), Level::DEBUG);
+        assert_eq!(max_level(5), Level::TRACE);
+    }
+
+    #[test]
+    fn test_message_visitor() {
+        // Asserting in on_event would panic inside the subscriber, so
+        // messages are recorded and checked afterwards
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+        impl<S> Layer<S> for Recorder
+        where
+            S: Subscriber,
+        {
+            fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+                let mut visitor = MessageVisitor::default();
+                event.record(&mut visitor);
+                self.0.lock().unwrap().push(visitor.0);
+            }
+        }
+
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = registry().with(Recorder(messages.clone()));
+        tracing::subscriber::with_default(subscriber, || info!(host = "localhost", "Connected"));
+        assert_eq!(*messages.lock().unwrap(), ["Connected host=\"localhost\""]);
+    }
+}
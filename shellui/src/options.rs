@@ -0,0 +1,114 @@
+//! Global options
+//!
+//! Options that most applications provide, to be flattened into the
+//! application parser and handed back to shellui with
+//! `ShellParser::global_options`.
+use crate::format::OutputFormat;
+use crate::ShellConfig;
+use clap::{ArgAction, Args};
+use colored::control::set_override;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(0);
+
+/// Global options
+///
+/// Flatten them into the application parser with `#[command(flatten)]`.
+/// They are applied when launching, and are kept in shell mode.
+#[derive(Args, Clone, Debug, Default)]
+pub struct GlobalOptions {
+    /// Increase verbosity, can be repeated
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Disable colors
+    #[arg(long, global = true)]
+    pub no_color: bool,
+    /// Output format [default: human]
+    ///
+    /// Overrides the output format of the shell configuration.
+    #[arg(long, value_enum, global = true)]
+    pub output: Option<OutputFormat>,
+    /// Stable output for scripts, same as `--output porcelain`
+    #[arg(long, global = true)]
+    pub porcelain: bool,
+    /// Do not print the headers of tables
+    #[arg(long, global = true)]
+    pub no_headers: bool,
+    /// Answer yes to all confirmations
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+}
+
+impl GlobalOptions {
+    /// Apply the options to the configuration of the launch
+    ///
+    /// Verbosity and colors are applied to the whole process.
+    pub fn apply(&self, config: &mut ShellConfig) {
+        set_verbosity(self.verbose);
+        if self.porcelain {
+            config.output_format = OutputFormat::Porcelain;
+        } else if let Some(output) = self.output {
+            config.output_format = output;
+        }
+        if self.no_color || config.output_format == OutputFormat::Porcelain {
+            set_override(false);
+        }
+        config.show_headers &= !self.no_headers;
+        config.assume_yes |= self.yes;
+        #[cfg(feature = "backtrace")]
+        if self.verbose >= 2 {
+            crate::errors::set_show_backtrace(true);
+        }
+    }
+}
+
+pub fn set_verbosity(verbosity: u8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+/// Verbosity, as the number of `-v` flags
+pub fn verbosity() -> u8 {
+    VERBOSITY.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct TestArgs {
+        #[command(flatten)]
+        options: GlobalOptions,
+    }
+
+    #[test]
+    fn test_parse_global_options() {
+        let args = TestArgs::try_parse_from(["test", "-vv", "--output", "ndjson", "-y"]).unwrap();
+        assert_eq!(args.options.verbose, 2);
+        assert!(!args.options.no_color);
+        assert_eq!(args.options.output, Some(OutputFormat::Ndjson));
+        assert!(args.options.yes);
+        assert!(!args.options.porcelain);
+
+        let args = TestArgs::try_parse_from(["test", "--porcelain"]).unwrap();
+        assert!(args.options.porcelain);
+    }
+
+    #[test]
+    fn test_apply_global_options() {
+        let mut config = ShellConfig {
+            output_format: OutputFormat::Json,
+            ..ShellConfig::default()
+        };
+        let args = TestArgs::try_parse_from(["test", "--no-headers", "-y"]).unwrap();
+        args.options.apply(&mut config);
+        assert_eq!(config.output_format, OutputFormat::Json);
+        assert!(!config.show_headers);
+        assert!(config.assume_yes);
+
+        let args = TestArgs::try_parse_from(["test", "--output", "ndjson"]).unwrap();
+        args.options.apply(&mut config);
+        assert_eq!(config.output_format, OutputFormat::Ndjson);
+    }
+}
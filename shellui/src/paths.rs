@@ -0,0 +1,80 @@
+//! Application paths
+//!
+//! Platform-specific locations for the files of an application: XDG
+//! directories on Linux, `Library` on macOS and Known Folders on Windows.
+use crate::errors::WithContext;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+
+/// Directories of an application
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AppPaths {
+    config_dir: PathBuf,
+    cache_dir: PathBuf,
+    state_dir: PathBuf,
+}
+
+impl AppPaths {
+    /// Configuration directory, like `~/.config/<app>`
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// Cache directory, like `~/.cache/<app>`
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// State directory, like `~/.local/state/<app>`
+    ///
+    /// Uses the local data directory on platforms without a state directory.
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    /// Configuration file, used by `config::Config::load`
+    pub fn config_path(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    /// History file, to be returned by `Context::history_path`
+    pub fn history_path(&self) -> PathBuf {
+        self.state_dir.join("history")
+    }
+
+    /// Session state file, to be returned by `Context::state_path`
+    pub fn state_path(&self) -> PathBuf {
+        self.state_dir.join("state.json")
+    }
+}
+
+/// Directories of an application, from its name
+pub fn for_app(app_name: &str) -> Result<AppPaths> {
+    let config_dir =
+        dirs::config_dir().with_context("Could not find the configuration directory")?;
+    let cache_dir = dirs::cache_dir().with_context("Could not find the cache directory")?;
+    let state_dir = dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .with_context("Could not find the state directory")?;
+    Ok(AppPaths {
+        config_dir: config_dir.join(app_name),
+        cache_dir: cache_dir.join(app_name),
+        state_dir: state_dir.join(app_name),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_app() {
+        let Ok(paths) = for_app("shellui_test") else {
+            // No home directory
+            return;
+        };
+        assert!(paths.config_path().ends_with("shellui_test/config.toml"));
+        assert!(paths.history_path().ends_with("shellui_test/history"));
+        assert!(paths.cache_dir().ends_with("shellui_test"));
+    }
+}
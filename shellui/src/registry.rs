@@ -0,0 +1,175 @@
+//! Commands registered at runtime
+//!
+//! Besides the subcommands derived with clap, applications can register
+//! commands at startup or at runtime, for example from plugins. Registered
+//! commands are listed in help, completion and hints like any other command.
+use crate::errors::ShellUiResult;
+use crate::Context;
+use clap::{ArgMatches, Command};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+type Handler<C> = Rc<dyn Fn(&mut C, &ArgMatches) -> ShellUiResult<()>>;
+
+struct RegisteredCommand<C> {
+    command: Command,
+    category: Option<String>,
+    handler: Handler<C>,
+}
+
+/// Registry of commands
+///
+/// Provided to shellui with `Context::registry`.
+pub struct CommandRegistry<C> {
+    commands: BTreeMap<String, RegisteredCommand<C>>,
+    changed: Cell<bool>,
+}
+
+impl<C> CommandRegistry<C> {
+    pub fn new() -> Self {
+        CommandRegistry {
+            commands: BTreeMap::new(),
+            changed: Cell::new(false),
+        }
+    }
+
+    /// Register a command
+    ///
+    /// The command name must not conflict with the other commands. The handler
+    /// is called with the context and the parsed arguments of the command.
+    pub fn register<F>(&mut self, command: Command, handler: F)
+    where
+        F: 'static + Fn(&mut C, &ArgMatches) -> ShellUiResult<()>,
+    {
+        self.insert(command, None, Rc::new(handler));
+    }
+
+    /// Register a command, listed under a category by `help`
+    pub fn register_in<S, F>(&mut self, category: S, command: Command, handler: F)
+    where
+        S: ToString,
+        F: 'static + Fn(&mut C, &ArgMatches) -> ShellUiResult<()>,
+    {
+        self.insert(command, Some(category.to_string()), Rc::new(handler));
+    }
+
+    fn insert(&mut self, command: Command, category: Option<String>, handler: Handler<C>) {
+        let name = command.get_name().to_string();
+        let registered = RegisteredCommand {
+            command,
+            category,
+            handler,
+        };
+        self.commands.insert(name, registered);
+        self.changed.set(true);
+    }
+
+    /// Unregister a command, returning `false` if it was not registered
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let removed = self.commands.remove(name).is_some();
+        self.changed.set(self.changed.get() || removed);
+        removed
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.contains_key(name)
+    }
+
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.commands.values().map(|registered| &registered.command)
+    }
+
+    /// Category of a registered command
+    pub fn category(&self, name: &str) -> Option<&str> {
+        self.commands.get(name)?.category.as_deref()
+    }
+
+    pub(crate) fn handler(&self, name: &str) -> Option<Handler<C>> {
+        self.commands
+            .get(name)
+            .map(|registered| registered.handler.clone())
+    }
+
+    /// Check if commands were registered or unregistered since the last call
+    pub(crate) fn take_changed(&self) -> bool {
+        self.changed.replace(false)
+    }
+}
+
+impl<C> Default for CommandRegistry<C> {
+    fn default() -> Self {
+        CommandRegistry::new()
+    }
+}
+
+/// Run a registered command
+pub(crate) fn run_registered<C>(
+    context: &mut C,
+    name: &str,
+    matches: &ArgMatches,
+) -> ShellUiResult<()>
+where
+    C: Context,
+{
+    match context
+        .registry()
+        .and_then(|registry| registry.handler(name))
+    {
+        Some(handler) => handler(context, matches),
+        None => Ok(()),
+    }
+}
+
+/// Check if a command is registered
+pub(crate) fn is_registered<C>(context: &C, name: &str) -> bool
+where
+    C: Context,
+{
+    context
+        .registry()
+        .is_some_and(|registry| registry.contains(name))
+}
+
+/// Add the registered commands to a command
+pub(crate) fn with_registered<C>(context: &C, command: Command) -> Command
+where
+    C: Context,
+{
+    match context.registry() {
+        Some(registry) => command.subcommands(registry.commands().cloned()),
+        None => command,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = CommandRegistry::<usize>::new();
+        registry.register(Command::new("add"), |count, _| {
+            *count += 1;
+            Ok(())
+        });
+        assert!(registry.contains("add"));
+        assert!(registry.take_changed());
+        assert!(!registry.take_changed());
+        assert_eq!(registry.category("add"), None);
+
+        registry.register_in("Math", Command::new("sub"), |_, _| Ok(()));
+        assert_eq!(registry.category("sub"), Some("Math"));
+        assert!(registry.unregister("sub"));
+
+        let mut count = 0;
+        let handler = registry.handler("add").unwrap();
+        handler(&mut count, &ArgMatches::default()).unwrap();
+        assert_eq!(count, 1);
+
+        assert!(registry.unregister("add"));
+        assert!(!registry.unregister("add"));
+        assert!(registry.take_changed());
+        assert_eq!(registry.commands().count(), 0);
+    }
+}
@@ -0,0 +1,258 @@
+//! Remote shell
+//!
+//! Serve the shell over a socket, so that a running daemon can be
+//! driven remotely, for example with `nc`. Each line received is run
+//! like in the interactive shell, and its output is sent back. The
+//! `exit` builtin closes the connection.
+//!
+//! Prompts are not interactive, as they would read the input of the
+//! daemon, and fail with an error sent back to the connection.
+use crate::errors::ShellUiResult;
+use crate::format::{start_capture, stop_capture};
+use crate::input::{with_scripted_input, NonInteractivePolicy};
+use crate::shell::{run_line, ActiveConfig, LineParser, ShellAction};
+use crate::{install_translator, Context, ShellConfig, ShellParser};
+use std::io::{BufRead, BufReader, Read, Result, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+/// Listener accepting connections
+pub trait Listener {
+    type Stream: Read + Write;
+    fn accept_stream(&self) -> Result<Self::Stream>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+    fn accept_stream(&self) -> Result<Self::Stream> {
+        Ok(self.accept()?.0)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+    fn accept_stream(&self) -> Result<Self::Stream> {
+        Ok(self.accept()?.0)
+    }
+}
+
+/// Serve the shell
+///
+/// Connections are served one at a time, sharing the same context.
+pub fn launch_server<T, L>(listener: L) -> ShellUiResult<()>
+where
+    T: ShellParser,
+    L: Listener,
+{
+    launch_server_with::<T, L>(T::Context::new()?, listener)
+}
+
+/// Serve the shell with a context
+pub fn launch_server_with<T, L>(mut context: T::Context, listener: L) -> ShellUiResult<()>
+where
+    T: ShellParser,
+    L: Listener,
+{
+    install_translator(&context);
+    loop {
+        let stream = listener.accept_stream()?;
+        // A failing connection should not stop the server
+        let _ = serve::<T, _>(&mut context, stream);
+    }
+}
+
+fn serve<T, S>(context: &mut T::Context, stream: S) -> Result<()>
+where
+    T: ShellParser,
+    S: Read + Write,
+{
+    let _config = ActiveConfig::install(ShellConfig {
+        non_interactive_policy: NonInteractivePolicy::Fail,
+        ..context.shell_config()
+    });
+    let mut parser = LineParser::new(context);
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        start_capture();
+        let action =
+            with_scripted_input(&[], || run_line::<T>(context, &mut parser, line.trim_end()));
+        let capture = stop_capture();
+
+        let stream = reader.get_mut();
+        stream.write_all(capture.stdout.as_bytes())?;
+        stream.write_all(capture.stderr.as_bytes())?;
+        stream.flush()?;
+        match action {
+            Ok(ShellAction::None | ShellAction::ClearScreen | ShellAction::ClearAll) => {}
+            Ok(ShellAction::Exit(_)) => break,
+            Err(error) => {
+                writeln!(stream, "{error}")?;
+                break;
+            }
+        }
+        line.clear();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ShellUiError;
+    use crate::format::print_stdout;
+    use crate::input::confirm;
+    use clap::{Parser, Subcommand};
+    use colored::control::set_override;
+    use std::io::{Cursor, Error};
+    use std::net::Shutdown;
+    use std::path::PathBuf;
+    use std::thread;
+
+    #[derive(Default)]
+    struct TestContext {
+        count: usize,
+    }
+
+    impl Context for TestContext {
+        fn new() -> Result<Self> {
+            Ok(TestContext::default())
+        }
+
+        fn history_path(&self) -> Option<PathBuf> {
+            None
+        }
+
+        fn shell_config(&self) -> ShellConfig {
+            // Answers are never read from the input of the server
+            ShellConfig {
+                non_interactive_policy: NonInteractivePolicy::ReadInput,
+                ..ShellConfig::default()
+            }
+        }
+    }
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(subcommand)]
+        command: Option<TestCommand>,
+    }
+
+    #[derive(Subcommand)]
+    enum TestCommand {
+        Hello { name: String },
+        Fail,
+        Confirm,
+    }
+
+    impl ShellParser for TestCli {
+        type Context = TestContext;
+        type Commands = TestCommand;
+
+        fn try_get_command(self) -> Option<Self::Commands> {
+            self.command
+        }
+
+        fn run_command(context: &mut Self::Context, command: &Self::Commands) -> ShellUiResult<()> {
+            context.count += 1;
+            match command {
+                TestCommand::Hello { name } => {
+                    print_stdout(format!("Hello {name}"));
+                    Ok(())
+                }
+                TestCommand::Fail => Err(ShellUiError::from(Error::other("Failed"))),
+                TestCommand::Confirm => {
+                    confirm("Continue?", true)?;
+                    print_stdout("Confirmed");
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Connection reading scripted lines, and recording the response
+    struct TestStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl TestStream {
+        fn new(input: &str) -> Self {
+            TestStream {
+                input: Cursor::new(input.as_bytes().to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for TestStream {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for TestStream {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn serve_lines(context: &mut TestContext, input: &str) -> String {
+        let mut stream = TestStream::new(input);
+        serve::<TestCli, _>(context, &mut stream).unwrap();
+        String::from_utf8(stream.output).unwrap()
+    }
+
+    #[test]
+    fn test_serve() {
+        set_override(false);
+        let mut context = TestContext::default();
+        let output = serve_lines(&mut context, "hello world\nfail\nunknown\n");
+        assert!(output.starts_with("Hello world\n"));
+        assert!(output.contains("Failed"));
+        assert!(output.contains("Unknown command \"unknown\""));
+        assert_eq!(context.count, 2);
+
+        // Lines after exit are not run, and the context is kept between connections
+        let output = serve_lines(&mut context, "exit\nhello world\n");
+        assert_eq!(output, "");
+        assert_eq!(context.count, 2);
+        serve_lines(&mut context, "hello again");
+        assert_eq!(context.count, 3);
+    }
+
+    #[test]
+    fn test_serve_prompt() {
+        set_override(false);
+        let mut context = TestContext::default();
+        let output = serve_lines(&mut context, "confirm\nhello world\n");
+        assert!(output.contains("\"Continue?\" is required, but the input is not interactive"));
+        assert!(!output.contains("Confirmed"));
+        assert!(output.ends_with("Hello world\n"));
+    }
+
+    #[test]
+    fn test_serve_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            stream.write_all(b"hello remote\nexit\n").unwrap();
+            stream.shutdown(Shutdown::Write).unwrap();
+            let mut output = String::new();
+            stream.read_to_string(&mut output).unwrap();
+            output
+        });
+
+        let mut context = TestContext::default();
+        let stream = listener.accept_stream().unwrap();
+        serve::<TestCli, _>(&mut context, stream).unwrap();
+        assert_eq!(client.join().unwrap(), "Hello remote\n");
+    }
+}
@@ -1,123 +1,1635 @@
+mod bookmarks;
+mod help;
+mod history;
+mod keys;
+mod search;
+mod sessions;
 mod ui;
+mod variables;
 
+use self::bookmarks::BookmarkCommand;
+use self::help::format_help;
+use self::history::{expire as expire_history, print_history, record as record_history};
+use self::keys::{bind_keys, LastCommand};
+pub use self::keys::{KeyAction, KeyBinding};
+use self::search::search;
+use self::sessions::{SessionCommand, Sessions};
 use self::ui::ShellUi;
-use crate::errors::ShellUiError;
-use crate::format::AsFormatted;
-use crate::{Context, ShellParser};
-use clap::{CommandFactory, Parser, Subcommand};
+use self::variables::{parse_assignment, Variables};
+use crate::completions::{print_completions, Shell};
+use crate::config::display_value;
+use crate::errors::{RetryPolicy, ShellUiError, ShellUiResult, WithContext};
+use crate::format::{
+    is_capturing, print_stderr, print_stdout, start_data_capture, stop_capture, AsFormatted,
+    Message, OutputFormat, PrintTable, Stream,
+};
+use crate::i18n::{tr, tr_args};
+use crate::input::{confirm, is_interactive, NonInteractivePolicy};
+use crate::registry::{is_registered, run_registered, with_registered, CommandRegistry};
+use crate::spinner::Spinner;
+use crate::telemetry::{self, command_name, session_stats};
+use crate::update::UpdateCheck;
+use crate::{notify_error, print_error, run_command, Context, ShellParser};
+#[cfg(feature = "async")]
+use crate::{run_command_async, AsyncShellParser};
+use clap::error::ErrorKind as ClapErrorKind;
+use clap::{ArgMatches, Command, CommandFactory, FromArgMatches, Parser, Subcommand};
+use colored::Colorize;
 use rustyline::error::ReadlineError;
-use rustyline::history::FileHistory;
+use rustyline::history::{FileHistory, History};
 use rustyline::{CompletionType, Config, Editor};
-use std::io::{Error, Result};
+use std::cell::RefCell;
+use std::cmp::max;
+use std::fs;
+use std::io::{self, Error, ErrorKind, Result, Write};
 use std::iter;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+#[cfg(feature = "async")]
+use tokio::{signal, task};
+
+const BUILTIN_COMMANDS: &[&str] = &[
+    "bookmark",
+    "clear",
+    "completions",
+    "config",
+    "exit",
+    "history",
+    "search",
+    "session",
+    "stats",
+];
+
+/// Interaction of the shell with completion candidates
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CompletionStyle {
+    /// Complete the common prefix, then list the candidates
+    #[default]
+    List,
+    /// Cycle through the candidates with each `Tab`
+    Circular,
+}
+
+/// Shell configuration
+#[derive(Clone, Debug)]
+pub struct ShellConfig {
+    /// Display inline hints
+    pub hints: bool,
+    /// Minimum number of typed characters before hints are displayed
+    pub hint_min_chars: usize,
+    /// Retry policy for commands failing with a retryable error
+    pub retry_policy: Option<RetryPolicy>,
+    /// Run commands from unambiguous prefixes, like `net cr` for `network create`
+    pub abbreviations: bool,
+    /// Display a spinner with the elapsed time while a command runs
+    pub spinner: bool,
+    /// Show hidden commands, like experimental ones, in completion, hints and help
+    pub expert: bool,
+    /// Keys bound to actions on the previous command
+    pub key_bindings: Vec<(KeyBinding, KeyAction)>,
+    /// Clear the scrollback with `clear`, as with `clear --all`
+    pub clear_scrollback: bool,
+    /// Remove the history entries older than this number of days
+    pub history_expiry_days: Option<u64>,
+    /// Interaction with completion candidates
+    pub completion_style: CompletionStyle,
+    /// Number of completion candidates listed without asking first
+    ///
+    /// Longer lists are paged, after a confirmation.
+    pub completion_max_rows: usize,
+    /// Output format of commands, also set with `--output`
+    pub output_format: OutputFormat,
+    /// Print the headers of tables, also unset with `--no-headers`
+    pub show_headers: bool,
+    /// Stream used by `AsFormatted::print_formatted`
+    ///
+    /// Messages are printed to stderr by default, keeping stdout for the
+    /// output of commands, like tables, that might be piped. Errors are
+    /// always printed to stderr.
+    pub message_stream: Stream,
+    /// Text displayed for missing values, like `-` or `<none>`
+    ///
+    /// Missing values are displayed empty by default.
+    pub null_display: String,
+    /// Behavior of prompts when the input is not interactive
+    pub non_interactive_policy: NonInteractivePolicy,
+    /// Answer yes to all confirmations, also set with `--yes`
+    pub assume_yes: bool,
+    /// Abort prompts after this number of invalid answers
+    ///
+    /// Prompts are asked again until the answer is valid by default.
+    pub max_retries: Option<usize>,
+    /// Exit code of commands ending with a warning
+    ///
+    /// Warnings are not failures by default and exit with 0.
+    pub warning_exit_code: u8,
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        ShellConfig {
+            hints: true,
+            hint_min_chars: 0,
+            retry_policy: None,
+            abbreviations: false,
+            spinner: true,
+            expert: false,
+            key_bindings: vec![
+                (KeyBinding::alt('.'), KeyAction::InsertLastArgument),
+                (KeyBinding::ctrl('o'), KeyAction::RunPrevious),
+            ],
+            clear_scrollback: false,
+            history_expiry_days: None,
+            completion_style: CompletionStyle::List,
+            completion_max_rows: 100,
+            output_format: OutputFormat::Human,
+            show_headers: true,
+            message_stream: Stream::Stderr,
+            null_display: String::new(),
+            non_interactive_policy: NonInteractivePolicy::Fail,
+            assume_yes: false,
+            max_retries: None,
+            warning_exit_code: 0,
+        }
+    }
+}
+
+// Printing and prompts do not have access to the context, and read the
+// configuration of the launch running on the current thread instead.
+thread_local! {
+    static ACTIVE_CONFIG: RefCell<Rc<ShellConfig>> = RefCell::new(Rc::default());
+}
+
+/// Configuration of the launch running on the current thread
+///
+/// The previous configuration is restored when dropped.
+pub(crate) struct ActiveConfig(Rc<ShellConfig>);
+
+impl ActiveConfig {
+    pub(crate) fn install(config: ShellConfig) -> Self {
+        ActiveConfig(ACTIVE_CONFIG.replace(Rc::new(config)))
+    }
+}
+
+impl Drop for ActiveConfig {
+    fn drop(&mut self) {
+        ACTIVE_CONFIG.set(self.0.clone());
+    }
+}
+
+/// Read the configuration of the launch running on the current thread
+pub(crate) fn active_config<F, R>(f: F) -> R
+where
+    F: FnOnce(&ShellConfig) -> R,
+{
+    ACTIVE_CONFIG.with_borrow(|config| f(config))
+}
 
 #[derive(Parser)]
 #[command(bin_name = "", disable_version_flag = true, disable_help_flag = true)]
-struct ShellArgs<T>
+struct ShellArgs<C>
 where
-    T: ShellParser,
+    C: Subcommand,
 {
     #[command(subcommand)]
-    command: ShellCommand<T>,
+    command: ShellCommand<C>,
 }
-impl<T> ShellArgs<T>
+
+#[derive(Clone, Debug, Subcommand)]
+enum ShellCommand<C>
 where
-    T: ShellParser,
+    C: Subcommand,
 {
-    pub fn try_run(context: &mut T::Context, line: &str) -> Result<ShellAction> {
-        let parsed = shell_words::split(line).map_err(Error::other)?;
+    #[command(flatten)]
+    Common(C),
+    /// Bookmark and run command lines
+    Bookmark {
+        #[command(subcommand)]
+        command: BookmarkCommand,
+    },
+    /// Clear the shell
+    Clear {
+        /// Also clear the scrollback
+        #[arg(long)]
+        all: bool,
+    },
+    /// Print the completion script for a system shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Get or set configuration values
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Exit the shell
+    Exit {
+        /// Exit code of the process, for scripts
+        code: Option<u8>,
+        /// Exit even if background jobs are running
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the history, with the time of each command
+    History {
+        /// Number of entries to print
+        #[arg(short = 'n', long)]
+        count: Option<usize>,
+    },
+    /// Search commands by name or description
+    Search { keyword: String },
+    /// Create and switch between sessions
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+    /// Print statistics on the commands run in this session
+    Stats,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print a configuration value
+    Get { key: String },
+    /// Set a configuration value
+    Set { key: String, value: String },
+    /// List all configuration values
+    List,
+}
 
-        if !parsed.is_empty() {
-            let iter = iter::once("shellui").chain(parsed.iter().map(String::as_str));
-            match ShellArgs::<T>::try_parse_from(iter) {
-                Ok(args) => args.command.run(context),
-                Err(error) => {
-                    error.print()?;
-                    Ok(ShellAction::None)
+impl ConfigCommand {
+    fn run<X>(&self, context: &mut X) -> ShellUiResult<()>
+    where
+        X: Context,
+    {
+        let no_config = || tr("shellui-config-missing", "No configuration available");
+        match self {
+            ConfigCommand::Get { key } => {
+                let config = context.config().with_context(no_config())?;
+                let value = config.get(key).with_context(tr_args(
+                    "shellui-config-unknown-key",
+                    "Unknown configuration key { $key }",
+                    &[("key", key)],
+                ))?;
+                print_stdout(display_value(value));
+            }
+            ConfigCommand::Set { key, value } => {
+                let config = context.config_mut().with_context(no_config())?;
+                config.set(key, value)?;
+                config.save()?;
+                context.config_changed(key);
+            }
+            ConfigCommand::List => {
+                let config = context.config().with_context(no_config())?;
+                for (key, value) in config.list() {
+                    print_stdout(format!("{key} = {value}"));
                 }
             }
-        } else {
-            Ok(ShellAction::None)
         }
+        Ok(())
     }
 }
 
-#[derive(Clone, Debug, Subcommand)]
-enum ShellCommand<T>
-where
-    T: ShellParser,
-{
-    #[command(flatten)]
-    Common(T::Commands),
-    /// Clear the shell
-    Clear,
-    /// Exit the shell
-    Exit,
-}
+#[cfg_attr(not(feature = "docs"), allow(dead_code))]
+#[derive(Subcommand)]
+enum NoCommands {}
 
 pub enum ShellAction {
     None,
     ClearScreen,
-    Eof,
+    /// Clear the screen and the scrollback
+    ClearAll,
+    /// Exit the shell, with the exit code of the process
+    Exit(u8),
 }
 
-impl<T> ShellCommand<T>
+impl<C> ShellCommand<C>
 where
-    T: ShellParser,
+    C: Subcommand,
 {
-    fn run(&self, context: &mut T::Context) -> Result<ShellAction> {
+    /// Run a builtin command
+    ///
+    /// `P` is the application CLI.
+    fn run_builtin<P, X>(&self, context: &mut X) -> ShellAction
+    where
+        P: CommandFactory,
+        X: Context,
+    {
         match self {
-            ShellCommand::Common(command) => match T::run_command(context, command) {
-                Ok(()) => Ok(ShellAction::None),
-                Err(error) => match error {
-                    ShellUiError::Interrupt => Ok(ShellAction::None),
-                    _ => {
-                        error.print_formatted();
-                        Ok(ShellAction::None)
+            // Bookmarks and sessions are handled when parsing
+            ShellCommand::Common(_)
+            | ShellCommand::Bookmark { .. }
+            | ShellCommand::Session { .. } => ShellAction::None,
+            ShellCommand::Clear { all } if *all || context.shell_config().clear_scrollback => {
+                ShellAction::ClearAll
+            }
+            ShellCommand::Clear { .. } => ShellAction::ClearScreen,
+            ShellCommand::Completions { shell } => {
+                print_completions::<P>(*shell);
+                ShellAction::None
+            }
+            ShellCommand::Config { command } => {
+                let result = command.run(context);
+                report_error(context, result);
+                ShellAction::None
+            }
+            ShellCommand::Exit { code, force } if *force || confirm_exit(context) => {
+                ShellAction::Exit(code.unwrap_or_default())
+            }
+            ShellCommand::Exit { .. } => ShellAction::None,
+            ShellCommand::History { count } => {
+                let result = print_history(context, *count);
+                report_error(context, result);
+                ShellAction::None
+            }
+            ShellCommand::Search { keyword } => {
+                let results = search(&build_command::<C, _>(context), keyword);
+                if results.is_empty() {
+                    Message::info(tr_args(
+                        "shellui-search-empty",
+                        "No command matches { $keyword }",
+                        &[("keyword", keyword)],
+                    ))
+                    .print_formatted();
+                } else {
+                    results.print_table_default();
+                }
+                ShellAction::None
+            }
+            ShellCommand::Stats => {
+                let commands = session_stats().commands();
+                if commands.is_empty() {
+                    Message::info(tr("shellui-stats-empty", "No command run in this session"))
+                        .print_formatted();
+                } else {
+                    commands.print_table_default();
+                }
+                ShellAction::None
+            }
+        }
+    }
+}
+
+enum ParsedLine<C>
+where
+    C: Subcommand,
+{
+    Shell(ShellCommand<C>),
+    Registered(String, ArgMatches),
+}
+
+/// Parser of shell lines
+///
+/// The clap command is built once, and rebuilt only when the
+/// command set changes.
+pub(crate) struct LineParser<C, X>
+where
+    C: Subcommand,
+{
+    command: Command,
+    /// Previous command line, that can be bookmarked
+    previous_line: Option<String>,
+    sessions: Sessions<X>,
+    /// Set when switching sessions, as each context has its commands
+    session_changed: bool,
+    variables: Variables,
+    _commands: PhantomData<C>,
+}
+
+impl<C, X> LineParser<C, X>
+where
+    C: Subcommand,
+    X: Context,
+{
+    pub(crate) fn new(context: &X) -> Self {
+        LineParser {
+            command: build_command::<C, _>(context),
+            previous_line: None,
+            sessions: Sessions::new(),
+            session_changed: false,
+            variables: Variables::default(),
+            _commands: PhantomData,
+        }
+    }
+
+    /// Prompt of the shell
+    fn prompt(&self) -> String {
+        self.sessions.prompt()
+    }
+
+    fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Rebuild the command if the command set changed
+    ///
+    /// Returns `true` if the command was rebuilt.
+    pub(crate) fn update(&mut self, context: &mut X) -> bool {
+        // All are checked to reset the change flags
+        let registry_changed = context
+            .registry()
+            .is_some_and(CommandRegistry::take_changed);
+        let session_changed = mem::take(&mut self.session_changed);
+        let changed = context.commands_changed() || registry_changed || session_changed;
+        if changed {
+            self.command = build_command::<C, _>(context);
+        }
+        changed
+    }
+
+    /// Parse a shell line
+    ///
+    /// Returns the name of the command and the parsed command, or `None`
+    /// if the line is empty, or if it cannot be parsed, after printing the error.
+    /// Bookmark commands are run, and `bookmark run` returns the bookmarked line.
+    fn parse(&mut self, context: &mut X, line: &str) -> Result<Option<(String, ParsedLine<C>)>> {
+        match self.parse_line(context, line)? {
+            Some((_, ParsedLine::Shell(ShellCommand::Session { command }))) => {
+                let result = self.sessions.run(&command, context);
+                self.session_changed = result.is_ok();
+                report_error(context, result);
+                Ok(None)
+            }
+            Some((_, ParsedLine::Shell(ShellCommand::Bookmark { command }))) => {
+                match command.run(context, self.previous_line.as_deref()) {
+                    Ok(Some(line)) => self.parse(context, &line),
+                    result => {
+                        report_error(context, result.map(|_| ()));
+                        Ok(None)
                     }
-                },
-            },
-            ShellCommand::Clear => Ok(ShellAction::ClearScreen),
-            ShellCommand::Exit => Ok(ShellAction::Eof),
+                }
+            }
+            Some(parsed) => {
+                self.previous_line = Some(line.trim().to_string());
+                Ok(Some(parsed))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn parse_line(&mut self, context: &X, line: &str) -> Result<Option<(String, ParsedLine<C>)>> {
+        let line = self.variables.expand(line);
+        let mut parsed = match shell_words::split(&line) {
+            Ok(parsed) => parsed,
+            Err(error) => {
+                Message::error(error.to_string()).print_to(Stream::Stderr);
+                return Ok(None);
+            }
+        };
+        if context.shell_config().abbreviations {
+            if let Err(error) = expand_prefixes(&self.command, &mut parsed) {
+                Message::error(error).print_to(Stream::Stderr);
+                return Ok(None);
+            }
+        }
+        if parsed.is_empty() {
+            return Ok(None);
+        }
+        if parsed == ["help"] {
+            for line in format_help(context, &self.command) {
+                print_stdout(line);
+            }
+            return Ok(None);
+        }
+
+        let iter = iter::once("shellui").chain(parsed.iter().map(String::as_str));
+        let mut matches = match self.command.try_get_matches_from_mut(iter) {
+            Ok(matches) => matches,
+            Err(error) if error.kind() == ClapErrorKind::InvalidSubcommand => {
+                match unknown_command(&self.command, &parsed) {
+                    Some(message) => Message::error(message).print_to(Stream::Stderr),
+                    None => print_parse_error(&self.command, &parsed, error)?,
+                }
+                return Ok(None);
+            }
+            Err(error) => {
+                print_parse_error(&self.command, &parsed, error)?;
+                return Ok(None);
+            }
+        };
+        let command = command_name(&matches);
+        if let Some(name) = matches.subcommand_name() {
+            if is_registered(context, name) {
+                let registered = matches.remove_subcommand();
+                return Ok(registered
+                    .map(|(name, matches)| (command, ParsedLine::Registered(name, matches))));
+            }
+        }
+        match ShellArgs::<C>::from_arg_matches(&matches) {
+            Ok(args) => Ok(Some((command, ParsedLine::Shell(args.command)))),
+            Err(error) => {
+                print_parse_error(&self.command, &parsed, error)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Replace unambiguous prefixes of subcommands by their names
+///
+/// Returns an error listing the candidates if a prefix is ambiguous.
+fn expand_prefixes(command: &Command, args: &mut [String]) -> std::result::Result<(), String> {
+    let mut command = command;
+    for arg in args.iter_mut() {
+        if !command.has_subcommands() || arg.starts_with('-') || arg == "help" {
+            break;
+        }
+        if let Some(subcommand) = command.find_subcommand(arg.as_str()) {
+            command = subcommand;
+            continue;
+        }
+
+        let mut candidates = command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(Command::get_name)
+            .chain(iter::once("help"))
+            .filter(|name| name.starts_with(arg.as_str()))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+        candidates.dedup();
+        match candidates.as_slice() {
+            [] => break,
+            [name] if *name == "help" => {
+                *arg = name.to_string();
+                break;
+            }
+            [name] => {
+                command = command.find_subcommand(name).unwrap_or(command);
+                *arg = name.to_string();
+            }
+            _ => {
+                return Err(tr_args(
+                    "shellui-ambiguous-command",
+                    "Ambiguous command \"{ $command }\", could be { $candidates }",
+                    &[("command", arg), ("candidates", &candidates.join(", "))],
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Error message for an unknown subcommand, with the closest commands
+fn unknown_command(command: &Command, args: &[String]) -> Option<String> {
+    let mut command = command;
+    let unknown = args
+        .iter()
+        .find(|arg| match command.find_subcommand(arg.as_str()) {
+            Some(subcommand) => {
+                command = subcommand;
+                false
+            }
+            None => true,
+        })?;
+    if !command.has_subcommands() || unknown.starts_with('-') {
+        return None;
+    }
+
+    // Typos are at most a third of the name, and at least one character
+    let max_distance = max(1, unknown.chars().count() / 3);
+    let mut candidates = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .map(Command::get_name)
+        .map(|name| (levenshtein(unknown, name), name))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    candidates.sort_unstable();
+
+    let message = match candidates.as_slice() {
+        [] => tr_args(
+            "shellui-unknown-command",
+            "Unknown command \"{ $command }\"",
+            &[("command", unknown)],
+        ),
+        _ => {
+            let candidates = candidates
+                .iter()
+                .map(|(_, name)| format!("\"{name}\""))
+                .collect::<Vec<_>>();
+            tr_args(
+                "shellui-unknown-command-suggestions",
+                "Unknown command \"{ $command }\", did you mean { $candidates }?",
+                &[("command", unknown), ("candidates", &candidates.join(", "))],
+            )
+        }
+    };
+    Some(message)
+}
+
+/// Number of single-character edits to change a string into another
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut distances = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = distances[j + 1];
+            distances[j + 1] = substitution.min(previous + 1).min(distances[j] + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+/// Print a concise parse error
+///
+/// Prints the problem, the usage of the subcommand and a hint to get its
+/// help, instead of the full error of clap.
+fn print_parse_error(command: &Command, args: &[String], error: clap::Error) -> Result<()> {
+    let kind = error.kind();
+    if !error.use_stderr()
+        || kind == ClapErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        || kind == ClapErrorKind::MissingSubcommand
+    {
+        return print_clap_error(error);
+    }
+    let rendered = error.render().to_string();
+    let (problem, details) = rendered.split_once("\n\n").unwrap_or((&rendered, ""));
+    let problem = problem.strip_prefix("error: ").unwrap_or(problem);
+    let problem = problem.lines().map(str::trim).collect::<Vec<_>>().join(" ");
+    Message::error(problem).print_to(Stream::Stderr);
+
+    if let Some(usage) = details
+        .lines()
+        .find_map(|line| line.strip_prefix("Usage: "))
+    {
+        // The shell has no binary name
+        let usage = tr_args(
+            "shellui-usage",
+            "usage: { $usage }",
+            &[("usage", usage.trim())],
+        );
+        print_stderr(format!("  {}", usage.dimmed()));
+    }
+    let path = iter::once("help")
+        .chain(subcommand_path(command, args))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Message::hint(tr_args(
+        "shellui-parse-error-help",
+        "Run \"{ $command }\" for more information",
+        &[("command", &path)],
+    ))
+    .print_to(Stream::Stderr);
+    Ok(())
+}
+
+/// Names of the subcommands starting the args
+fn subcommand_path<'a>(command: &Command, args: &'a [String]) -> Vec<&'a str> {
+    let mut command = command;
+    let mut path = Vec::new();
+    for arg in args {
+        match command.find_subcommand(arg.as_str()) {
+            Some(subcommand) => {
+                command = subcommand;
+                path.push(arg.as_str());
+            }
+            None => break,
         }
     }
+    path
 }
 
-pub fn launch_shell<T>(context: &mut T::Context) -> Result<()>
+fn print_clap_error(error: clap::Error) -> Result<()> {
+    if is_capturing() {
+        let rendered = error.render().to_string();
+        if error.use_stderr() {
+            print_stderr(rendered.trim_end());
+        } else {
+            print_stdout(rendered.trim_end());
+        }
+        Ok(())
+    } else {
+        error.print()
+    }
+}
+
+/// Ask for a confirmation before exiting while background jobs are running
+fn confirm_exit<X>(context: &X) -> bool
 where
-    T: ShellParser,
+    X: Context,
+{
+    // Interrupting the confirmation keeps the shell open
+    !warn_running_jobs(context)
+        || confirm(&tr("shellui-exit-confirm", "Exit anyway?"), false).unwrap_or(false)
+}
+
+/// Warn about the running jobs, if any
+///
+/// Returns if jobs are running.
+fn warn_running_jobs<X>(context: &X) -> bool
+where
+    X: Context,
 {
-    let history_path = context.history_path();
-    let helper = ShellUi::new(ShellArgs::<T>::command());
+    let jobs = context.running_jobs();
+    let message = match jobs {
+        0 => return false,
+        1 => tr("shellui-exit-job-running", "1 job still running"),
+        _ => tr_args(
+            "shellui-exit-jobs-running",
+            "{ $count } jobs still running",
+            &[("count", &jobs.to_string())],
+        ),
+    };
+    Message::warning(message).print_to(Stream::Stderr);
+    true
+}
+
+fn report_error<X>(context: &mut X, result: ShellUiResult<()>)
+where
+    X: Context,
+{
+    if let Err(error) = result {
+        notify_error(context, &error);
+        if !matches!(error, ShellUiError::Interrupt) {
+            print_error(&error);
+        }
+    }
+}
+
+fn build_command<C, X>(context: &X) -> Command
+where
+    C: Subcommand,
+    X: Context,
+{
+    let command = shell_command::<C>();
+    let command = if context.config().is_some() {
+        command
+    } else {
+        command.mut_subcommand("config", |command| command.hide(true))
+    };
+    with_registered(context, command)
+}
+
+/// Shell builtin commands
+#[cfg_attr(not(feature = "docs"), allow(dead_code))]
+pub(crate) fn builtin_commands() -> Vec<Command> {
+    shell_command::<NoCommands>()
+        .get_subcommands()
+        .cloned()
+        .collect()
+}
+
+fn shell_command<C>() -> Command
+where
+    C: Subcommand,
+{
+    ShellArgs::<C>::command()
+        .mut_subcommand("bookmark", |command| {
+            command.about(tr(
+                "shellui-bookmark-about",
+                "Bookmark and run command lines",
+            ))
+        })
+        .mut_subcommand("clear", |command| {
+            command.about(tr("shellui-clear-about", "Clear the shell"))
+        })
+        .mut_subcommand("completions", |command| {
+            command.about(tr(
+                "shellui-completions-about",
+                "Print the completion script for a system shell",
+            ))
+        })
+        .mut_subcommand("config", |command| {
+            command.about(tr(
+                "shellui-config-about",
+                "Get or set configuration values",
+            ))
+        })
+        .mut_subcommand("exit", |command| {
+            command.about(tr("shellui-exit-about", "Exit the shell"))
+        })
+        .mut_subcommand("history", |command| {
+            command.about(tr(
+                "shellui-history-about",
+                "Print the history, with the time of each command",
+            ))
+        })
+        .mut_subcommand("search", |command| {
+            command.about(tr(
+                "shellui-search-about",
+                "Search commands by name or description",
+            ))
+        })
+        .mut_subcommand("session", |command| {
+            command.about(tr(
+                "shellui-session-about",
+                "Create and switch between sessions",
+            ))
+        })
+        .mut_subcommand("stats", |command| {
+            command.about(tr(
+                "shellui-stats-about",
+                "Print statistics on the commands run in this session",
+            ))
+        })
+}
+
+fn create_editor<X>(
+    context: &X,
+    command: Command,
+    last_command: &Arc<LastCommand>,
+) -> Result<Editor<ShellUi, FileHistory>>
+where
+    X: Context,
+{
+    let shell_config = context.shell_config();
+    let helper = ShellUi::new(command)
+        .with_builtins(BUILTIN_COMMANDS)
+        .with_hints(shell_config.hints.then_some(shell_config.hint_min_chars))
+        .with_expert(shell_config.expert);
+    let completion_type = match shell_config.completion_style {
+        CompletionStyle::List => CompletionType::List,
+        CompletionStyle::Circular => CompletionType::Circular,
+    };
     let config = Config::builder()
-        .completion_type(CompletionType::List)
+        .completion_type(completion_type)
+        .completion_prompt_limit(shell_config.completion_max_rows)
         .auto_add_history(true)
         .build();
     let mut rl: Editor<ShellUi, FileHistory> = Editor::with_config(config).map_err(Error::other)?;
     rl.set_helper(Some(helper));
-    if let Some(history_path) = &history_path {
+    bind_keys(&mut rl, &shell_config.key_bindings, last_command);
+    if let Some(history_path) = context.history_path() {
         rl.load_history(&history_path).map_err(Error::other)?;
     }
+    if let Some(days) = shell_config.history_expiry_days {
+        expire_history(rl.history_mut(), context, days)?;
+    }
+    Ok(rl)
+}
+
+/// Update the editor after a command
+///
+/// Breaks with the exit code if the shell should exit.
+fn after_command<C, X>(
+    rl: &mut Editor<ShellUi, FileHistory>,
+    context: &mut X,
+    parser: &mut LineParser<C, X>,
+    action: ShellAction,
+) -> Result<ControlFlow<u8>>
+where
+    C: Subcommand,
+    X: Context,
+{
+    if parser.update(context) {
+        if let Some(helper) = rl.helper_mut() {
+            helper.rebuild(parser.command().clone());
+        }
+    }
+    match action {
+        ShellAction::None => {}
+        ShellAction::ClearScreen => rl.clear_screen().map_err(Error::other)?,
+        ShellAction::ClearAll => {
+            rl.clear_screen().map_err(Error::other)?;
+            // Not all terminals support clearing the scrollback
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x1b[3J")?;
+            stdout.flush()?;
+            print_banner(context);
+        }
+        ShellAction::Exit(code) => return Ok(ControlFlow::Break(code)),
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+fn print_banner<X>(context: &X)
+where
+    X: Context,
+{
+    if let Some(banner) = context.banner() {
+        print_stdout(banner);
+    }
+}
+
+/// Save the history
+///
+/// The new entries are merged with the history file while it is locked,
+/// so that the histories of concurrent shells are combined.
+fn save_history<X>(history: &mut FileHistory, context: &X) -> Result<()>
+where
+    X: Context,
+{
+    if let Some(history_path) = context.history_path() {
+        history.append(&history_path).map_err(Error::other)?;
+    }
+    Ok(())
+}
+
+fn load_state<X>(context: &mut X) -> Result<()>
+where
+    X: Context,
+{
+    let Some(state_path) = context.state_path() else {
+        return Ok(());
+    };
+    let content = match fs::read_to_string(&state_path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    let state = serde_json::from_str(&content).map_err(Error::other)?;
+    context.load_state(state)
+}
+
+fn save_state<X>(context: &X) -> Result<()>
+where
+    X: Context,
+{
+    if let (Some(state_path), Some(state)) = (context.state_path(), context.save_state()) {
+        fs::write(state_path, state.to_string())?;
+    }
+    Ok(())
+}
+
+/// Command of a line, run by the sync or async shell
+enum LineCommand<C> {
+    /// The line was handled, like builtins and registered commands
+    Done(ShellAction),
+    /// Common command, with its name and start time for telemetry
+    Run(String, Instant, C),
+}
+
+/// Start running a line
+///
+/// Variable assignments, like `ids = network list`, capture the data of
+/// their command. Returns the variable, if any, and the command.
+fn begin_line<'a, C, X>(
+    context: &mut X,
+    parser: &mut LineParser<C, X>,
+    line: &'a str,
+) -> (Option<&'a str>, &'a str)
+where
+    C: Subcommand,
+    X: Context,
+{
+    // Commands might have been changed outside of the shell
+    parser.update(context);
+    match parse_assignment(line) {
+        Some((name, command)) => {
+            start_data_capture();
+            (Some(name), command)
+        }
+        None => (None, line),
+    }
+}
+
+/// End running a line, setting the variable to the captured data
+fn end_line<C, X>(parser: &mut LineParser<C, X>, variable: Option<&str>)
+where
+    C: Subcommand,
+    X: Context,
+{
+    if let Some(name) = variable {
+        parser.variables.set(name, &stop_capture().stdout);
+    }
+}
+
+/// Parse a line, running builtins and registered commands
+///
+/// `P` is the application CLI. Common commands are returned, to be run
+/// by the sync or async shell.
+fn prepare_line<P, C, X>(
+    context: &mut X,
+    parser: &mut LineParser<C, X>,
+    line: &str,
+) -> Result<LineCommand<C>>
+where
+    P: CommandFactory,
+    C: Subcommand,
+    X: Context,
+{
+    let start = Instant::now();
+    let command = match parser.parse(context, line)? {
+        Some((name, ParsedLine::Shell(ShellCommand::Common(command)))) => {
+            LineCommand::Run(name, start, command)
+        }
+        Some((_, ParsedLine::Shell(command))) => {
+            LineCommand::Done(command.run_builtin::<P, _>(context))
+        }
+        Some((name, ParsedLine::Registered(registered, matches))) => {
+            let spinner = Spinner::start(context.shell_config().spinner);
+            let result = run_registered(context, &registered, &matches);
+            drop(spinner);
+            finish_command(context, name, start, result);
+            LineCommand::Done(ShellAction::None)
+        }
+        None => LineCommand::Done(ShellAction::None),
+    };
+    Ok(command)
+}
+
+/// Record and report the result of a command
+fn finish_command<X>(context: &mut X, name: String, start: Instant, result: ShellUiResult<()>)
+where
+    X: Context,
+{
+    telemetry::record(context, name, start, &result);
+    report_error(context, result);
+}
+
+/// Parse and run a shell line
+pub(crate) fn run_line<T>(
+    context: &mut T::Context,
+    parser: &mut LineParser<T::Commands, T::Context>,
+    line: &str,
+) -> Result<ShellAction>
+where
+    T: ShellParser,
+{
+    let (variable, line) = begin_line(context, parser, line);
+    let action = prepare_line::<T, _, _>(context, parser, line).map(|command| match command {
+        LineCommand::Done(action) => action,
+        LineCommand::Run(name, start, command) => {
+            let spinner = Spinner::start(context.shell_config().spinner);
+            let result = run_command::<T>(context, &command);
+            drop(spinner);
+            finish_command(context, name, start, result);
+            ShellAction::None
+        }
+    });
+    end_line(parser, variable);
+    action
+}
+
+/// Async twin of `run_line`
+#[cfg(feature = "async")]
+async fn run_line_async<T>(
+    context: &mut T::Context,
+    parser: &mut LineParser<T::Commands, T::Context>,
+    line: &str,
+) -> Result<ShellAction>
+where
+    T: AsyncShellParser,
+{
+    let (variable, line) = begin_line(context, parser, line);
+    let action = match prepare_line::<T, _, _>(context, parser, line) {
+        Ok(LineCommand::Run(name, start, command)) => {
+            let spinner = Spinner::start(context.shell_config().spinner);
+            let result = tokio::select! {
+                result = run_command_async::<T>(context, &command) => result,
+                _ = signal::ctrl_c() => Err(ShellUiError::Interrupt),
+            };
+            drop(spinner);
+            finish_command(context, name, start, result);
+            Ok(ShellAction::None)
+        }
+        Ok(LineCommand::Done(action)) => Ok(action),
+        Err(error) => Err(error),
+    };
+    end_line(parser, variable);
+    action
+}
+
+fn readline(rl: &mut Editor<ShellUi, FileHistory>, prompt: &str) -> rustyline::Result<String> {
+    #[cfg(feature = "logging")]
+    crate::logging::set_prompt_active(true);
+    let readline = rl.readline(prompt);
+    #[cfg(feature = "logging")]
+    crate::logging::set_prompt_active(false);
+    readline
+}
 
-    loop {
-        let readline = rl.readline("> ");
+/// State of a running shell, shared by the sync and async loops
+///
+/// The editor is kept apart, as the async loop moves it to a blocking
+/// task while reading a line.
+struct ShellLoop<C, X>
+where
+    C: Subcommand,
+    X: Context,
+{
+    parser: LineParser<C, X>,
+    last_command: Arc<LastCommand>,
+    update_check: UpdateCheck,
+}
+
+impl<C, X> ShellLoop<C, X>
+where
+    C: Subcommand,
+    X: Context,
+{
+    fn start(context: &mut X) -> Result<(Self, Editor<ShellUi, FileHistory>)> {
+        let parser = LineParser::<C, _>::new(context);
+        let last_command = Arc::new(LastCommand::default());
+        let rl = create_editor(context, parser.command().clone(), &last_command)?;
+        load_state(context)?;
+        context.on_start()?;
+        print_banner(context);
+        let update_check = UpdateCheck::start(context);
+        let shell = ShellLoop {
+            parser,
+            last_command,
+            update_check,
+        };
+        Ok((shell, rl))
+    }
+
+    /// Prompt of the next line, printed after the pending notices
+    fn prompt(&mut self) -> String {
+        self.update_check.print_notice();
+        self.parser.prompt()
+    }
+
+    /// Line to run from the result of reading a line
+    ///
+    /// Breaks if the shell should exit.
+    fn accept(
+        &self,
+        context: &X,
+        readline: rustyline::Result<String>,
+    ) -> ControlFlow<(), Option<String>> {
         match readline {
-            Ok(line) => match ShellArgs::<T>::try_run(context, &line)? {
-                ShellAction::None => {}
-                ShellAction::ClearScreen => rl.clear_screen().map_err(Error::other)?,
-                ShellAction::Eof => break,
-            },
-            Err(ReadlineError::Interrupted) => {
-                // Continue
+            Ok(line) => ControlFlow::Continue(Some(self.last_command.resolve(line))),
+            Err(ReadlineError::Interrupted) => ControlFlow::Continue(None),
+            // The end of a non-interactive input is read again on every call
+            Err(ReadlineError::Eof) if !is_interactive() => {
+                warn_running_jobs(context);
+                ControlFlow::Break(())
             }
-            Err(ReadlineError::Eof) => break,
-            Err(_) => break,
+            Err(ReadlineError::Eof) if !confirm_exit(context) => ControlFlow::Continue(None),
+            Err(_) => ControlFlow::Break(()),
         }
     }
 
-    if let Some(history_path) = history_path {
-        rl.save_history(&history_path).map_err(Error::other)?;
+    /// Update the shell after running a line
+    ///
+    /// Breaks with the exit code if the shell should exit.
+    fn after_line(
+        &mut self,
+        rl: &mut Editor<ShellUi, FileHistory>,
+        context: &mut X,
+        line: &str,
+        action: ShellAction,
+    ) -> Result<ControlFlow<u8>> {
+        self.last_command.set(line);
+        record_history(context, line);
+        after_command(rl, context, &mut self.parser, action)
     }
 
-    Ok(())
+    fn finish(
+        self,
+        rl: &mut Editor<ShellUi, FileHistory>,
+        context: &mut X,
+        exit_code: u8,
+    ) -> Result<u8> {
+        save_history(rl.history_mut(), context)?;
+        // The state of the current session is saved last, and is the one
+        // loaded at the next start if the sessions share their state path
+        for mut inactive in self.parser.sessions.into_inactive() {
+            save_state(&inactive)?;
+            inactive.on_exit()?;
+        }
+        save_state(context)?;
+        context.on_exit()?;
+        Ok(exit_code)
+    }
+}
+
+/// Run the shell until it exits
+///
+/// Returns the exit code passed to the `exit` builtin, like `exit 3`.
+pub fn launch_shell<T>(context: &mut T::Context) -> Result<u8>
+where
+    T: ShellParser,
+{
+    let (mut shell, mut rl) = ShellLoop::<T::Commands, _>::start(context)?;
+    let exit_code = loop {
+        let prompt = shell.prompt();
+        let line = match shell.accept(context, readline(&mut rl, &prompt)) {
+            ControlFlow::Continue(Some(line)) => line,
+            ControlFlow::Continue(None) => continue,
+            ControlFlow::Break(()) => break 0,
+        };
+        let action = run_line::<T>(context, &mut shell.parser, &line)?;
+        if let ControlFlow::Break(code) = shell.after_line(&mut rl, context, &line, action)? {
+            break code;
+        }
+    };
+    shell.finish(&mut rl, context, exit_code)
+}
+
+/// Async twin of `launch_shell`
+///
+/// Lines are read in a blocking task, and commands are
+/// cancelled with Ctrl+C.
+#[cfg(feature = "async")]
+pub async fn launch_shell_async<T>(context: &mut T::Context) -> Result<u8>
+where
+    T: AsyncShellParser,
+{
+    let (mut shell, mut rl) = ShellLoop::<T::Commands, _>::start(context)?;
+    let exit_code = loop {
+        let prompt = shell.prompt();
+        let (editor, readline) = task::spawn_blocking(move || {
+            let readline = readline(&mut rl, &prompt);
+            (rl, readline)
+        })
+        .await
+        .map_err(Error::other)?;
+        rl = editor;
+
+        let line = match shell.accept(context, readline) {
+            ControlFlow::Continue(Some(line)) => line,
+            ControlFlow::Continue(None) => continue,
+            ControlFlow::Break(()) => break 0,
+        };
+        let action = run_line_async::<T>(context, &mut shell.parser, &line).await?;
+        if let ControlFlow::Break(code) = shell.after_line(&mut rl, context, &line, action)? {
+            break code;
+        }
+    };
+    shell.finish(&mut rl, context, exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::start_capture;
+    use crate::input::with_scripted_input;
+    use crate::testing::test_dir;
+    use serde_json::{json, Value};
+    use std::cell::Cell;
+    use std::path::PathBuf;
+
+    thread_local! {
+        /// Number of contexts that exited, including the inactive sessions
+        static EXITS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Default)]
+    struct TestContext {
+        dir: PathBuf,
+        state: Option<Value>,
+        /// Calls of the lifecycle hooks
+        events: Vec<&'static str>,
+        commands_changed: bool,
+        registry: CommandRegistry<TestContext>,
+        jobs: usize,
+    }
+
+    impl TestContext {
+        fn in_dir(dir: PathBuf) -> Self {
+            TestContext {
+                dir,
+                ..TestContext::default()
+            }
+        }
+    }
+
+    impl Context for TestContext {
+        fn new() -> Result<Self> {
+            Ok(TestContext::default())
+        }
+
+        fn history_path(&self) -> Option<PathBuf> {
+            Some(self.dir.join("history"))
+        }
+
+        fn save_state(&self) -> Option<Value> {
+            self.state.clone()
+        }
+
+        fn load_state(&mut self, state: Value) -> Result<()> {
+            self.state = Some(state);
+            Ok(())
+        }
+
+        fn commands_changed(&mut self) -> bool {
+            mem::take(&mut self.commands_changed)
+        }
+
+        fn registry(&self) -> Option<&CommandRegistry<Self>> {
+            Some(&self.registry)
+        }
+
+        fn running_jobs(&self) -> usize {
+            self.jobs
+        }
+
+        fn on_start(&mut self) -> Result<()> {
+            self.events.push("start");
+            Ok(())
+        }
+
+        fn on_exit(&mut self) -> Result<()> {
+            EXITS.set(EXITS.get() + 1);
+            self.events.push("exit");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_save_history() {
+        let context = TestContext::in_dir(test_dir("save_history"));
+        let history_path = context.history_path().unwrap();
+
+        let mut first = FileHistory::new();
+        first.add("list").unwrap();
+        save_history(&mut first, &context).unwrap();
+
+        // Two shells running at the same time
+        let mut first = FileHistory::new();
+        let mut second = FileHistory::new();
+        first.load(&history_path).unwrap();
+        second.load(&history_path).unwrap();
+        first.add("create").unwrap();
+        second.add("delete").unwrap();
+        second.add("delete").unwrap();
+        save_history(&mut second, &context).unwrap();
+        save_history(&mut first, &context).unwrap();
+
+        let mut history = FileHistory::new();
+        history.load(&history_path).unwrap();
+        let entries = history.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(entries, vec!["list", "delete", "create"]);
+    }
+
+    #[test]
+    fn test_state() {
+        let dir = test_dir("state");
+        let mut context = TestContext::in_dir(dir.clone());
+        let state_path = context.state_path().unwrap();
+        assert!(state_path.ends_with("history.state"));
+
+        load_state(&mut context).unwrap();
+        assert_eq!(context.state, None);
+
+        context.state = Some(json!({"namespace": "test"}));
+        save_state(&context).unwrap();
+
+        let mut context = TestContext::in_dir(dir);
+        load_state(&mut context).unwrap();
+        assert_eq!(context.state, Some(json!({"namespace": "test"})));
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        let dir = test_dir("lifecycle_hooks");
+        fs::write(dir.join("history"), "").unwrap();
+        fs::write(dir.join("history.state"), "{\"count\": 1}").unwrap();
+        let mut context = TestContext::in_dir(dir.clone());
+        context.state = Some(json!({"count": 2}));
+
+        // The state is loaded before the shell starts
+        let (shell, mut rl) = ShellLoop::<NoCommands, _>::start(&mut context).unwrap();
+        assert_eq!(context.state, Some(json!({"count": 1})));
+        assert_eq!(context.events, ["start"]);
+
+        context.state = Some(json!({"count": 3}));
+        assert_eq!(shell.finish(&mut rl, &mut context, 2).unwrap(), 2);
+        assert_eq!(context.events, ["start", "exit"]);
+        let state = fs::read_to_string(dir.join("history.state")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&state).unwrap(),
+            json!({"count": 3})
+        );
+    }
+
+    #[test]
+    fn test_session_exit_hooks() {
+        let dir = test_dir("session_exit_hooks");
+        fs::write(dir.join("history"), "").unwrap();
+        let mut context = TestContext::in_dir(dir.clone());
+        let (mut shell, mut rl) = ShellLoop::<NoCommands, _>::start(&mut context).unwrap();
+        context.state = Some(json!({"session": "default"}));
+
+        // The default session is inactive when the shell exits
+        let parsed = shell
+            .parser
+            .parse(&mut context, "session new other")
+            .unwrap();
+        assert!(parsed.is_none());
+        assert!(context.state.is_none());
+        EXITS.set(0);
+        shell.finish(&mut rl, &mut context, 0).unwrap();
+        assert_eq!(EXITS.get(), 2);
+        let state = fs::read_to_string(dir.join("history.state")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&state).unwrap(),
+            json!({"session": "default"})
+        );
+    }
+
+    #[test]
+    fn test_exit_at_end_of_input() {
+        let dir = test_dir("exit_at_end_of_input");
+        fs::write(dir.join("history"), "").unwrap();
+        let mut context = TestContext::in_dir(dir);
+        context.jobs = 2;
+        let (shell, _rl) = ShellLoop::<NoCommands, _>::start(&mut context).unwrap();
+
+        // Without a terminal, the shell exits after warning about the jobs
+        start_capture();
+        let flow = with_scripted_input(&[], || shell.accept(&context, Err(ReadlineError::Eof)));
+        let capture = stop_capture();
+        assert_eq!(flow, ControlFlow::Break(()));
+        assert!(capture.stderr.contains("2 jobs still running"));
+    }
+
+    #[test]
+    fn test_command_cache() {
+        let mut context = TestContext::default();
+        let mut parser = LineParser::<NoCommands, _>::new(&context);
+        assert!(parser.command().find_subcommand("add").is_none());
+        assert!(!parser.update(&mut context));
+
+        // The command is only rebuilt once per change
+        context
+            .registry
+            .register(Command::new("add"), |_, _| Ok(()));
+        assert!(parser.update(&mut context));
+        assert!(!parser.update(&mut context));
+        assert!(parser.command().find_subcommand("add").is_some());
+        let parsed = parser.parse(&mut context, "add").unwrap();
+        assert!(matches!(parsed, Some((name, ParsedLine::Registered(_, _))) if name == "add"));
+
+        context.commands_changed = true;
+        assert!(parser.update(&mut context));
+        assert!(!parser.update(&mut context));
+    }
+
+    #[test]
+    fn test_parse_variables() {
+        let mut context = TestContext::default();
+        context
+            .registry
+            .register(Command::new("greet").arg(clap::Arg::new("name")), |_, _| {
+                Ok(())
+            });
+        let mut parser = LineParser::<NoCommands, _>::new(&context);
+        parser.update(&mut context);
+
+        // Values are not parsed again as shell syntax
+        parser.variables.set("name", "O'Brien #1");
+        let parsed = parser.parse(&mut context, "greet $name").unwrap();
+        let Some((_, ParsedLine::Registered(_, matches))) = parsed else {
+            panic!("expected a registered command");
+        };
+        let name = matches.get_one::<String>("name").map(String::as_str);
+        assert_eq!(name, Some("O'Brien #1"));
+
+        // Unbalanced quotes are reported without exiting the shell
+        assert!(matches!(parser.parse(&mut context, "greet 'O"), Ok(None)));
+    }
+
+    #[test]
+    fn test_active_config() {
+        use crate::format::{output_format, show_headers};
+
+        assert_eq!(output_format(), OutputFormat::Human);
+        {
+            let _config = ActiveConfig::install(ShellConfig {
+                output_format: OutputFormat::Json,
+                show_headers: false,
+                ..ShellConfig::default()
+            });
+            assert_eq!(output_format(), OutputFormat::Json);
+            assert!(!show_headers());
+            {
+                let _config = ActiveConfig::install(ShellConfig::default());
+                assert_eq!(output_format(), OutputFormat::Human);
+            }
+            assert_eq!(output_format(), OutputFormat::Json);
+        }
+        assert_eq!(output_format(), OutputFormat::Human);
+        assert!(show_headers());
+    }
+
+    #[test]
+    fn test_subcommand_path() {
+        let command = Command::new("test")
+            .subcommand(Command::new("network").subcommand(Command::new("create")));
+        let path = |line: &str| {
+            let args = shell_words::split(line).unwrap();
+            subcommand_path(&command, &args).join(" ")
+        };
+        assert_eq!(path("network create --unknown"), "network create");
+        assert_eq!(path("network other"), "network");
+        assert_eq!(path("--unknown"), "");
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let command = Command::new("test")
+            .subcommand(Command::new("list"))
+            .subcommand(Command::new("last"))
+            .subcommand(Command::new("network").subcommand(Command::new("create")));
+        let unknown = |line: &str| unknown_command(&command, &shell_words::split(line).unwrap());
+
+        assert_eq!(
+            unknown("lst"),
+            Some("Unknown command \"lst\", did you mean \"last\", \"list\"?".to_string())
+        );
+        assert_eq!(
+            unknown("network craete"),
+            Some("Unknown command \"craete\", did you mean \"create\"?".to_string())
+        );
+        assert_eq!(
+            unknown("delete"),
+            Some("Unknown command \"delete\"".to_string())
+        );
+        assert_eq!(unknown("list"), None);
+
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_expand_prefixes() {
+        let command = Command::new("test")
+            .subcommand(
+                Command::new("network")
+                    .subcommand(Command::new("create"))
+                    .subcommand(Command::new("credentials")),
+            )
+            .subcommand(Command::new("exit"));
+        let expand = |line: &str| {
+            let mut args = shell_words::split(line).unwrap();
+            expand_prefixes(&command, &mut args).map(|()| args.join(" "))
+        };
+
+        assert_eq!(expand("net crea foo"), Ok("network create foo".to_string()));
+        assert_eq!(
+            expand("network cred"),
+            Ok("network credentials".to_string())
+        );
+        assert_eq!(expand("ex"), Ok("exit".to_string()));
+        assert_eq!(expand("he net"), Ok("help net".to_string()));
+        assert_eq!(expand("unknown"), Ok("unknown".to_string()));
+        assert_eq!(
+            expand("net cr"),
+            Err("Ambiguous command \"cr\", could be create, credentials".to_string())
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[derive(Parser)]
+    struct AsyncCli {
+        #[command(subcommand)]
+        command: Option<AsyncCommands>,
+    }
+
+    #[cfg(feature = "async")]
+    #[derive(Subcommand)]
+    enum AsyncCommands {
+        Greet { name: String },
+        Fail,
+    }
+
+    #[cfg(feature = "async")]
+    impl AsyncShellParser for AsyncCli {
+        type Context = TestContext;
+        type Commands = AsyncCommands;
+
+        fn try_get_command(self) -> Option<Self::Commands> {
+            self.command
+        }
+
+        async fn run_command(
+            _context: &mut TestContext,
+            command: &AsyncCommands,
+        ) -> ShellUiResult<()> {
+            task::yield_now().await;
+            match command {
+                AsyncCommands::Greet { name } => {
+                    print_stdout(format!("Hello {name}"));
+                    Ok(())
+                }
+                AsyncCommands::Fail => Err(ShellUiError::with_code(3, Error::other("Failed"))),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_run_line_async() {
+        let _config = ActiveConfig::install(ShellConfig::default());
+        let mut context = TestContext::in_dir(test_dir("run_line_async"));
+        let mut parser = LineParser::<AsyncCommands, _>::new(&context);
+
+        start_capture();
+        for line in [
+            "greet world",
+            "name=$(greet variable)",
+            "greet \"$name\"",
+            "fail",
+        ] {
+            let action = run_line_async::<AsyncCli>(&mut context, &mut parser, line).await;
+            assert!(matches!(action, Ok(ShellAction::None)));
+        }
+        let action = run_line_async::<AsyncCli>(&mut context, &mut parser, "exit 2").await;
+        assert!(matches!(action, Ok(ShellAction::Exit(2))));
+        let capture = stop_capture();
+        assert_eq!(capture.stdout, "Hello world\nHello Hello variable\n");
+        assert!(capture.stderr.contains("Failed"));
+    }
 }
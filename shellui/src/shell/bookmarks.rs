@@ -0,0 +1,105 @@
+use crate::errors::{ShellUiResult, WithContext};
+use crate::format::{AsFormatted, MapTable, Message, PrintTable};
+use crate::i18n::{tr, tr_args};
+use crate::Context;
+use clap::Subcommand;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Subcommand)]
+pub(super) enum BookmarkCommand {
+    /// Bookmark the previous command line
+    Add { name: String },
+    /// Run a bookmarked command line
+    Run { name: String },
+    /// List the bookmarks
+    List,
+}
+
+impl BookmarkCommand {
+    /// Run a bookmark command
+    ///
+    /// Returns the command line to run, for `bookmark run`.
+    pub(super) fn run<X>(
+        &self,
+        context: &X,
+        previous: Option<&str>,
+    ) -> ShellUiResult<Option<String>>
+    where
+        X: Context,
+    {
+        let path = bookmarks_path(context).with_context(tr(
+            "shellui-bookmarks-missing",
+            "Bookmarks are not available",
+        ))?;
+        let mut bookmarks = load(&path)?;
+        match self {
+            BookmarkCommand::Add { name } => {
+                let line = previous.with_context(tr(
+                    "shellui-bookmark-no-previous",
+                    "No previous command to bookmark",
+                ))?;
+                bookmarks.insert(name.clone(), line.to_string());
+                save(&path, &bookmarks)?;
+                Ok(None)
+            }
+            BookmarkCommand::Run { name } => {
+                let line = bookmarks.remove(name).with_context(tr_args(
+                    "shellui-bookmark-unknown",
+                    "Unknown bookmark { $name }",
+                    &[("name", name)],
+                ))?;
+                Ok(Some(line))
+            }
+            BookmarkCommand::List => {
+                if bookmarks.is_empty() {
+                    Message::info(tr("shellui-bookmarks-empty", "No bookmarks")).print_formatted();
+                } else {
+                    bookmarks.key_values().print_table_default();
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Bookmarks are stored next to the history file, like `.mycli_history.bookmarks`
+fn bookmarks_path<X>(context: &X) -> Option<PathBuf>
+where
+    X: Context,
+{
+    let mut path = context.history_path()?.into_os_string();
+    path.push(".bookmarks");
+    Some(PathBuf::from(path))
+}
+
+fn load(path: &Path) -> Result<BTreeMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(Error::other),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(error) => Err(error),
+    }
+}
+
+fn save(path: &Path, bookmarks: &BTreeMap<String, String>) -> Result<()> {
+    let content = serde_json::to_string_pretty(bookmarks).map_err(Error::other)?;
+    fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_dir;
+
+    #[test]
+    fn test_bookmarks() {
+        let path = test_dir("bookmarks").join("history.bookmarks");
+
+        assert!(load(&path).unwrap().is_empty());
+        let bookmarks = BTreeMap::from([("deploy".to_string(), "deploy --all".to_string())]);
+        save(&path, &bookmarks).unwrap();
+        assert_eq!(load(&path).unwrap(), bookmarks);
+    }
+}
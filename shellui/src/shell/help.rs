@@ -0,0 +1,134 @@
+use super::BUILTIN_COMMANDS;
+use crate::format::theme;
+use crate::i18n::tr;
+use crate::Context;
+use clap::Command;
+use colored::Colorize;
+use std::collections::BTreeMap;
+
+/// Format the help of the shell
+///
+/// Commands are grouped by category, with the commands without category
+/// first, and the builtins last. Hidden commands are only listed in expert mode.
+pub(super) fn format_help<X>(context: &X, command: &Command) -> Vec<String>
+where
+    X: Context,
+{
+    let mut commands = Vec::new();
+    let mut categories = BTreeMap::<String, Vec<_>>::new();
+    let mut builtins = Vec::new();
+    let expert = context.shell_config().expert;
+    for subcommand in command
+        .get_subcommands()
+        .filter(|c| expert || !c.is_hide_set())
+    {
+        let name = subcommand.get_name();
+        let about = subcommand
+            .get_about()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let category = context.command_category(name).or_else(|| {
+            let registry = context.registry()?;
+            registry.category(name).map(ToString::to_string)
+        });
+        match category {
+            _ if BUILTIN_COMMANDS.contains(&name) => builtins.push((name, about)),
+            Some(category) => categories.entry(category).or_default().push((name, about)),
+            None => commands.push((name, about)),
+        }
+    }
+    builtins.push(("help", tr("shellui-help-about", "Print this help")));
+    builtins.sort();
+
+    let groups = [(tr("shellui-help-commands", "Commands"), commands)]
+        .into_iter()
+        .chain(categories)
+        .chain([(tr("shellui-help-builtins", "Shell commands"), builtins)])
+        .filter(|(_, commands)| !commands.is_empty())
+        .collect::<Vec<_>>();
+
+    let width = groups
+        .iter()
+        .flat_map(|(_, commands)| commands.iter().map(|(name, _)| name.len()))
+        .max()
+        .unwrap_or_default();
+    let header_color = theme().info;
+    let mut lines = Vec::new();
+    for (i, (category, commands)) in groups.into_iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        lines.push(
+            format!("{category}:")
+                .color(header_color)
+                .bold()
+                .to_string(),
+        );
+        for (name, about) in commands {
+            let padding = " ".repeat(width - name.len());
+            lines.push(format!("  {}{padding}   {about}", name.bold()));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::CommandRegistry;
+    use std::env;
+    use std::io::Result;
+    use std::path::PathBuf;
+
+    struct TestContext(CommandRegistry<TestContext>);
+
+    impl Context for TestContext {
+        fn new() -> Result<Self> {
+            Ok(TestContext(CommandRegistry::new()))
+        }
+
+        fn history_path(&self) -> Option<PathBuf> {
+            None
+        }
+
+        fn registry(&self) -> Option<&CommandRegistry<Self>> {
+            Some(&self.0)
+        }
+
+        fn command_category(&self, command: &str) -> Option<String> {
+            (command == "create").then(|| "Network".to_string())
+        }
+    }
+
+    #[test]
+    fn test_format_help() {
+        env::set_var("NO_COLOR", "1");
+
+        let mut context = TestContext::new().unwrap();
+        context.0.register_in(
+            "Network",
+            Command::new("delete").about("Delete a network"),
+            |_, _| Ok(()),
+        );
+        let command = Command::new("test")
+            .subcommand(Command::new("list").about("List networks"))
+            .subcommand(Command::new("create").about("Create a network"))
+            .subcommand(Command::new("hidden").hide(true))
+            .subcommand(Command::new("exit").about("Exit the shell"));
+        let command = crate::registry::with_registered(&context, command);
+
+        let expected = vec![
+            "Commands:",
+            "  list     List networks",
+            "",
+            "Network:",
+            "  create   Create a network",
+            "  delete   Delete a network",
+            "",
+            "Shell commands:",
+            "  exit     Exit the shell",
+            "  help     Print this help",
+        ];
+        assert_eq!(format_help(&context, &command), expected);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::errors::{ShellUiResult, WithContext};
+use crate::format::{AsFormatted, Message, ObjectFormatter, PrintTable};
+use crate::i18n::tr;
+use crate::Context;
+use rustyline::history::{FileHistory, History};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Command line run at a given time
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct HistoryEntry {
+    /// Seconds since the Unix epoch
+    time: u64,
+    line: String,
+}
+
+impl ObjectFormatter for HistoryEntry {
+    type Header = &'static str;
+    type Mode = ();
+    type Output<'a> = Cow<'a, str>;
+
+    fn headers(_mode: Option<()>) -> Vec<Self::Header> {
+        vec!["time", "command"]
+    }
+
+    fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> Cow<'_, str> {
+        match *header {
+            "time" => Cow::Owned(format_time(self.time)),
+            _ => Cow::Borrowed(&self.line),
+        }
+    }
+}
+
+/// Print the last `count` entries of the history, with their timestamps
+pub(super) fn print_history<X>(context: &X, count: Option<usize>) -> ShellUiResult<()>
+where
+    X: Context,
+{
+    let path = timestamps_path(context)
+        .with_context(tr("shellui-history-missing", "History is not available"))?;
+    let entries = load(&path)?;
+    let skipped = count.map_or(0, |count| entries.len().saturating_sub(count));
+    if entries.is_empty() {
+        Message::info(tr("shellui-history-empty", "No history")).print_formatted();
+    } else {
+        entries[skipped..].print_table_default();
+    }
+    Ok(())
+}
+
+/// Record the time a line is run
+///
+/// Failing to record the time only leaves the entry without timestamp.
+pub(super) fn record<X>(context: &X, line: &str)
+where
+    X: Context,
+{
+    if let Some(path) = timestamps_path(context).filter(|_| !line.trim().is_empty()) {
+        let _ = append(&path, now(), line);
+    }
+}
+
+/// Remove the entries older than `days` from the history
+///
+/// Entries recorded without timestamp are kept.
+pub(super) fn expire<X>(history: &mut FileHistory, context: &X, days: u64) -> Result<()>
+where
+    X: Context,
+{
+    let (Some(history_path), Some(path)) = (context.history_path(), timestamps_path(context))
+    else {
+        return Ok(());
+    };
+    let cutoff = now().saturating_sub(days * SECONDS_PER_DAY);
+    let (kept, expired) = load(&path)?
+        .into_iter()
+        .partition::<Vec<_>, _>(|entry| entry.time >= cutoff);
+    if expired.is_empty() {
+        return Ok(());
+    }
+    save(&path, &kept)?;
+
+    // Lines run again recently are kept
+    let recent = kept
+        .iter()
+        .map(|entry| entry.line.as_str())
+        .collect::<HashSet<_>>();
+    let expired = expired
+        .iter()
+        .map(|entry| entry.line.as_str())
+        .filter(|line| !recent.contains(line))
+        .collect::<HashSet<_>>();
+    let lines = history
+        .iter()
+        .filter(|line| !expired.contains(line.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    history.clear().map_err(Error::other)?;
+    for line in lines {
+        history.add_owned(line).map_err(Error::other)?;
+    }
+    history.save(&history_path).map_err(Error::other)
+}
+
+/// Timestamps are stored next to the history file, like `.mycli_history.timestamps`
+fn timestamps_path<X>(context: &X) -> Option<PathBuf>
+where
+    X: Context,
+{
+    let mut path = context.history_path()?.into_os_string();
+    path.push(".timestamps");
+    Some(PathBuf::from(path))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Entries are stored as JSON lines, to be appended by concurrent shells
+fn append(path: &Path, time: u64, line: &str) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", json!({"time": time, "line": line}))
+}
+
+fn load(path: &Path) -> Result<Vec<HistoryEntry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    // Invalid lines, like partially written ones, are skipped
+    let entries = content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|value| {
+            Some(HistoryEntry {
+                time: value.get("time")?.as_u64()?,
+                line: value.get("line")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    Ok(entries)
+}
+
+fn save(path: &Path, entries: &[HistoryEntry]) -> Result<()> {
+    let content = entries
+        .iter()
+        .map(|entry| format!("{}\n", json!({"time": entry.time, "line": entry.line})))
+        .collect::<String>();
+    fs::write(path, content)
+}
+
+/// Format a time as UTC, like `2024-05-01 08:30:00`
+fn format_time(time: u64) -> String {
+    // Civil date from the number of days since the epoch, by Howard Hinnant
+    let days = time / SECONDS_PER_DAY;
+    let seconds = time % SECONDS_PER_DAY;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_dir;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(format_time(1_714_552_200), "2024-05-01 08:30:00");
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let path = test_dir("history").join("history.timestamps");
+
+        assert!(load(&path).unwrap().is_empty());
+        append(&path, 10, "list").unwrap();
+        append(&path, 20, "deploy \"my app\"").unwrap();
+        let entries = load(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[1].format_value(None, &"command"),
+            "deploy \"my app\""
+        );
+
+        save(&path, &entries[1..]).unwrap();
+        assert_eq!(load(&path).unwrap(), entries[1..]);
+    }
+}
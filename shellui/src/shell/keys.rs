@@ -0,0 +1,155 @@
+use super::ui::ShellUi;
+use rustyline::history::FileHistory;
+use rustyline::{
+    Cmd, ConditionalEventHandler, Editor, Event, EventContext, EventHandler, KeyEvent, Modifiers,
+    Movement, RepeatCount,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Key combination, like `Ctrl+O`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KeyBinding {
+    key: char,
+    modifiers: Modifiers,
+}
+
+impl KeyBinding {
+    /// Key pressed with Ctrl
+    pub fn ctrl(key: char) -> Self {
+        KeyBinding {
+            key,
+            modifiers: Modifiers::CTRL,
+        }
+    }
+
+    /// Key pressed with Alt, or Meta
+    pub fn alt(key: char) -> Self {
+        KeyBinding {
+            key,
+            modifiers: Modifiers::ALT,
+        }
+    }
+
+    fn event(&self) -> KeyEvent {
+        KeyEvent::normalize(KeyEvent::new(self.key, self.modifiers))
+    }
+}
+
+/// Action bound to a key
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyAction {
+    /// Insert the last argument of the previous command
+    InsertLastArgument,
+    /// Replace the line by the previous command, to edit it
+    EditPrevious,
+    /// Run the previous command again, from an empty line
+    RunPrevious,
+}
+
+/// Previous command, shared with the key handlers
+#[derive(Debug, Default)]
+pub(super) struct LastCommand {
+    line: Mutex<Option<String>>,
+    run_again: AtomicBool,
+}
+
+impl LastCommand {
+    pub(super) fn set(&self, line: &str) {
+        if !line.trim().is_empty() {
+            *self.line.lock().unwrap() = Some(line.to_string());
+        }
+    }
+
+    /// Line to run, that is the previous command if `RunPrevious` was used
+    pub(super) fn resolve(&self, line: String) -> String {
+        if self.run_again.swap(false, Ordering::Relaxed) && line.trim().is_empty() {
+            self.line.lock().unwrap().clone().unwrap_or(line)
+        } else {
+            line
+        }
+    }
+
+    fn last_argument(&self) -> Option<String> {
+        let line = self.line.lock().unwrap();
+        let argument = shell_words::split(line.as_deref()?).ok()?.pop()?;
+        Some(shell_words::quote(&argument).into_owned())
+    }
+}
+
+struct KeyHandler {
+    action: KeyAction,
+    last_command: Arc<LastCommand>,
+}
+
+impl ConditionalEventHandler for KeyHandler {
+    // Keys keep their default behavior when there is no previous command
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, context: &EventContext) -> Option<Cmd> {
+        match self.action {
+            KeyAction::InsertLastArgument => {
+                let argument = self.last_command.last_argument()?;
+                Some(Cmd::Insert(1, argument))
+            }
+            KeyAction::EditPrevious => {
+                let line = self.last_command.line.lock().unwrap().clone()?;
+                Some(Cmd::Replace(Movement::WholeBuffer, Some(line)))
+            }
+            KeyAction::RunPrevious if context.line().trim().is_empty() => {
+                self.last_command.line.lock().unwrap().as_ref()?;
+                self.last_command.run_again.store(true, Ordering::Relaxed);
+                Some(Cmd::AcceptLine)
+            }
+            KeyAction::RunPrevious => None,
+        }
+    }
+}
+
+/// Bind the keys of the shell configuration
+pub(super) fn bind_keys(
+    rl: &mut Editor<ShellUi, FileHistory>,
+    bindings: &[(KeyBinding, KeyAction)],
+    last_command: &Arc<LastCommand>,
+) {
+    for (binding, action) in bindings {
+        let handler = KeyHandler {
+            action: *action,
+            last_command: last_command.clone(),
+        };
+        rl.bind_sequence(
+            binding.event(),
+            EventHandler::Conditional(Box::new(handler)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::KeyCode;
+
+    #[test]
+    fn test_last_command() {
+        let last_command = LastCommand::default();
+        assert_eq!(last_command.last_argument(), None);
+        last_command.set("deploy 'my app'");
+        last_command.set("  ");
+        assert_eq!(last_command.last_argument(), Some("'my app'".to_string()));
+
+        assert_eq!(last_command.resolve(String::new()), "");
+        last_command.run_again.store(true, Ordering::Relaxed);
+        assert_eq!(last_command.resolve(String::new()), "deploy 'my app'");
+        assert_eq!(last_command.resolve(String::new()), "");
+    }
+
+    #[test]
+    fn test_key_binding() {
+        assert_eq!(
+            KeyBinding::ctrl('o').event(),
+            KeyEvent(KeyCode::Char('O'), Modifiers::CTRL)
+        );
+        assert_eq!(
+            KeyBinding::alt('.').event(),
+            KeyEvent(KeyCode::Char('.'), Modifiers::ALT)
+        );
+    }
+}
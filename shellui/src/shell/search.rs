@@ -0,0 +1,108 @@
+use crate::format::{Message, ObjectFormatter};
+use clap::Command;
+
+/// Command matching a search
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct SearchResult {
+    path: String,
+    about: String,
+}
+
+impl ObjectFormatter for SearchResult {
+    type Header = &'static str;
+    type Mode = ();
+    type Output<'a> = Message;
+
+    fn headers(_mode: Option<()>) -> Vec<Self::Header> {
+        vec!["command", "description"]
+    }
+
+    fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> Message {
+        match *header {
+            "command" => Message::info(&self.path),
+            _ => Message::new(&self.about),
+        }
+    }
+}
+
+/// Search commands whose name or description contain a keyword
+///
+/// The search is case-insensitive, and covers all the subcommands.
+pub(super) fn search(command: &Command, keyword: &str) -> Vec<SearchResult> {
+    let keyword = keyword.to_lowercase();
+    let mut results = Vec::new();
+    search_subcommands(command, &[], &keyword, &mut results);
+    results
+}
+
+fn search_subcommands(
+    command: &Command,
+    path: &[&str],
+    keyword: &str,
+    results: &mut Vec<SearchResult>,
+) {
+    for subcommand in command.get_subcommands().filter(|c| !c.is_hide_set()) {
+        let mut path = path.to_vec();
+        path.push(subcommand.get_name());
+
+        let about = subcommand
+            .get_about()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let long_about = subcommand.get_long_about().map(ToString::to_string);
+        let matches = [
+            Some(subcommand.get_name()),
+            Some(about.as_str()),
+            long_about.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|text| text.to_lowercase().contains(keyword));
+        if matches {
+            results.push(SearchResult {
+                path: path.join(" "),
+                about,
+            });
+        }
+        search_subcommands(subcommand, &path, keyword, results);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let command = Command::new("test")
+            .subcommand(
+                Command::new("network")
+                    .about("Manage networks")
+                    .subcommand(Command::new("create").about("Create a network"))
+                    .subcommand(
+                        Command::new("delete")
+                            .about("Delete something")
+                            .long_about("Delete a Network, and its routes"),
+                    ),
+            )
+            .subcommand(Command::new("volume").about("Manage volumes"))
+            .subcommand(Command::new("networks-hidden").hide(true));
+
+        let paths = |keyword: &str| {
+            search(&command, keyword)
+                .into_iter()
+                .map(|result| result.path)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths("NETWORK"),
+            vec!["network", "network create", "network delete"]
+        );
+        assert_eq!(paths("volume"), vec!["volume"]);
+        assert!(paths("unknown").is_empty());
+        assert_eq!(
+            search(&command, "volume")[0].format_value(None, &"description"),
+            Message::new("Manage volumes")
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::errors::{ShellUiResult, WithContext};
+use crate::format::print_stdout;
+use crate::i18n::tr_args;
+use crate::Context;
+use clap::Subcommand;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Error;
+use std::mem;
+
+const DEFAULT_SESSION: &str = "default";
+
+#[derive(Clone, Debug, Subcommand)]
+pub(super) enum SessionCommand {
+    /// Create a session, and switch to it
+    New { name: String },
+    /// List the sessions
+    List,
+    /// Switch to another session
+    Switch { name: String },
+}
+
+/// Named sessions of a shell
+///
+/// Each session has its own context. The context of the current session
+/// is the one used by the shell, and the others are kept here.
+pub(super) struct Sessions<X> {
+    current: String,
+    inactive: BTreeMap<String, X>,
+}
+
+impl<X> Sessions<X>
+where
+    X: Context,
+{
+    pub(super) fn new() -> Self {
+        Sessions {
+            current: DEFAULT_SESSION.to_string(),
+            inactive: BTreeMap::new(),
+        }
+    }
+
+    /// Run a session command, swapping the context when switching sessions
+    pub(super) fn run(&mut self, command: &SessionCommand, context: &mut X) -> ShellUiResult<()> {
+        match command {
+            SessionCommand::New { name } => {
+                if *name == self.current || self.inactive.contains_key(name) {
+                    let message = tr_args(
+                        "shellui-session-exists",
+                        "Session { $name } already exists",
+                        &[("name", name)],
+                    );
+                    return Err(Error::other(message).into());
+                }
+                let mut new_context = X::new()?;
+                new_context.on_start()?;
+                self.switch_to(name, new_context, context);
+            }
+            SessionCommand::List => {
+                let names = self.inactive.keys().chain([&self.current]);
+                for name in names.collect::<BTreeSet<_>>() {
+                    let marker = if *name == self.current { "*" } else { " " };
+                    print_stdout(format!("{marker} {name}"));
+                }
+            }
+            SessionCommand::Switch { name } => {
+                if *name == self.current {
+                    return Ok(());
+                }
+                let other = self.inactive.remove(name).with_context(tr_args(
+                    "shellui-session-unknown",
+                    "Unknown session { $name }",
+                    &[("name", name)],
+                ))?;
+                self.switch_to(name, other, context);
+            }
+        }
+        Ok(())
+    }
+
+    fn switch_to(&mut self, name: &str, mut other: X, context: &mut X) {
+        mem::swap(context, &mut other);
+        let previous = mem::replace(&mut self.current, name.to_string());
+        self.inactive.insert(previous, other);
+    }
+
+    /// Contexts of the sessions other than the current one
+    pub(super) fn into_inactive(self) -> impl Iterator<Item = X> {
+        self.inactive.into_values()
+    }
+
+    /// Prompt of the shell, showing the current session
+    pub(super) fn prompt(&self) -> String {
+        if self.inactive.is_empty() && self.current == DEFAULT_SESSION {
+            "> ".to_string()
+        } else {
+            format!("[{}] > ", self.current)
+        }
+    }
+}
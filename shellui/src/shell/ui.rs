@@ -1,4 +1,6 @@
-use clap::Command;
+use crate::format::{theme, AsFormatted, Color, Message, Theme};
+use crate::input::{suggest_paths, PathRequirement};
+use clap::{Arg, Command, ValueHint};
 use colored::Colorize;
 use rustyline::completion::Completer;
 use rustyline::highlight::Highlighter;
@@ -6,171 +8,507 @@ use rustyline::hint::{Hint, Hinter};
 use rustyline::validate::Validator;
 use rustyline::{Context, Helper, Result};
 use std::borrow::Cow;
-
-#[derive(Clone, Debug)]
-enum CommandItem {
-    Command(String),
-    Arg(String),
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::Bound;
+
+#[derive(Debug, Default)]
+struct CommandNode {
+    subcommands: BTreeMap<String, CommandNode>,
+    /// Positional args, like `name` or `name=default`
+    args: Vec<String>,
+    /// Flags, like `--timeout`, with their value, like `secs=30`
+    options: BTreeMap<String, Option<String>>,
+    /// Options taking a path
+    path_options: BTreeMap<String, PathRequirement>,
+    /// Positional args taking a path, by index
+    path_args: BTreeMap<usize, PathRequirement>,
+    /// Hidden commands can be run, but are not suggested
+    hidden: bool,
 }
 
-#[derive(Clone, Debug)]
-struct CommandLine(Vec<CommandItem>);
-
-impl CommandLine {
-    fn to_command_line_iter(&self) -> impl Iterator<Item = &str> {
-        self.0.iter().filter_map(|item| {
-            if let CommandItem::Command(command) = item {
-                Some(command.as_str())
-            } else {
-                None
+impl CommandNode {
+    fn from_command(command: &Command) -> Self {
+        let mut node = CommandNode {
+            hidden: command.is_hide_set(),
+            ..CommandNode::default()
+        };
+        for arg in command.get_arguments().filter(|arg| !arg.is_positional()) {
+            let value = arg.get_action().takes_values().then(|| placeholder(arg));
+            let longs = arg.get_long_and_visible_aliases().into_iter().flatten();
+            let shorts = arg.get_short_and_visible_aliases().into_iter().flatten();
+            let flags = longs
+                .map(|long| format!("--{long}"))
+                .chain(shorts.map(|short| format!("-{short}")));
+            for flag in flags {
+                if let Some(requirement) = path_requirement(arg) {
+                    node.path_options.insert(flag.clone(), requirement);
+                }
+                node.options.insert(flag, value.clone());
             }
-        })
+        }
+        if command.has_subcommands() {
+            node.subcommands
+                .insert("help".to_string(), CommandNode::default());
+            for subcommand in command.get_subcommands() {
+                let name = subcommand.get_name().to_string();
+                node.subcommands
+                    .insert(name, CommandNode::from_command(subcommand));
+            }
+        } else {
+            node.args = command.get_positionals().map(placeholder).collect();
+            node.path_args = command
+                .get_positionals()
+                .enumerate()
+                .filter_map(|(i, arg)| Some((i, path_requirement(arg)?)))
+                .collect();
+        }
+        node
+    }
+
+    fn find_suggestions<'a>(
+        &'a self,
+        prefix: &'a str,
+        expert: bool,
+    ) -> impl Iterator<Item = &'a str> {
+        self.subcommands
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(move |(name, _)| name.starts_with(prefix))
+            .filter(move |(_, node)| expert || !node.hidden)
+            .map(|(name, _)| name.as_str())
     }
 }
 
 pub struct ShellUi {
-    commands: Vec<CommandLine>,
+    root: CommandNode,
+    builtins: Vec<String>,
+    hint_min_chars: Option<usize>,
+    expert: bool,
 }
 
 impl ShellUi {
     pub fn new(command: Command) -> Self {
-        let commands = Self::parse_command_tree(&command);
-        ShellUi { commands }
+        ShellUi {
+            root: Self::build_tree(&command),
+            builtins: vec!["help".to_string()],
+            hint_min_chars: Some(0),
+            expert: false,
+        }
+    }
+
+    /// Configure hints
+    ///
+    /// Hints are only displayed once `min_chars` characters are typed,
+    /// and are disabled if `None` is passed.
+    pub fn with_hints(mut self, min_chars: Option<usize>) -> Self {
+        self.hint_min_chars = min_chars;
+        self
     }
 
-    fn parse_command_tree(command: &Command) -> Vec<CommandLine> {
-        let mut output = Vec::new();
-        Self::recursive_fill_command_tree(command, Vec::new(), &mut output);
-        output
+    /// Suggest hidden commands in completion and hints
+    pub fn with_expert(mut self, expert: bool) -> Self {
+        self.expert = expert;
+        self
     }
 
-    fn recursive_fill_command_tree(
-        parent: &Command,
-        prefix: Vec<CommandItem>,
-        output: &mut Vec<CommandLine>,
-    ) {
-        let mut help_line = prefix.clone();
-        help_line.push(CommandItem::Command("help".to_string()));
-        output.push(CommandLine(help_line));
+    pub fn with_builtins(mut self, builtins: &[&str]) -> Self {
+        self.builtins
+            .extend(builtins.iter().map(ToString::to_string));
+        self
+    }
 
-        for command in parent.get_subcommands() {
-            let mut line = prefix.clone();
-            line.push(CommandItem::Command(command.get_name().to_string()));
+    /// Rebuild the command tree
+    ///
+    /// To be called when the set of commands changes at runtime.
+    pub fn rebuild(&mut self, command: Command) {
+        self.root = Self::build_tree(&command);
+    }
 
-            output.push(CommandLine(line.clone()));
-            if command.has_subcommands() {
-                Self::recursive_fill_command_tree(command, line, output);
-            } else {
-                for arg in command.get_positionals() {
-                    line.push(CommandItem::Arg(arg.get_id().to_string()));
-                    output.push(CommandLine(line.clone()));
-                }
-            }
-        }
+    fn build_tree(command: &Command) -> CommandNode {
+        let mut root = CommandNode::from_command(command);
+        root.subcommands.entry("help".to_string()).or_default();
+        root
     }
 
-    fn find_matches<'a, S>(
-        &'a self,
-        args: &'a [S],
-        limit: usize,
-    ) -> impl Iterator<Item = &'a CommandLine>
+    /// Walk the command tree along the given args
+    ///
+    /// Returns the deepest matching command and the number of remaining args
+    /// that are positional arguments of that command. Returns `None` if
+    /// an arg does not match any subcommand.
+    fn resolve<S>(&self, args: &[S]) -> Option<(&CommandNode, usize)>
     where
         S: AsRef<str>,
     {
-        self.commands
-            .iter()
-            .filter(move |command| {
-                let command = command
-                    .to_command_line_iter()
-                    .take(limit)
-                    .collect::<Vec<_>>();
-                let args = args.iter().map(AsRef::as_ref).collect::<Vec<_>>();
-                command == args
-            })
-            .filter(move |command| command.0.len() == limit.saturating_add(1))
-    }
-
-    fn find_matching_suggestions<'a, S>(
-        &'a self,
-        args: &'a [S],
-        limit: usize,
-        last_arg: &'a str,
-    ) -> impl Iterator<Item = &'a str>
+        let mut node = &self.root;
+        for (i, arg) in args.iter().enumerate() {
+            match node.subcommands.get(arg.as_ref()) {
+                Some(child) => node = child,
+                None if node.subcommands.is_empty() => return Some((node, args.len() - i)),
+                None => return None,
+            }
+        }
+        Some((node, 0))
+    }
+
+    fn resolve_command<S>(&self, args: &[S]) -> Option<&CommandNode>
     where
         S: AsRef<str>,
     {
-        self.find_matches(args, limit)
-            .filter_map(move |command| command.to_command_line_iter().nth(limit))
-            .filter(move |command| command.starts_with(last_arg))
+        match self.resolve(args)? {
+            (node, 0) => Some(node),
+            _ => None,
+        }
     }
 
     fn solve_hint(&self, line: &str) -> Option<UiHint> {
-        let args = shell_words::split(line).ok()?;
-        let ends_with_whitespace = line.ends_with(char::is_whitespace);
+        let min_chars = self.hint_min_chars?;
+        if line.trim_start().chars().count() < min_chars {
+            return None;
+        }
 
-        if ends_with_whitespace {
+        let args = split_args(line);
+        if ends_with_separator(line) {
             // We want a suggestion of the next arg
             // but we will only suggest args
-            let limit = args.len();
-
-            let command = self.find_matches(&args, limit).next()?;
-
-            let item = command.0.get(limit)?;
-            if let CommandItem::Arg(name) = item {
-                Some(UiHint(format!("<{name}>"), None))
-            } else {
-                None
-            }
+            let (command, index) = self.resolve(&args)?;
+            let value = args
+                .last()
+                .and_then(|arg| command.options.get(arg.as_ref()).cloned().flatten());
+            let name = match value {
+                Some(value) => value,
+                None => command.args.get(index)?.clone(),
+            };
+            Some(UiHint(format!("<{name}>"), None))
         } else {
-            let limit = args.len().saturating_sub(1);
-            let limited_args = args.iter().take(limit).collect::<Vec<_>>();
-            let last_arg = args.last()?;
+            let (last_arg, args) = args.split_last()?;
+            if last_arg.starts_with("--") {
+                return self.solve_option_hint(last_arg, args);
+            }
             let command = self
-                .find_matching_suggestions(&limited_args, limit, last_arg.as_str())
+                .resolve_command(args)?
+                .find_suggestions(last_arg, self.expert)
                 .next()?;
 
-            let suffix = command.strip_prefix(last_arg)?;
+            let suffix = command.strip_prefix(last_arg.as_ref())?;
             Some(UiHint(suffix.to_string(), Some(suffix.to_string())))
         }
     }
 
+    /// Hint the name of an option, and its value
+    fn solve_option_hint<S>(&self, prefix: &str, args: &[S]) -> Option<UiHint>
+    where
+        S: AsRef<str>,
+    {
+        let (command, _) = self.resolve(args)?;
+        let (name, value) = command
+            .options
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .find(|(name, _)| name.starts_with("--"))
+            .filter(|(name, _)| name.starts_with(prefix))?;
+        let suffix = name.strip_prefix(prefix)?;
+        let display = match value {
+            Some(value) => format!("{suffix} <{value}>"),
+            None => suffix.to_string(),
+        };
+        Some(UiHint(display, Some(suffix.to_string())))
+    }
+
     fn solve_complete(&self, line: &str, pos: usize) -> Option<(usize, Vec<String>)> {
         let line = line.get(0..pos)?;
-        let args = shell_words::split(line).ok()?;
-        let ends_with_whitespace = line.ends_with(char::is_whitespace);
+        let args = split_args(line);
+        if ends_with_separator(line) || line.is_empty() {
+            let (args, is_help) = self.strip_help(&args);
+            if let Some(requirement) = self.expected_path(&args).filter(|_| !is_help) {
+                return Some((line.len(), complete_paths("", requirement)));
+            }
 
-        if ends_with_whitespace || line.is_empty() {
             // We want completion of the next arg
             // and we will only complete with commands
-            let limit = args.len();
-
             let completions = self
-                .find_matches(&args, limit)
-                .filter_map(|command| command.0.get(limit))
-                .filter_map(|command| match command {
-                    CommandItem::Command(name) => Some(name.clone()),
-                    CommandItem::Arg(_) => None,
-                })
+                .resolve_command(&args)?
+                .find_suggestions("", self.expert)
+                .map(ToString::to_string)
                 .collect();
 
-            Some((line.len(), completions))
+            Some((line.len(), self.sort_completions(completions)))
         } else {
-            let last_arg = args.last()?;
-            let index = line.rfind(last_arg)?;
+            let (last_arg, args) = args.split_last()?;
+            let (args, is_help) = self.strip_help(args);
+            // The completion replaces the last word, including its quotes
+            let word = tokens(line).last()?.0;
+            let index = line.len() - word.len();
+            if let Some(requirement) = self.expected_path(&args).filter(|_| !is_help) {
+                // Backslashes are separators on Windows, not escapes
+                let prefix = if cfg!(windows) && !word.contains(['\'', '"']) {
+                    word
+                } else {
+                    last_arg.as_ref()
+                };
+                return Some((index, complete_paths(prefix, requirement)));
+            }
 
-            let limit = args.len().saturating_sub(1);
-            let limited_args = args.iter().take(limit).collect::<Vec<_>>();
-            let last_arg = args.last()?;
             let completions = self
-                .find_matching_suggestions(&limited_args, limit, last_arg)
+                .resolve_command(&args)?
+                .find_suggestions(last_arg, self.expert)
                 .map(ToString::to_string)
                 .collect();
 
-            Some((index, completions))
+            Some((index, self.sort_completions(completions)))
+        }
+    }
+
+    /// Remove `help` from args, to complete the commands it documents
+    ///
+    /// `help network cr` and `network help cr` complete like `network cr`.
+    /// Returns whether `help` was removed.
+    fn strip_help<'a, S>(&self, args: &'a [S]) -> (Vec<&'a str>, bool)
+    where
+        S: AsRef<str>,
+    {
+        let mut node = &self.root;
+        for (i, arg) in args.iter().enumerate() {
+            let arg = arg.as_ref();
+            if arg == "help" && !node.subcommands.is_empty() {
+                let stripped = args[..i].iter().chain(&args[i + 1..]);
+                return (stripped.map(AsRef::as_ref).collect(), true);
+            }
+            match node.subcommands.get(arg) {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        (args.iter().map(AsRef::as_ref).collect(), false)
+    }
+
+    /// Requirement on the path expected after the given args, if any
+    fn expected_path<S>(&self, args: &[S]) -> Option<PathRequirement>
+    where
+        S: AsRef<str>,
+    {
+        let (node, remaining) = self.resolve(args)?;
+        let args = &args[args.len() - remaining..];
+        if let Some(requirement) = args
+            .last()
+            .and_then(|arg| node.path_options.get(arg.as_ref()))
+        {
+            return Some(*requirement);
+        }
+
+        // Positional args, without the flags and their values
+        let mut index = 0;
+        let mut expects_value = false;
+        for arg in args.iter().map(AsRef::as_ref) {
+            if mem::take(&mut expects_value) {
+                continue;
+            }
+            if arg.starts_with('-') {
+                expects_value = node.options.get(arg).is_some_and(Option::is_some);
+            } else {
+                index += 1;
+            }
+        }
+        node.path_args.get(&index).copied()
+    }
+
+    /// Classify the tokens of a line, for highlighting
+    ///
+    /// Commands are only recognized before the first positional argument.
+    fn classify<'l>(&self, line: &'l str) -> Vec<(&'l str, Option<TokenKind>)> {
+        let mut classified = Vec::new();
+        let mut node = &self.root;
+        let mut in_commands = true;
+        let mut expects_value = false;
+        for (token, is_word) in tokens(line) {
+            if !is_word {
+                classified.push((token, None));
+                continue;
+            }
+            if mem::take(&mut expects_value) {
+                classified.push((token, Some(TokenKind::FlagValue)));
+            } else if token.starts_with('-') {
+                match token.split_once('=') {
+                    Some((flag, value)) => {
+                        classified.push((flag, Some(TokenKind::Flag)));
+                        classified.push(("=", None));
+                        classified.push((value, Some(TokenKind::FlagValue)));
+                    }
+                    None => {
+                        expects_value = node.options.get(token).is_some_and(Option::is_some);
+                        classified.push((token, Some(TokenKind::Flag)));
+                    }
+                }
+            } else if let Some(child) = node.subcommands.get(token).filter(|_| in_commands) {
+                node = child;
+                classified.push((token, Some(TokenKind::Command)));
+            } else {
+                in_commands = false;
+                let kind = match token.chars().next() {
+                    Some('\'' | '"') => Some(TokenKind::String),
+                    Some('$') => Some(TokenKind::Variable),
+                    _ => None,
+                };
+                classified.push((token, kind));
+            }
+        }
+        classified
+    }
+
+    fn sort_completions(&self, mut completions: Vec<String>) -> Vec<String> {
+        // Builtins are listed after the application commands
+        completions
+            .sort_by(|a, b| (self.builtins.contains(a), a).cmp(&(self.builtins.contains(b), b)));
+        completions.dedup();
+        completions
+    }
+}
+
+/// Placeholder of the value of an arg, like `secs` or `secs=30` with a default
+fn placeholder(arg: &Arg) -> String {
+    let name = match arg.get_value_names() {
+        Some([name, ..]) => name.to_string(),
+        _ => arg.get_id().to_string(),
+    };
+    let defaults = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy())
+        .collect::<Vec<_>>();
+    if defaults.is_empty() {
+        name
+    } else {
+        format!("{name}={}", defaults.join(","))
+    }
+}
+
+/// Paths to complete, from the value hint of an arg
+///
+/// Clap infers the hint of `PathBuf` args.
+fn path_requirement(arg: &Arg) -> Option<PathRequirement> {
+    match arg.get_value_hint() {
+        ValueHint::DirPath => Some(PathRequirement::ExistingDir),
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::ExecutablePath => {
+            Some(PathRequirement::ExistingFile)
         }
+        _ => None,
     }
 }
 
+/// Paths starting with a prefix, quoted to be parsed back as typed
+///
+/// Paths with spaces or backslashes, like Windows paths, are
+/// single-quoted, as backslashes are escapes for the shell parser.
+fn complete_paths(prefix: &str, requirement: PathRequirement) -> Vec<String> {
+    suggest_paths(prefix, requirement)
+        .into_iter()
+        .map(|path| shell_words::quote(&path).into_owned())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TokenKind {
+    Command,
+    Flag,
+    FlagValue,
+    String,
+    Variable,
+}
+
+impl TokenKind {
+    fn color(self, theme: &Theme) -> Color {
+        match self {
+            TokenKind::Command => theme.command,
+            TokenKind::Flag => theme.flag,
+            TokenKind::FlagValue => theme.flag_value,
+            TokenKind::String => theme.string,
+            TokenKind::Variable => theme.variable,
+        }
+    }
+}
+
+/// Split a line into words and whitespace
+///
+/// Quoted whitespace is part of the words, and unterminated quotes
+/// extend until the end of the line.
+fn tokens(line: &str) -> Vec<(&str, bool)> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        let in_word = quote.is_some() || escaped || !c.is_whitespace();
+        let was_in_word = line[start..i]
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace());
+        if i > start && in_word != was_in_word {
+            tokens.push((&line[start..i], was_in_word));
+            start = i;
+        }
+        match (c, quote) {
+            _ if escaped => escaped = false,
+            ('\\', Some('"') | None) => escaped = true,
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    if start < line.len() {
+        let is_word = line[start..]
+            .chars()
+            .next()
+            .is_some_and(|c| !c.is_whitespace());
+        tokens.push((&line[start..], is_word));
+    }
+    tokens
+}
+
+/// If a line ends with whitespace that is not quoted or escaped
+fn ends_with_separator(line: &str) -> bool {
+    tokens(line).last().is_some_and(|(_, is_word)| !is_word)
+}
+
+/// Split a line into args
+///
+/// Called on every keystroke, so args are borrowed from the line
+/// unless they need to be unquoted. Unlike when running the line,
+/// unterminated quotes and escapes are accepted, as the line is
+/// still being typed.
+fn split_args(line: &str) -> Vec<Cow<'_, str>> {
+    tokens(line)
+        .into_iter()
+        .filter(|(_, is_word)| *is_word)
+        .take_while(|(word, _)| !word.starts_with('#'))
+        .map(|(word, _)| unquote(word))
+        .collect()
+}
+
+/// Remove the quotes and escapes of a word, like the shell-words parser
+fn unquote(word: &str) -> Cow<'_, str> {
+    if !word.contains(['"', '\'', '\\']) {
+        return Cow::Borrowed(word);
+    }
+    let mut unquoted = String::with_capacity(word.len());
+    let mut quote = None;
+    let mut chars = word.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', None) => unquoted.extend(chars.next()),
+            // Between double quotes, only some characters are escaped
+            ('\\', Some('"')) => match chars.peek() {
+                Some(&next @ ('"' | '\\' | '$' | '`' | '\n')) => {
+                    unquoted.push(next);
+                    chars.next();
+                }
+                _ => unquoted.push(c),
+            },
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (c, _) => unquoted.push(c),
+        }
+    }
+    Cow::Owned(unquoted)
+}
+
 impl Completer for ShellUi {
     type Candidate = String;
 
@@ -185,8 +523,38 @@ impl Completer for ShellUi {
 }
 
 impl Highlighter for ShellUi {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let theme = theme();
+        let mut highlighted = String::with_capacity(line.len());
+        for (token, kind) in self.classify(line) {
+            match kind {
+                Some(kind) => highlighted.push_str(&token.color(kind.color(&theme)).to_string()),
+                None => highlighted.push_str(token),
+            }
+        }
+        Cow::Owned(highlighted)
+    }
+
+    // Token classes change with each edit
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+
+    // Default values, like `=30` in `<secs=30>`, are dimmed
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
-        Cow::Owned(hint.white().dimmed().to_string())
+        let default = hint
+            .rfind('<')
+            .and_then(|start| Some(start + hint[start..].find('=')?))
+            .filter(|_| hint.ends_with('>'));
+        let Some(default) = default else {
+            return Cow::Owned(Message::hint(hint).as_formatted().into_owned());
+        };
+        Cow::Owned(format!(
+            "{}{}{}",
+            Message::hint(&hint[..default]).as_formatted(),
+            hint[default..hint.len() - 1].dimmed(),
+            Message::hint(">").as_formatted()
+        ))
     }
 }
 
@@ -218,7 +586,10 @@ impl Helper for ShellUi {}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use clap::Arg;
+    use crate::testing::test_dir;
+    use clap::ArgAction;
+    use std::fs;
+    use std::path::{PathBuf, MAIN_SEPARATOR_STR};
 
     #[test]
     fn test_solve_hint_partial() {
@@ -279,6 +650,22 @@ mod tests {
         assert_eq!(hint, Some(UiHint("<arg1>".to_string(), None)));
     }
 
+    #[test]
+    fn test_solve_hint_second_arg() {
+        let command = Command::new("test").subcommand(
+            Command::new("test1")
+                .arg(Arg::new("arg1"))
+                .arg(Arg::new("arg2")),
+        );
+        let ui = ShellUi::new(command);
+        assert_eq!(
+            ui.solve_hint("test1 value "),
+            Some(UiHint("<arg2>".to_string(), None))
+        );
+        assert_eq!(ui.solve_hint("test1 value value "), None);
+        assert_eq!(ui.solve_hint("unknown "), None);
+    }
+
     #[test]
     fn test_solve_complete_partial() {
         let command = Command::new("test")
@@ -306,9 +693,289 @@ mod tests {
             Some((
                 6,
                 vec![
-                    "help".to_string(),
                     "test11".to_string(),
-                    "test12".to_string()
+                    "test12".to_string(),
+                    "help".to_string()
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_solve_hint_config() {
+        let command = Command::new("test").subcommand(Command::new("test1"));
+        let ui = ShellUi::new(command.clone()).with_hints(Some(3));
+        assert_eq!(ui.solve_hint("te"), None);
+        assert_eq!(
+            ui.solve_hint("tes"),
+            Some(UiHint("t1".to_string(), Some("t1".to_string())))
+        );
+
+        let ui = ShellUi::new(command).with_hints(None);
+        assert_eq!(ui.solve_hint("tes"), None);
+    }
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args(" test1  arg "),
+            vec![Cow::Borrowed("test1"), Cow::Borrowed("arg")]
+        );
+        assert_eq!(
+            split_args("test1 'an arg'"),
+            vec![Cow::Borrowed("test1"), Cow::Borrowed("an arg")]
+        );
+        assert_eq!(
+            split_args("test1 'unterminated"),
+            vec!["test1", "unterminated"]
+        );
+        assert_eq!(
+            split_args(r#"test1 "a \"b\" \c"#),
+            vec!["test1", r#"a "b" \c"#]
+        );
+        assert_eq!(split_args(r"test1 an\ arg\"), vec!["test1", "an arg"]);
+        assert_eq!(split_args("test1 arg # comment"), vec!["test1", "arg"]);
+    }
+
+    #[test]
+    fn test_solve_partially_quoted() {
+        let command = Command::new("test")
+            .subcommand(Command::new("my command").subcommand(Command::new("sub")))
+            .subcommand(Command::new("other"));
+        let ui = ShellUi::new(command);
+        assert_eq!(
+            ui.solve_hint("'my co"),
+            Some(UiHint("mmand".to_string(), Some("mmand".to_string())))
+        );
+        assert_eq!(ui.solve_complete("other '", 7), Some((6, Vec::new())));
+        assert_eq!(
+            ui.solve_complete("'my command' ", 13),
+            Some((13, vec!["sub".to_string(), "help".to_string()]))
+        );
+        assert_eq!(
+            ui.solve_complete("'my ", 4),
+            Some((0, vec!["my command".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let mut ui = ShellUi::new(Command::new("test").subcommand(Command::new("test1")));
+        assert_eq!(ui.solve_hint("other"), None);
+
+        ui.rebuild(Command::new("test").subcommand(Command::new("other1")));
+        assert_eq!(
+            ui.solve_hint("other"),
+            Some(UiHint("1".to_string(), Some("1".to_string())))
+        );
+        assert_eq!(ui.solve_hint("test"), None);
+    }
+
+    #[test]
+    fn test_solve_hint_options() {
+        let command = Command::new("test").subcommand(
+            Command::new("fetch")
+                .arg(Arg::new("url"))
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .value_name("secs")
+                        .default_value("30"),
+                )
+                .arg(Arg::new("output").long("output"))
+                .arg(Arg::new("force").long("force").action(ArgAction::SetTrue)),
+        );
+        let ui = ShellUi::new(command);
+        let hint = |display: &str, completion: &str| {
+            Some(UiHint(display.to_string(), Some(completion.to_string())))
+        };
+        assert_eq!(
+            ui.solve_hint("fetch --ti"),
+            hint("meout <secs=30>", "meout")
+        );
+        assert_eq!(ui.solve_hint("fetch --out"), hint("put <output>", "put"));
+        assert_eq!(ui.solve_hint("fetch --fo"), hint("rce", "rce"));
+        assert_eq!(ui.solve_hint("fetch --unknown"), None);
+        assert_eq!(
+            ui.solve_hint("fetch --timeout "),
+            Some(UiHint("<secs=30>".to_string(), None))
+        );
+    }
+
+    #[test]
+    fn test_solve_complete_paths() {
+        let dir = test_dir("complete_paths");
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("file.txt"), "").unwrap();
+        let dir = format!("{}{MAIN_SEPARATOR_STR}", dir.display());
+
+        let command = Command::new("test").subcommand(
+            Command::new("copy")
+                .arg(Arg::new("source").value_parser(clap::value_parser!(PathBuf)))
+                .arg(Arg::new("target").value_hint(ValueHint::DirPath))
+                .arg(Arg::new("name"))
+                .arg(Arg::new("log").long("log").value_hint(ValueHint::FilePath)),
+        );
+        let ui = ShellUi::new(command);
+        let quote = |path: String| shell_words::quote(&path).into_owned();
+
+        let line = format!("copy {} ", quote(format!("{dir}f")));
+        let line = line.trim_end();
+        assert_eq!(
+            ui.solve_complete(line, line.len()),
+            Some((5, vec![quote(format!("{dir}file.txt"))]))
+        );
+        let line = format!("copy src --log {} '{dir}", quote(format!("{dir}file.txt")));
+        assert_eq!(
+            ui.solve_complete(&line, line.len()),
+            Some((
+                line.rfind('\'').unwrap(),
+                vec![quote(format!("{dir}sub dir{MAIN_SEPARATOR_STR}"))]
+            ))
+        );
+        let line = format!("copy --log '{dir}fi");
+        assert_eq!(
+            ui.solve_complete(&line, line.len()),
+            Some((11, vec![quote(format!("{dir}file.txt"))]))
+        );
+        let line = "copy source target n";
+        assert_eq!(ui.solve_complete(line, line.len()), None);
+    }
+
+    #[test]
+    fn test_solve_complete_help() {
+        let command = Command::new("test")
+            .subcommand(
+                Command::new("network")
+                    .subcommand(Command::new("create").arg(Arg::new("name")))
+                    .subcommand(Command::new("delete")),
+            )
+            .subcommand(Command::new("exit"));
+        let ui = ShellUi::new(command).with_builtins(&["exit"]);
+        let strings = |values: &[&str]| values.iter().map(ToString::to_string).collect();
+
+        assert_eq!(
+            ui.solve_complete("help ", 5),
+            Some((5, strings(&["network", "exit", "help"])))
+        );
+        assert_eq!(
+            ui.solve_complete("help network c", 14),
+            Some((13, strings(&["create"])))
+        );
+        assert_eq!(
+            ui.solve_complete("network help ", 13),
+            Some((13, strings(&["create", "delete", "help"])))
+        );
+        assert_eq!(
+            ui.solve_complete("help network create ", 20),
+            Some((20, Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens(" a  'b c'\\ d"),
+            vec![
+                (" ", false),
+                ("a", true),
+                ("  ", false),
+                ("'b c'\\ d", true)
+            ]
+        );
+        assert_eq!(
+            tokens("a \"b"),
+            vec![("a", true), (" ", false), ("\"b", true)]
+        );
+        assert!(tokens("").is_empty());
+    }
+
+    #[test]
+    fn test_classify() {
+        let command = Command::new("test").subcommand(
+            Command::new("network").subcommand(
+                Command::new("create")
+                    .arg(Arg::new("name"))
+                    .arg(Arg::new("output").short('o').long("output"))
+                    .arg(Arg::new("force").long("force").action(ArgAction::SetTrue)),
+            ),
+        );
+        let ui = ShellUi::new(command);
+        let kinds = |line| {
+            ui.classify(line)
+                .into_iter()
+                .filter(|(token, _)| !token.trim().is_empty())
+                .map(|(token, kind)| (token.to_string(), kind))
+                .collect::<Vec<_>>()
+        };
+        let token = |token: &str, kind| (token.to_string(), kind);
+        assert_eq!(
+            kinds("network create --force -o json \"my net\" $name"),
+            vec![
+                token("network", Some(TokenKind::Command)),
+                token("create", Some(TokenKind::Command)),
+                token("--force", Some(TokenKind::Flag)),
+                token("-o", Some(TokenKind::Flag)),
+                token("json", Some(TokenKind::FlagValue)),
+                token("\"my net\"", Some(TokenKind::String)),
+                token("$name", Some(TokenKind::Variable)),
+            ]
+        );
+        assert_eq!(
+            kinds("network create --output=json create"),
+            vec![
+                token("network", Some(TokenKind::Command)),
+                token("create", Some(TokenKind::Command)),
+                token("--output", Some(TokenKind::Flag)),
+                token("=", None),
+                token("json", Some(TokenKind::FlagValue)),
+                token("create", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hidden() {
+        let command = Command::new("test")
+            .subcommand(Command::new("list"))
+            .subcommand(Command::new("lab").hide(true).arg(Arg::new("feature")));
+        let ui = ShellUi::new(command.clone());
+        assert_eq!(
+            ui.solve_complete("l", 1),
+            Some((0, vec!["list".to_string()]))
+        );
+        assert_eq!(ui.solve_hint("la"), None);
+        assert_eq!(
+            ui.solve_hint("lab "),
+            Some(UiHint("<feature>".to_string(), None))
+        );
+
+        let ui = ShellUi::new(command).with_expert(true);
+        assert_eq!(
+            ui.solve_complete("l", 1),
+            Some((0, vec!["lab".to_string(), "list".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_solve_complete_sorted_deduplicated() {
+        let command = Command::new("test")
+            .subcommand(Command::new("exit"))
+            .subcommand(Command::new("beta"))
+            .subcommand(Command::new("alpha"))
+            .subcommand(Command::new("alpha"));
+        let complete = ShellUi::new(command)
+            .with_builtins(&["exit"])
+            .solve_complete("", 0);
+        assert_eq!(
+            complete,
+            Some((
+                0,
+                vec![
+                    "alpha".to_string(),
+                    "beta".to_string(),
+                    "exit".to_string(),
+                    "help".to_string()
                 ]
             ))
         );
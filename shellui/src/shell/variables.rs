@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+/// Variables of a shell, set with `name=$(command)`
+///
+/// Variables are expanded with `$name` or `${name}`, except between single
+/// quotes. Unknown variables are kept as is.
+#[derive(Debug, Default)]
+pub(super) struct Variables {
+    values: BTreeMap<String, String>,
+}
+
+impl Variables {
+    /// Set a variable to the output of a command, without surrounding whitespace
+    pub(super) fn set(&mut self, name: &str, output: &str) {
+        self.values
+            .insert(name.to_string(), output.trim().to_string());
+    }
+
+    /// Expand the variables of a line
+    ///
+    /// Values are escaped so that they are not parsed again as shell syntax,
+    /// and each value is kept as a single word.
+    pub(super) fn expand(&self, line: &str) -> String {
+        let mut expanded = String::with_capacity(line.len());
+        let mut quote = None;
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            match (c, quote) {
+                ('\'' | '"', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                ('\\', Some('\'')) => {}
+                ('\\', _) => {
+                    // Keep escaped characters, including an escaped `$`
+                    let len = rest[1..].chars().next().map_or(0, char::len_utf8);
+                    expanded.push_str(&rest[..len + 1]);
+                    rest = &rest[len + 1..];
+                    continue;
+                }
+                ('$', None | Some('"')) => {
+                    if let Some((name, len)) = variable_at(rest) {
+                        if let Some(value) = self.values.get(name) {
+                            push_value(&mut expanded, value, quote.is_some());
+                            rest = &rest[len..];
+                            continue;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            expanded.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        expanded
+    }
+}
+
+/// Parse an assignment, like `ids=$(list --ids-only)`
+///
+/// Returns the name of the variable and the command line.
+pub(super) fn parse_assignment(line: &str) -> Option<(&str, &str)> {
+    let (name, value) = line.trim().split_once('=')?;
+    let command = value.strip_prefix("$(")?.strip_suffix(')')?;
+    is_name(name).then_some((name, command))
+}
+
+/// Push an escaped value, either inside double quotes or as a quoted word
+fn push_value(expanded: &mut String, value: &str, in_double_quotes: bool) {
+    if in_double_quotes {
+        for c in value.chars() {
+            if matches!(c, '"' | '\\' | '$' | '`') {
+                expanded.push('\\');
+            }
+            expanded.push(c);
+        }
+    } else {
+        expanded.push_str(&shell_words::quote(value));
+    }
+}
+
+/// Name and length of the variable starting a string, like `$name` or `${name}`
+fn variable_at(text: &str) -> Option<(&str, usize)> {
+    let text = text.strip_prefix('$')?;
+    if let Some(text) = text.strip_prefix('{') {
+        let (name, _) = text.split_once('}')?;
+        return is_name(name).then_some((name, name.len() + 3));
+    }
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    let name = &text[..len];
+    is_name(name).then_some((name, len + 1))
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_assignment() {
+        assert_eq!(
+            parse_assignment("ids=$(list --ids-only)"),
+            Some(("ids", "list --ids-only"))
+        );
+        assert_eq!(parse_assignment("ids=list"), None);
+        assert_eq!(parse_assignment("1d=$(list)"), None);
+        assert_eq!(parse_assignment("config set key=$(value)"), None);
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut variables = Variables::default();
+        variables.set("ids", "1 2\n");
+        variables.set("name", "api");
+
+        assert_eq!(variables.expand("delete $ids"), "delete '1 2'");
+        assert_eq!(variables.expand("deploy ${name}_v2"), "deploy api_v2");
+        assert_eq!(variables.expand("echo '$name' $name"), "echo '$name' api");
+        assert_eq!(variables.expand("echo $unknown $"), "echo $unknown $");
+        assert_eq!(variables.expand("echo \\$name"), "echo \\$name");
+    }
+
+    #[test]
+    fn test_expand_special_characters() {
+        let mut variables = Variables::default();
+        variables.set("name", "O'Brien #1");
+        variables.set("quoted", "say \"hi\" \\ $HOME");
+
+        let split = |line: &str| shell_words::split(&variables.expand(line)).unwrap();
+        assert_eq!(split("greet $name"), ["greet", "O'Brien #1"]);
+        assert_eq!(split("greet \"$name\""), ["greet", "O'Brien #1"]);
+        assert_eq!(split("greet '$name'"), ["greet", "$name"]);
+        assert_eq!(split("echo \"$quoted\""), ["echo", "say \"hi\" \\ $HOME"]);
+        assert_eq!(split("echo ${name}_v2"), ["echo", "O'Brien #1_v2"]);
+    }
+}
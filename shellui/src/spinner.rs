@@ -0,0 +1,158 @@
+//! Spinner displayed while a shell command runs
+//!
+//! The spinner is drawn on the prompt line by a background thread, with the
+//! time elapsed since the command started. It is cleared and stopped as soon
+//! as the command prints something, or prompts for an input: the output
+//! and prompt functions call `stop` before writing to the terminal.
+use crate::format::{is_capturing, theme, Icons};
+use colored::Colorize;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Commands faster than this delay do not display the spinner
+const DELAY: Duration = Duration::from_millis(300);
+const INTERVAL: Duration = Duration::from_millis(100);
+const FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const ASCII_FRAMES: &[&str] = &["|", "/", "-", "\\"];
+
+static STATE: State = State::new();
+
+/// State of the spinner, shared with its drawing thread
+struct State {
+    running: AtomicBool,
+    /// Set while the spinner is displayed, locked while drawing
+    drawn: Mutex<bool>,
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            running: AtomicBool::new(false),
+            drawn: Mutex::new(false),
+        }
+    }
+
+    /// Draw a frame, unless the spinner was stopped
+    fn draw<W>(&self, out: &mut W, frame: &str, elapsed: Duration) -> bool
+    where
+        W: Write,
+    {
+        let mut drawn = self.drawn.lock().unwrap();
+        if !self.running.load(Ordering::SeqCst) {
+            return false;
+        }
+        let elapsed = format!("{:.1}s", elapsed.as_secs_f64());
+        let _ = write!(out, "\r\x1b[2K{frame} {}", elapsed.dimmed());
+        let _ = out.flush();
+        *drawn = true;
+        true
+    }
+
+    fn stop<W>(&self, out: &mut W)
+    where
+        W: Write,
+    {
+        if !self.running.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        let mut drawn = self.drawn.lock().unwrap();
+        if *drawn {
+            let _ = write!(out, "\r\x1b[2K");
+            let _ = out.flush();
+            *drawn = false;
+        }
+    }
+}
+
+/// Running spinner, stopped when dropped
+pub(crate) struct Spinner(Option<JoinHandle<()>>);
+
+impl Spinner {
+    /// Start the spinner, if enabled and if stderr is a terminal
+    pub(crate) fn start(enabled: bool) -> Self {
+        if !enabled || is_capturing() || !io::stderr().is_terminal() {
+            return Spinner(None);
+        }
+        let frames = match theme().icons {
+            Icons::Ascii => ASCII_FRAMES,
+            Icons::None | Icons::Unicode => FRAMES,
+        };
+
+        STATE.running.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        let handle = thread::spawn(move || {
+            thread::park_timeout(DELAY);
+            for frame in frames.iter().cycle() {
+                if !STATE.draw(&mut io::stderr(), frame, start.elapsed()) {
+                    break;
+                }
+                thread::park_timeout(INTERVAL);
+            }
+        });
+        Spinner(Some(handle))
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(handle) = self.0.take() {
+            stop();
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Stop the spinner, clearing its line
+///
+/// Must be called before writing to the terminal or prompting, as the
+/// spinner would draw over the output.
+pub(crate) fn stop() {
+    STATE.stop(&mut io::stderr());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spinner_disabled() {
+        assert!(Spinner::start(false).0.is_none());
+        // Tests do not run in a terminal
+        assert!(Spinner::start(true).0.is_none());
+    }
+
+    #[test]
+    fn test_spinner_draw_and_stop() {
+        let state = State::new();
+        let mut out = Vec::new();
+        assert!(!state.draw(&mut out, "|", Duration::from_millis(500)));
+        assert!(out.is_empty());
+
+        state.running.store(true, Ordering::SeqCst);
+        assert!(state.draw(&mut out, "|", Duration::from_millis(500)));
+        let drawn = String::from_utf8(out).unwrap();
+        assert!(drawn.starts_with("\r\x1b[2K| "));
+        assert!(drawn.contains("0.5s"));
+
+        // The line is cleared once, and nothing is drawn after stopping
+        let mut out = Vec::new();
+        state.stop(&mut out);
+        state.stop(&mut out);
+        assert!(!state.draw(&mut out, "/", Duration::from_millis(600)));
+        assert_eq!(out, b"\r\x1b[2K");
+    }
+
+    #[test]
+    fn test_spinner_stop_before_drawing() {
+        // Fast commands stop the spinner before its first frame
+        let state = State::new();
+        state.running.store(true, Ordering::SeqCst);
+        let mut out = Vec::new();
+        state.stop(&mut out);
+        assert!(out.is_empty());
+    }
+}
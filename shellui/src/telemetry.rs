@@ -0,0 +1,170 @@
+//! Usage metrics
+//!
+//! Each command run from the shell or as a CLI is recorded with its name,
+//! duration and outcome. Only the names of the commands and subcommands are
+//! recorded, never their arguments. Records are summarized by the `stats`
+//! builtin, and are delivered to the `TelemetrySink` of `Context::telemetry`,
+//! if the application opts in.
+use crate::errors::ShellUiResult;
+use crate::format::ObjectFormatter;
+use crate::Context;
+use clap::ArgMatches;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static SESSION: Mutex<SessionStats> = Mutex::new(SessionStats::new());
+
+/// A command that was run
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommandEvent {
+    /// Command and subcommand names, like `remote add`
+    pub command: String,
+    pub duration: Duration,
+    pub success: bool,
+}
+
+/// Receiver of command events
+pub trait TelemetrySink {
+    fn record(&self, event: &CommandEvent);
+}
+
+/// Statistics of a command over the session
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CommandStats {
+    pub command: String,
+    pub count: usize,
+    pub failures: usize,
+    pub total_duration: Duration,
+}
+
+impl CommandStats {
+    pub fn average_duration(&self) -> Duration {
+        match u32::try_from(self.count) {
+            Ok(count) if count > 0 => self.total_duration / count,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+impl ObjectFormatter for CommandStats {
+    type Header = &'static str;
+    type Mode = ();
+    type Output<'a> = Cow<'a, str>;
+
+    fn headers(_mode: Option<()>) -> Vec<Self::Header> {
+        vec!["command", "count", "failures", "average"]
+    }
+
+    fn format_value(&self, _mode: Option<()>, header: &Self::Header) -> Cow<'_, str> {
+        match *header {
+            "command" => Cow::Borrowed(&self.command),
+            "count" => Cow::Owned(self.count.to_string()),
+            "failures" => Cow::Owned(self.failures.to_string()),
+            "average" => Cow::Owned(format!("{} ms", self.average_duration().as_millis())),
+            _ => Cow::Borrowed(""),
+        }
+    }
+}
+
+/// Statistics of the commands run in a session
+#[derive(Clone, Debug, Default)]
+pub struct SessionStats {
+    commands: BTreeMap<String, CommandStats>,
+}
+
+impl SessionStats {
+    pub const fn new() -> Self {
+        SessionStats {
+            commands: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: &CommandEvent) {
+        let stats = self
+            .commands
+            .entry(event.command.clone())
+            .or_insert_with(|| CommandStats {
+                command: event.command.clone(),
+                ..CommandStats::default()
+            });
+        stats.count += 1;
+        if !event.success {
+            stats.failures += 1;
+        }
+        stats.total_duration += event.duration;
+    }
+
+    /// Statistics per command, sorted by command name
+    pub fn commands(&self) -> Vec<CommandStats> {
+        self.commands.values().cloned().collect()
+    }
+}
+
+/// Statistics of the commands run in the current session
+pub fn session_stats() -> SessionStats {
+    SESSION.lock().unwrap().clone()
+}
+
+/// Name of the command being run, without its arguments
+pub(crate) fn command_name(matches: &ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, subcommand)) = matches.subcommand() {
+        names.push(name);
+        matches = subcommand;
+    }
+    names.join(" ")
+}
+
+/// Record a command that started at `start`
+pub(crate) fn record<X>(context: &X, command: String, start: Instant, result: &ShellUiResult<()>)
+where
+    X: Context,
+{
+    let event = CommandEvent {
+        command,
+        duration: start.elapsed(),
+        success: result.is_ok(),
+    };
+    SESSION.lock().unwrap().record(&event);
+    if let Some(sink) = context.telemetry() {
+        sink.record(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, Command};
+
+    #[test]
+    fn test_session_stats() {
+        let mut stats = SessionStats::new();
+        for (command, millis, success) in [("b", 10, true), ("a", 20, true), ("b", 30, false)] {
+            stats.record(&CommandEvent {
+                command: command.to_string(),
+                duration: Duration::from_millis(millis),
+                success,
+            });
+        }
+        let commands = stats.commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].command, "a");
+        assert_eq!(commands[1].count, 2);
+        assert_eq!(commands[1].failures, 1);
+        assert_eq!(commands[1].average_duration(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_command_name() {
+        let command = Command::new("test").subcommand(
+            Command::new("remote").subcommand(Command::new("add").arg(Arg::new("name"))),
+        );
+        let matches = command
+            .try_get_matches_from(["test", "remote", "add", "secret"])
+            .unwrap();
+        assert_eq!(command_name(&matches), "remote add");
+    }
+}
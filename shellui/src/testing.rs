@@ -0,0 +1,297 @@
+//! Testing helpers
+//!
+//! Run shell sessions programmatically, without a terminal, to
+//! integration-test applications.
+use crate::format::{start_capture, stop_capture, strip_colors};
+use crate::shell::{run_line, ActiveConfig, LineParser, ShellAction};
+use crate::{Context, ShellParser};
+#[cfg(test)]
+use std::path::PathBuf;
+#[cfg(test)]
+use std::{env, fs, process};
+
+/// Shell session driven by scripted lines
+///
+/// Lines go through the same parsing and dispatch as the interactive shell,
+/// and the output of shellui's printing functions is captured. Colors are
+/// stripped from the output, and lifecycle hooks are not called.
+pub struct ShellTester<T>
+where
+    T: ShellParser,
+{
+    context: T::Context,
+    parser: LineParser<T::Commands, T::Context>,
+}
+
+impl<T> ShellTester<T>
+where
+    T: ShellParser,
+{
+    pub fn new(context: T::Context) -> Self {
+        let parser = LineParser::new(&context);
+        ShellTester { context, parser }
+    }
+
+    /// Run a line, as if typed in the shell
+    pub fn run(&mut self, line: &str) -> ShellOutput {
+        let _config = ActiveConfig::install(self.context.shell_config());
+        start_capture();
+        let action = run_line::<T>(&mut self.context, &mut self.parser, line);
+        let capture = stop_capture();
+
+        let mut output = ShellOutput {
+            stdout: strip_colors(&capture.stdout).into_owned(),
+            stderr: strip_colors(&capture.stderr).into_owned(),
+            exited: false,
+            exit_code: 0,
+        };
+        match action {
+            Ok(ShellAction::None | ShellAction::ClearScreen | ShellAction::ClearAll) => {}
+            Ok(ShellAction::Exit(code)) => {
+                output.exited = true;
+                output.exit_code = code;
+            }
+            Err(error) => {
+                // The shell exits on unexpected errors
+                output.stderr.push_str(&format!("{error}\n"));
+                output.exited = true;
+            }
+        }
+        output
+    }
+
+    pub fn context(&self) -> &T::Context {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut T::Context {
+        &mut self.context
+    }
+}
+
+/// Empty temporary directory, unique to a test and to the test process
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("shellui_test_{name}_{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Output of a line run by `ShellTester`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ShellOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// If the shell would exit after this line
+    pub exited: bool,
+    /// Exit code passed to `exit`
+    pub exit_code: u8,
+}
+
+impl ShellOutput {
+    #[track_caller]
+    pub fn assert_stdout_contains(&self, expected: &str) -> &Self {
+        assert!(
+            self.stdout.contains(expected),
+            "stdout does not contain {expected:?}: {:?}",
+            self.stdout
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_stderr_contains(&self, expected: &str) -> &Self {
+        assert!(
+            self.stderr.contains(expected),
+            "stderr does not contain {expected:?}: {:?}",
+            self.stderr
+        );
+        self
+    }
+
+    /// Assert that nothing was printed to stderr
+    #[track_caller]
+    pub fn assert_no_error(&self) -> &Self {
+        assert!(
+            self.stderr.is_empty(),
+            "stderr is not empty: {:?}",
+            self.stderr
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{ShellUiError, ShellUiResult};
+    use crate::format::{print_stdout, AsFormatted, Message};
+    use crate::registry::CommandRegistry;
+    use crate::Context;
+    use clap::{Arg, Command};
+    use clap::{Parser, Subcommand};
+    use std::io::Result;
+    use std::path::PathBuf;
+
+    #[derive(Default)]
+    struct TestContext {
+        count: usize,
+        jobs: usize,
+        registry: CommandRegistry<TestContext>,
+    }
+
+    impl Context for TestContext {
+        fn new() -> Result<Self> {
+            Ok(TestContext::default())
+        }
+
+        fn history_path(&self) -> Option<PathBuf> {
+            None
+        }
+
+        fn registry(&self) -> Option<&CommandRegistry<Self>> {
+            Some(&self.registry)
+        }
+
+        fn running_jobs(&self) -> usize {
+            self.jobs
+        }
+    }
+
+    #[derive(Parser)]
+    struct TestCli {
+        #[command(subcommand)]
+        command: Option<TestCommand>,
+    }
+
+    #[derive(Clone, Subcommand)]
+    enum TestCommand {
+        Hello { name: String },
+        Fail,
+    }
+
+    impl ShellParser for TestCli {
+        type Context = TestContext;
+        type Commands = TestCommand;
+
+        fn try_get_command(self) -> Option<Self::Commands> {
+            self.command
+        }
+
+        fn run_command(context: &mut Self::Context, command: &Self::Commands) -> ShellUiResult<()> {
+            context.count += 1;
+            match command {
+                TestCommand::Hello { name } => {
+                    Message::new(format!("Hello {name}")).print_formatted();
+                    Ok(())
+                }
+                TestCommand::Fail => Err(ShellUiError::warning("Failed")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_shell_tester() {
+        let mut tester = ShellTester::<TestCli>::new(TestContext::default());
+        tester
+            .run("hello world")
+            .assert_stderr_contains("Hello world");
+        tester.run("fail").assert_stderr_contains("Failed");
+        tester
+            .run("unknown")
+            .assert_stderr_contains("Unknown command \"unknown\"");
+        tester
+            .run("helo world")
+            .assert_stderr_contains("did you mean \"hello\"");
+        tester
+            .run("bookmark list")
+            .assert_stderr_contains("Bookmarks are not available");
+        assert!(!tester.run("").exited);
+        tester.run("clear --all").assert_no_error();
+        assert!(tester.run("exit").exited);
+        assert_eq!(tester.run("exit 3").exit_code, 3);
+        assert_eq!(tester.context().count, 2);
+    }
+
+    #[test]
+    fn test_shell_tester_exit_jobs() {
+        let context = TestContext {
+            jobs: 2,
+            ..TestContext::default()
+        };
+        let mut tester = ShellTester::<TestCli>::new(context);
+        let output = tester.run("exit");
+        output.assert_stderr_contains("2 jobs still running");
+        assert!(!output.exited);
+        assert!(tester.run("exit --force").exited);
+    }
+
+    #[test]
+    fn test_shell_tester_sessions() {
+        let mut tester = ShellTester::<TestCli>::new(TestContext::default());
+        tester.run("hello world");
+        tester.run("session new other").assert_no_error();
+        assert_eq!(tester.context().count, 0);
+        tester
+            .run("session list")
+            .assert_stdout_contains("  default\n* other");
+        tester
+            .run("session new default")
+            .assert_stderr_contains("Session default already exists");
+        tester
+            .run("session switch unknown")
+            .assert_stderr_contains("Unknown session unknown");
+        tester.run("session switch default").assert_no_error();
+        assert_eq!(tester.context().count, 1);
+    }
+
+    #[test]
+    fn test_shell_tester_registered() {
+        let mut context = TestContext::default();
+        context.registry.register(
+            Command::new("add").arg(Arg::new("value").required(true)),
+            |context, matches| {
+                let value = matches.get_one::<String>("value").unwrap();
+                context.count += value.parse::<usize>().unwrap();
+                Ok(())
+            },
+        );
+
+        let mut tester = ShellTester::<TestCli>::new(context);
+        tester.run("add 3").assert_no_error();
+        tester
+            .run("add")
+            .assert_stderr_contains("required arguments were not provided: <value>")
+            .assert_stderr_contains("usage: add <value>")
+            .assert_stderr_contains("Run \"help add\" for more information");
+        tester.run("help").assert_stdout_contains("add");
+        assert_eq!(tester.context().count, 3);
+    }
+
+    #[test]
+    fn test_shell_tester_variables() {
+        let mut context = TestContext::default();
+        context.registry.register(Command::new("answer"), |_, _| {
+            print_stdout("42\n");
+            Ok(())
+        });
+        context.registry.register(
+            Command::new("add").arg(Arg::new("value").required(true)),
+            |context, matches| {
+                let value = matches.get_one::<String>("value").unwrap();
+                context.count += value.parse::<usize>().unwrap();
+                Ok(())
+            },
+        );
+
+        let mut tester = ShellTester::<TestCli>::new(context);
+        let output = tester.run("value=$(answer)");
+        assert_eq!(output.stdout, "");
+        tester.run("add $value").assert_no_error();
+        tester
+            .run("hidden=$(hello world)")
+            .assert_stderr_contains("Hello world");
+        assert_eq!(tester.context().count, 43);
+    }
+}
@@ -0,0 +1,109 @@
+//! Update check
+//!
+//! The check of `Context::update_check` runs at most once per day, in a
+//! background thread started with the shell. The time of the last check is
+//! stored next to the history file.
+use crate::format::{AsFormatted, Message};
+use crate::i18n::tr_args;
+use crate::Context;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Pending update check
+pub(crate) struct UpdateCheck(Option<Receiver<Option<String>>>);
+
+impl UpdateCheck {
+    /// Start the update check if it is due
+    pub(crate) fn start<X>(context: &X) -> Self
+    where
+        X: Context,
+    {
+        let Some(path) = check_path(context) else {
+            return UpdateCheck(None);
+        };
+        let now = SystemTime::now();
+        if !is_due(&path, now) {
+            return UpdateCheck(None);
+        }
+        let Some(check) = context.update_check() else {
+            return UpdateCheck(None);
+        };
+        // Failing to persist the time only makes the check run again
+        let _ = write_last_check(&path, now);
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(check()));
+        UpdateCheck(Some(receiver))
+    }
+
+    /// Print the upgrade hint if the check found a newer version
+    pub(crate) fn print_notice(&mut self) {
+        let Some(receiver) = &self.0 else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(version) => {
+                if let Some(version) = version {
+                    Message::hint(tr_args(
+                        "shellui-update-available",
+                        "A new version is available: { $version }",
+                        &[("version", &version)],
+                    ))
+                    .print_formatted();
+                }
+                self.0 = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.0 = None,
+        }
+    }
+}
+
+fn check_path<X>(context: &X) -> Option<PathBuf>
+where
+    X: Context,
+{
+    let mut path = context.history_path()?.into_os_string();
+    path.push(".update-check");
+    Some(PathBuf::from(path))
+}
+
+fn is_due(path: &Path, now: SystemTime) -> bool {
+    let last_check = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u64>().ok())
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+    match last_check {
+        Some(last_check) => now
+            .duration_since(last_check)
+            .map_or(true, |elapsed| elapsed >= CHECK_INTERVAL),
+        None => true,
+    }
+}
+
+fn write_last_check(path: &Path, now: SystemTime) -> std::io::Result<()> {
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    fs::write(path, secs.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::test_dir;
+
+    #[test]
+    fn test_is_due() {
+        let path = test_dir("update").join("history.update-check");
+
+        let now = SystemTime::now();
+        assert!(is_due(&path, now));
+        write_last_check(&path, now).unwrap();
+        assert!(!is_due(&path, now + Duration::from_secs(60)));
+        assert!(is_due(&path, now + CHECK_INTERVAL));
+    }
+}